@@ -2,9 +2,47 @@ tasks = Tasks
 trash = Trash
 about = About
 
+# Smart Lists
+today = Today
+favorites = Favorites
+scheduled = Scheduled
+all = All
+completed = Completed
+
 # Content
 add-new-task = Add new task
 
+# Notifications
+complete = Complete
+snooze = Snooze
+
+# Diagnostics
+quarantined-files = { $count ->
+    [one] One damaged file could not be read and was moved to quarantine
+   *[other] {$count} damaged files could not be read and were moved to quarantine
+}
+
+# Undo
+task-deleted = Task deleted
+list-deleted = Deleted “{$list}”
+
+# Errors
+error-loading = Could not load your tasks: {$error}
+error-saving = Could not save your changes: {$error}
+retry = Retry
+
+# Search
+search-tasks = Search tasks
+no-results = No results
+no-results-suggestion = Try searching for other words
+tag = Tag
+sub-task = Sub-task
+
+# Trash
+trash-empty = Trash is empty
+trash-empty-suggestion = Deleted tasks will show up here
+deleted-from = {$list} · Deleted {$date}
+
 # Details
 title = Title
 details = Details
@@ -12,6 +50,19 @@ favorite = Favorite
 priority = Priority
 due-date = Due date
 reminder = Reminder
+no-reminder = No reminder
+reminder-off = Off
+reminder-in-an-hour = In an hour
+reminder-tomorrow = Tomorrow morning
+reminder-next-week = Next week
+repeat = Repeat
+monday = Mon
+tuesday = Tue
+wednesday = Wed
+thursday = Thu
+friday = Fri
+saturday = Sat
+sunday = Sun
 notes = Notes
 
 # Empty
@@ -43,6 +94,34 @@ select-date = Select a date
 
 # Export Dialog
 export = Export
+export-details = Include dates, priority, tags and notes
+save-as = Save as...
+icalendar = iCalendar files
+todo-txt = todo.txt files
+markdown = Markdown files
+file-saved = Saved to {$path}
+
+# Import Dialog
+import = Import tasks
+import-hint = Choose a file or paste text copied from another app, such as a list exported as Markdown.
+import-body = { $count ->
+    [one] Found 1 task.
+   *[other] Found {$count} tasks.
+}
+paste = Paste
+import-into = Import into
+import-confirm = Import
+imported-list = Imported tasks
+tasks-imported = { $count ->
+    [one] Imported 1 task
+   *[other] Imported {$count} tasks
+}
+
+# Conflict Dialog
+conflict = This task was changed somewhere else
+conflict-body = “{$task}” was changed in another window or application while you were editing it. Which version do you want to keep?
+keep-mine = Keep mine
+keep-theirs = Keep theirs
 
 # Dialogs
 cancel = Cancel
@@ -70,18 +149,52 @@ match-desktop = Match desktop
 dark = Dark
 light = Light
 
+### Storage
+storage = Storage
+provider = Provider
+copy-from = Copy data from {$provider}
+preview = Preview
+trash-retention = Empty trash after
+caldav-account = CalDAV account
+server-address = Server address
+username = User name
+password = Password
+connect = Connect
+todo-txt-files = todo.txt files
+todo-txt-file = Tasks file
+done-txt-file = Completed tasks file
+choose-file = Choose...
+never = Never
+days = { $count ->
+    [one] 1 day
+   *[other] {$count} days
+}
+
+# Copy Data Dialog
+copy-data = Copy data
+copy-data-confirm = Copy
+copy-data-up-to-date = Everything in {$provider} is already here.
+copy-data-summary = Copying from {$provider} will create {$lists_created} lists and {$tasks_created} tasks, and update {$lists_updated} lists and {$tasks_updated} tasks. Lists and tasks that only exist here are kept.
+
 # Menu
 
 ## File
 file = File
 new-window = New window
 new-list = New list
+menu-import = Import...
+menu-export-all = Export all lists...
+menu-export-folder = Export lists to a folder...
 quit = Quit
 
 ## Edit
 edit = Edit
+undo = Undo
+redo = Redo
+search = Search
 rename = Rename
 delete = Delete
+set-default-list = Use as default list
 
 ## View
 view = View
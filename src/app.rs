@@ -1,41 +1,62 @@
 use std::any::TypeId;
 use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
 use std::{env, process};
 
-use chrono::{Local, NaiveDate};
+use chrono::{Duration, Local, NaiveDate, Utc};
 use cli_clipboard::{ClipboardContext, ClipboardProvider};
 use cosmic::app::{message, Core, Message as CosmicMessage};
 use cosmic::cosmic_config::Update;
 use cosmic::cosmic_theme::ThemeMode;
 use cosmic::iced::alignment::{Horizontal, Vertical};
+use cosmic::iced::futures::{future, SinkExt, StreamExt};
 use cosmic::iced::keyboard::{Key, Modifiers};
 use cosmic::iced::{
-    event, keyboard::Event as KeyEvent, window, Alignment, Event, Length, Subscription,
+    event, keyboard::Event as KeyEvent, subscription, window, Alignment, Event, Length,
+    Subscription,
 };
 use cosmic::widget::menu::action::MenuAction;
 use cosmic::widget::menu::key_bind::KeyBind;
-use cosmic::widget::segmented_button::{Entity, EntityMut, SingleSelect};
+use cosmic::widget::segmented_button::Entity;
 use cosmic::widget::{horizontal_space, scrollable, segmented_button};
 use cosmic::{
-    app, cosmic_config, cosmic_theme, executor, theme, widget, Application, ApplicationExt,
+    app, cosmic_config, cosmic_theme, executor, theme, widget, Application, ApplicationExt, Apply,
     Command, Element,
 };
+use tasks_core::conflict::Conflict;
+use tasks_core::dbus::{Signal, TasksServer};
+use tasks_core::import::ImportedList;
+use tasks_core::markdown::Detail;
+use tasks_core::migration::MigrationSummary;
+use tasks_core::models::diagnostic::Diagnostic;
 use tasks_core::models::list::List;
+use tasks_core::models::smart_list::SmartList;
 use tasks_core::models::task::Task;
+use tasks_core::reminders::{ReminderEvent, ReminderScheduler};
+use tasks_core::search::SearchIndex;
 use tasks_core::service::{Provider, TaskService};
+use tasks_core::services::caldav::CalDavAccount;
+use tasks_core::services::todo_txt::TodoTxtFiles;
+use tasks_core::watch::{StorageChange, StorageWatcher};
 
 use crate::app::config::{AppTheme, CONFIG_VERSION};
+use crate::app::history::{Change, History};
+use crate::app::icon_cache::IconCache;
 use crate::app::key_bind::key_binds;
+use crate::app::operation::Operation;
 use crate::content::Content;
 use crate::details::Details;
-use crate::{content, details, fl, todo};
+use crate::search::Search;
+use crate::trash::Trash;
+use crate::{content, details, fl, search, todo, trash};
 
 pub mod config;
+pub mod history;
 pub mod icon_cache;
 mod key_bind;
 pub mod localize;
-pub mod markdown;
 pub mod menu;
+pub mod operation;
 pub mod settings;
 
 pub struct Tasks {
@@ -44,24 +65,55 @@ pub struct Tasks {
     nav_model: segmented_button::SingleSelectModel,
     content: Content,
     details: Details,
+    trash: Trash,
+    search: Search,
+    search_query: String,
+    search_input: widget::Id,
+    search_index: Option<SearchIndex>,
+    indexing: bool,
+    history: History,
+    /// Tasks with a save on its way, along with the latest edit made since,
+    /// which is saved once the one on its way is done.
+    saving: HashMap<String, Option<Task>>,
+    caldav_account: CalDavAccount,
+    todo_txt_files: TodoTxtFiles,
+    toast: Option<Toast>,
+    toast_count: usize,
+    server: Option<TasksServer>,
     config_handler: Option<cosmic_config::Config>,
     config: config::TasksConfig,
     app_themes: Vec<String>,
+    providers: Vec<String>,
+    trash_retentions: Vec<String>,
     context_page: ContextPage,
     key_binds: HashMap<KeyBind, Action>,
     modifiers: Modifiers,
     dialog_pages: VecDeque<DialogPage>,
     dialog_text_input: widget::Id,
+    import_lists: Vec<List>,
+    import_targets: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     Content(content::Message),
     Details(details::Message),
+    Trash(trash::Message),
+    Search(search::Message),
+    SearchInput(String),
+    ClearSearch,
+    FocusSearch,
+    SetSearchIndex(SearchIndex),
     ToggleContextPage(ContextPage),
     LaunchUrl(String),
     FetchLists,
+    ReloadLists,
     PopulateLists(Vec<List>),
+    SetTasks(Vec<Task>, Vec<Diagnostic>),
+    FetchDiagnostics,
+    SetDiagnostics(Vec<Diagnostic>),
+    DismissDiagnostics,
     WindowClose,
     WindowNew,
     DialogCancel,
@@ -70,20 +122,89 @@ pub enum Message {
     Key(Modifiers, Key),
     Modifiers(Modifiers),
     AppTheme(usize),
+    Provider(usize),
+    CalDavUrl(String),
+    CalDavUsername(String),
+    CalDavPassword(String),
+    SaveCalDavAccount,
+    ChooseTodoTxt,
+    ChooseDoneTxt,
+    SetTodoTxt(PathBuf),
+    SetDoneTxt(PathBuf),
+    SaveTodoTxtFiles,
+    TrashRetention(usize),
+    PurgeTrash,
+    PreviewMigration(Provider),
+    OpenMigrationDialog(Provider, MigrationSummary),
     SystemThemeModeChange,
     OpenNewListDialog,
     OpenRenameListDialog,
     OpenDeleteListDialog,
     OpenIconDialog,
     OpenCalendarDialog,
-    OpenExportDialog(String),
+    OpenExportDialog(List, Vec<Task>),
+    PickImportFile,
+    PasteImport,
+    OpenImportDialog(Vec<ImportedList>),
+    Imported(usize, bool),
+    ExportAll,
+    ExportFolder,
+    ExportDetail(bool),
+    SaveFile(String, Vec<(List, Vec<Task>)>, Detail),
+    FileSaved(PathBuf),
     AddList(List),
     DeleteList,
+    ListDeleted(List, Vec<Task>),
+    OccurrenceCreated(Task),
+    TaskSaved(Task),
+    Undo,
+    Redo,
+    Refresh,
+    Reminder(ReminderEvent),
+    ServerReady(TasksServer),
+    Remote(Signal),
+    Publish(Signal),
+    StorageChanged(Vec<StorageChange>),
+    SyncLists(Vec<List>),
+    Conflict(Box<Conflict>),
+    KeepTheirs,
+    Failed(Box<Operation>, String),
+    Retry(usize),
+    DismissToast(usize),
     Focus(widget::Id),
     Export(Vec<Task>),
     NavMenuAction(NavMenuAction),
 }
 
+/// Navigation entries that are not backed by a list.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NavPage {
+    SmartList(SmartList),
+    Trash,
+}
+
+/// A short notice shown after an action, such as a destructive one that can
+/// be undone or a failed operation that can be run again.
+pub struct Toast {
+    id: usize,
+    message: String,
+    action: Option<ToastAction>,
+}
+
+pub enum ToastAction {
+    Undo,
+    Retry(Operation),
+}
+
+/// Retention periods offered in the settings, in days. Zero keeps deleted tasks forever.
+const TRASH_RETENTIONS: [u32; 4] = [7, 30, 90, 0];
+
+/// How long the undo toast stays up.
+const TOAST_DURATION: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How long to wait before trying again when reminders could not be read.
+const REMINDER_RETRY: std::time::Duration = std::time::Duration::from_secs(60);
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ContextPage {
     About,
@@ -105,10 +226,28 @@ impl ContextPage {
 pub enum DialogPage {
     New(String),
     Icon(String),
-    Rename { to: String },
+    Rename {
+        to: String,
+    },
     Delete,
     Calendar(NaiveDate),
-    Export(String),
+    Export {
+        list: List,
+        tasks: Vec<Task>,
+        detail: Detail,
+        markdown: String,
+    },
+    Import {
+        lists: Vec<ImportedList>,
+        /// Zero for a new list for each imported one, otherwise one past the
+        /// index of the list to import into.
+        target: usize,
+    },
+    Migrate {
+        from: Provider,
+        summary: MigrationSummary,
+    },
+    Conflict(Box<Conflict>),
 }
 
 #[derive(Clone, Debug)]
@@ -127,6 +266,12 @@ pub enum Action {
     DeleteList,
     RenameList,
     Icon,
+    Undo,
+    Redo,
+    Search,
+    Import,
+    ExportAll,
+    ExportFolder,
 }
 
 impl MenuAction for Action {
@@ -141,6 +286,12 @@ impl MenuAction for Action {
             Action::Icon => Message::OpenIconDialog,
             Action::RenameList => Message::OpenRenameListDialog,
             Action::DeleteList => Message::OpenDeleteListDialog,
+            Action::Undo => Message::Undo,
+            Action::Redo => Message::Redo,
+            Action::Search => Message::FocusSearch,
+            Action::Import => Message::OpenImportDialog(Vec::new()),
+            Action::ExportAll => Message::ExportAll,
+            Action::ExportFolder => Message::ExportFolder,
         }
     }
 }
@@ -150,6 +301,7 @@ pub enum NavMenuAction {
     Rename(segmented_button::Entity),
     SetIcon(segmented_button::Entity),
     Delete(segmented_button::Entity),
+    SetDefault(segmented_button::Entity),
 }
 
 impl MenuAction for NavMenuAction {
@@ -202,29 +354,444 @@ impl Tasks {
             AppTheme::Light => 2,
             AppTheme::System => 0,
         };
-        widget::settings::view_column(vec![widget::settings::view_section(fl!("appearance"))
+        let provider_selected = TaskService::services()
+            .iter()
+            .position(|provider| *provider == self.config.provider);
+        let trash_retention_selected = TRASH_RETENTIONS
+            .iter()
+            .position(|days| *days == self.config.trash_retention_days);
+        let mut storage = widget::settings::view_section(fl!("storage"))
             .add(
-                widget::settings::item::builder(fl!("theme")).control(widget::dropdown(
-                    &self.app_themes,
-                    Some(app_theme_selected),
-                    Message::AppTheme,
+                widget::settings::item::builder(fl!("provider")).control(widget::dropdown(
+                    &self.providers,
+                    provider_selected,
+                    Message::Provider,
                 )),
             )
-            .into()])
-        .into()
+            .add(
+                widget::settings::item::builder(fl!("trash-retention")).control(widget::dropdown(
+                    &self.trash_retentions,
+                    trash_retention_selected,
+                    Message::TrashRetention,
+                )),
+            );
+        for provider in TaskService::services()
+            .iter()
+            .filter(|provider| **provider != self.config.provider)
+        {
+            let title = TaskService::new(Self::APP_ID, *provider)
+                .title()
+                .to_string();
+            storage = storage.add(
+                widget::settings::item::builder(fl!("copy-from", provider = title)).control(
+                    widget::button::standard(fl!("preview"))
+                        .on_press(Message::PreviewMigration(*provider)),
+                ),
+            );
+        }
+        let mut sections = vec![
+            widget::settings::view_section(fl!("appearance"))
+                .add(
+                    widget::settings::item::builder(fl!("theme")).control(widget::dropdown(
+                        &self.app_themes,
+                        Some(app_theme_selected),
+                        Message::AppTheme,
+                    )),
+                )
+                .into(),
+            storage.into(),
+        ];
+        match self.config.provider {
+            Provider::CalDav => sections.push(self.caldav_settings()),
+            Provider::TodoTxt => sections.push(self.todo_txt_settings()),
+            Provider::Computer | Provider::Sqlite => {}
+        }
+        widget::settings::view_column(sections).into()
     }
 
-    fn create_nav_item(&mut self, list: &List) -> EntityMut<SingleSelect> {
-        self.nav_model
-            .insert()
-            .text(format!(
-                "{} {}",
-                list.icon
-                    .clone()
-                    .unwrap_or(emojis::get_by_shortcode("pencil").unwrap().to_string()),
-                list.name.clone()
+    fn caldav_settings(&self) -> Element<Message> {
+        let account = &self.caldav_account;
+        widget::settings::view_section(fl!("caldav-account"))
+            .add(
+                widget::settings::item::builder(fl!("server-address")).control(
+                    widget::text_input("https://example.com/dav/", &account.url)
+                        .on_input(Message::CalDavUrl),
+                ),
+            )
+            .add(widget::settings::item::builder(fl!("username")).control(
+                widget::text_input("", &account.username).on_input(Message::CalDavUsername),
+            ))
+            .add(
+                widget::settings::item::builder(fl!("password")).control(
+                    widget::text_input("", &account.password)
+                        .password()
+                        .on_input(Message::CalDavPassword),
+                ),
+            )
+            .add(widget::settings::item::builder(String::new()).control(
+                widget::button::suggested(fl!("connect")).on_press_maybe(
+                    (!account.url.is_empty()).then_some(Message::SaveCalDavAccount),
+                ),
             ))
+            .into()
+    }
+
+    fn todo_txt_settings(&self) -> Element<Message> {
+        let spacing = theme::active().cosmic().spacing;
+        let files = &self.todo_txt_files;
+        let file = |path: &PathBuf, message: Message| {
+            widget::row::with_children(vec![
+                widget::text::body(path.display().to_string()).into(),
+                widget::button::standard(fl!("choose-file"))
+                    .on_press(message)
+                    .into(),
+            ])
+            .spacing(spacing.space_xs)
+            .align_items(Alignment::Center)
+        };
+        widget::settings::view_section(fl!("todo-txt-files"))
+            .add(
+                widget::settings::item::builder(fl!("todo-txt-file"))
+                    .control(file(&files.todo, Message::ChooseTodoTxt)),
+            )
+            .add(
+                widget::settings::item::builder(fl!("done-txt-file"))
+                    .control(file(&files.done, Message::ChooseDoneTxt)),
+            )
+            .into()
+    }
+
+    fn create_nav_item(&mut self, list: &List) -> Entity {
+        let entity = self
+            .nav_model
+            .insert()
+            .text(nav_text(list))
             .data(list.clone())
+            .id();
+        // Keep the trash below the lists.
+        if let Some(trash) = self.nav_page_entity(NavPage::Trash) {
+            let last = self.nav_model.iter().count().saturating_sub(1);
+            self.nav_model.position_set(trash, last as u16);
+        }
+        entity
+    }
+
+    fn apply_change(&mut self, change: Change) -> Command<CosmicMessage<Message>> {
+        self.toast = None;
+        self.search_index = None;
+        if self.context_page == ContextPage::TaskDetails {
+            self.core.window.show_context = false;
+        }
+        self.perform(Operation::ApplyChange(change))
+    }
+
+    /// Runs an operation against the storage. A conflict asks which version
+    /// to keep, any other failure is logged and shown with a way to retry.
+    fn perform(&self, operation: Operation) -> Command<CosmicMessage<Message>> {
+        let failed = operation.clone();
+        Command::perform(
+            operation.run(self.service.clone(), self.server.clone()),
+            move |result| match result {
+                Ok(Some(message)) => message::app(message),
+                Ok(None) => message::none(),
+                Err(err) => {
+                    if let Some(tasks_core::Error::Conflict(conflict)) =
+                        err.downcast_ref::<tasks_core::Error>()
+                    {
+                        return message::app(Message::Conflict(conflict.clone()));
+                    }
+                    log::error!("failed to {}: {err}", failed.describe());
+                    message::app(Message::Failed(Box::new(failed), err.to_string()))
+                }
+            },
+        )
+    }
+
+    /// Saves a task edited in the window. The copy it was edited from has to
+    /// still be the stored one, otherwise the user is asked which to keep.
+    ///
+    /// Saves of the same task are made one at a time, so typing does not race
+    /// a write against the one before it: an edit made while the task is being
+    /// saved waits for that save, and only the latest one is written.
+    fn save_task(&mut self, mut task: Task) -> Command<CosmicMessage<Message>> {
+        let expected = *task.last_modified_date_time();
+        let before = self
+            .history
+            .saved(task.id())
+            .filter(|before| **before != task)
+            .cloned();
+        task.touch();
+        match before {
+            Some(before) => self.history.record(Change::UpdateTask {
+                before: Box::new(before),
+                after: Box::new(task.clone()),
+            }),
+            None => self.history.track(std::slice::from_ref(&task)),
+        }
+        if let Some(index) = &mut self.search_index {
+            index.insert(task.clone());
+        }
+        self.details.task = Some(task.clone());
+        self.content.update(content::Message::Replace(task.clone()));
+        if let Some(waiting) = self.saving.get_mut(task.id()) {
+            *waiting = Some(task);
+            return self.run_search();
+        }
+        self.saving.insert(task.id().clone(), None);
+        let command = self.perform(Operation::UpdateTask { task, expected });
+        Command::batch(vec![self.run_search(), command])
+    }
+
+    /// Shows a task that was changed outside of the window, by a notification
+    /// action or another application.
+    fn task_changed(&mut self, task: Task) -> Command<CosmicMessage<Message>> {
+        self.history.track(std::slice::from_ref(&task));
+        self.search_index = None;
+        if self
+            .details
+            .task
+            .as_ref()
+            .is_some_and(|details| details.id() == task.id())
+        {
+            self.details.task = Some(task);
+        }
+        self.on_nav_select(self.nav_model.active())
+    }
+
+    /// Reads the current page again, keeping the selection, the search and
+    /// what is being typed.
+    fn reload(&mut self) -> Command<CosmicMessage<Message>> {
+        self.search_index = None;
+        let search = self.run_search();
+        let entity = self.nav_model.active();
+        let page = if let Some(list) = self.nav_model.data::<List>(entity) {
+            let message = content::Message::List(Some(list.clone()));
+            self.update(Message::Content(message))
+        } else {
+            match self.nav_model.data::<NavPage>(entity).copied() {
+                Some(NavPage::SmartList(smart_list)) => {
+                    let message = content::Message::SmartList(smart_list, self.default_list());
+                    self.update(Message::Content(message))
+                }
+                Some(NavPage::Trash) => self.perform(Operation::FetchDeletedTasks),
+                None => Command::none(),
+            }
+        };
+        Command::batch(vec![page, search])
+    }
+
+    /// Whether the current page shows tasks touched by `change`.
+    fn shows(&self, change: &StorageChange) -> bool {
+        match change {
+            StorageChange::Lists => false,
+            StorageChange::Tasks(list_id) => self
+                .nav_model
+                .active_data::<List>()
+                .is_none_or(|list| list.id() == list_id),
+            StorageChange::All => true,
+        }
+    }
+
+    /// Announces a change made in the window to other applications on D-Bus.
+    fn publish(&self, signal: Signal) -> Command<CosmicMessage<Message>> {
+        let Some(server) = self.server.clone() else {
+            return Command::none();
+        };
+        Command::perform(async move { server.emit(signal).await }, |result| {
+            if let Err(err) = result {
+                log::warn!("failed to announce change: {err}");
+            }
+            message::none()
+        })
+    }
+
+    /// Runs the current query against the index, building the index first if needed.
+    fn run_search(&mut self) -> Command<CosmicMessage<Message>> {
+        if self.search_query.trim().is_empty() {
+            return Command::none();
+        }
+        let Some(index) = &self.search_index else {
+            if self.indexing {
+                return Command::none();
+            }
+            self.indexing = true;
+            return self.perform(Operation::BuildSearchIndex);
+        };
+        let results = index.search(&self.search_query);
+        let lists = self
+            .nav_model
+            .iter()
+            .filter_map(|entity| self.nav_model.data::<List>(entity).cloned())
+            .collect();
+        self.search.update(search::Message::SetLists(lists));
+        self.search.update(search::Message::SetResults(results));
+        Command::none()
+    }
+
+    fn show_toast(
+        &mut self,
+        message: String,
+        action: Option<ToastAction>,
+    ) -> Command<CosmicMessage<Message>> {
+        let id = self.toast_count;
+        self.toast_count += 1;
+        self.toast = Some(Toast {
+            id,
+            message,
+            action,
+        });
+        Command::perform(tokio::time::sleep(TOAST_DURATION), move |_| {
+            message::app(Message::DismissToast(id))
+        })
+    }
+
+    /// Shows why an operation failed until it is dismissed or run again.
+    fn show_error(&mut self, operation: Operation, error: String) {
+        let message = if operation.is_write() {
+            fl!("error-saving", error = error)
+        } else {
+            fl!("error-loading", error = error)
+        };
+        let id = self.toast_count;
+        self.toast_count += 1;
+        self.toast = Some(Toast {
+            id,
+            message,
+            action: Some(ToastAction::Retry(operation)),
+        });
+    }
+
+    fn toast_view(&self, toast: &Toast) -> Element<Message> {
+        let spacing = theme::active().cosmic().spacing;
+        let mut row = widget::row::with_capacity(3)
+            .align_items(Alignment::Center)
+            .spacing(spacing.space_xxs)
+            .push(widget::text(&toast.message).width(Length::Fill));
+        if let Some(action) = &toast.action {
+            row = row.push(match action {
+                ToastAction::Undo => widget::button::text(fl!("undo")).on_press(Message::Undo),
+                ToastAction::Retry(_) => {
+                    widget::button::text(fl!("retry")).on_press(Message::Retry(toast.id))
+                }
+            });
+        }
+        row.push(
+            widget::button(IconCache::get("window-close-symbolic", 16))
+                .padding(spacing.space_xxs)
+                .style(theme::Button::Text)
+                .on_press(Message::DismissToast(toast.id)),
+        )
+        .padding([spacing.space_xxs, spacing.space_s])
+        .apply(widget::container)
+        .style(cosmic::style::Container::List)
+        .into()
+    }
+
+    /// The list new tasks go to when they are added from a smart list.
+    fn default_list(&self) -> Option<List> {
+        let mut lists = self
+            .nav_model
+            .iter()
+            .filter_map(|entity| self.nav_model.data::<List>(entity));
+        let first = lists.next();
+        let default = self
+            .config
+            .default_list
+            .as_ref()
+            .and_then(|id| first.into_iter().chain(lists).find(|list| list.id() == id));
+        default.or(first).cloned()
+    }
+
+    fn nav_page_entity(&self, page: NavPage) -> Option<Entity> {
+        self.nav_model
+            .iter()
+            .find(|entity| self.nav_model.data::<NavPage>(*entity) == Some(&page))
+    }
+
+    /// Posts the reminders of every list as desktop notifications while the app runs.
+    fn reminders(&self) -> Subscription<Message> {
+        struct ReminderSubscription;
+
+        let service = self.service.clone();
+        subscription::channel(
+            (TypeId::of::<ReminderSubscription>(), service.provider),
+            10,
+            |mut output| async move {
+                let mut scheduler = match ReminderScheduler::session(service).await {
+                    Ok(scheduler) => scheduler.with_actions(fl!("complete"), fl!("snooze")),
+                    Err(err) => {
+                        log::warn!("reminders are not available: {err}");
+                        return future::pending().await;
+                    }
+                };
+                loop {
+                    match scheduler.next().await {
+                        Ok(event) => {
+                            let _ = output.send(Message::Reminder(event)).await;
+                        }
+                        Err(err) => {
+                            log::error!("failed to process reminders: {err}");
+                            tokio::time::sleep(REMINDER_RETRY).await;
+                        }
+                    }
+                }
+            },
+        )
+    }
+
+    /// Serves the tasks on D-Bus while the window is open, forwarding the
+    /// changes other applications make through it.
+    fn server(&self) -> Subscription<Message> {
+        struct ServerSubscription;
+
+        let service = self.service.clone();
+        subscription::channel(
+            (TypeId::of::<ServerSubscription>(), service.provider),
+            10,
+            |mut output| async move {
+                let (server, mut changes) = match TasksServer::session(service).await {
+                    Ok(server) => server,
+                    Err(err) => {
+                        log::warn!("the D-Bus service is not available: {err}");
+                        return future::pending().await;
+                    }
+                };
+                let _ = output.send(Message::ServerReady(server)).await;
+                while let Some(signal) = changes.next().await {
+                    let _ = output.send(Message::Remote(signal)).await;
+                }
+                future::pending().await
+            },
+        )
+    }
+
+    /// Reports changes to the data on disk made by other processes, such as
+    /// another window or a sync tool.
+    fn storage(&self) -> Subscription<Message> {
+        struct StorageSubscription;
+
+        let service = self.service.clone();
+        subscription::channel(
+            (
+                TypeId::of::<StorageSubscription>(),
+                service.provider,
+                self.todo_txt_files.clone(),
+            ),
+            10,
+            |mut output| async move {
+                let mut watcher = match StorageWatcher::new(&service) {
+                    Ok(watcher) => watcher,
+                    Err(err) => {
+                        log::warn!("changes on disk will not be picked up: {err}");
+                        return future::pending().await;
+                    }
+                };
+                while let Some(changes) = watcher.next().await {
+                    let _ = output.send(Message::StorageChanged(changes)).await;
+                }
+                future::pending().await
+            },
+        )
     }
 }
 
@@ -245,26 +812,58 @@ impl Application for Tasks {
     fn init(mut core: Core, flags: Self::Flags) -> (Self, Command<CosmicMessage<Self::Message>>) {
         core.nav_bar_toggle_condensed();
         let nav_model = segmented_button::ModelBuilder::default().build();
-        let service = TaskService::new(Self::APP_ID, Provider::Computer);
-        let app = Tasks {
+        let service = TaskService::new(Self::APP_ID, flags.config.provider);
+        let mut app = Tasks {
             core,
             service: service.clone(),
             nav_model,
             content: Content::new(),
             details: Details::new(),
+            trash: Trash::new(),
+            search: Search::new(),
+            search_query: String::new(),
+            search_input: widget::Id::unique(),
+            search_index: None,
+            indexing: false,
+            history: History::new(),
+            saving: HashMap::new(),
+            caldav_account: CalDavAccount::load(Self::APP_ID).unwrap_or_default(),
+            todo_txt_files: TodoTxtFiles::load(Self::APP_ID).unwrap_or_default(),
+            toast: None,
+            toast_count: 0,
+            server: None,
             config_handler: flags.config_handler,
             config: flags.config,
             app_themes: vec![fl!("match-desktop"), fl!("dark"), fl!("light")],
+            providers: TaskService::services()
+                .iter()
+                .map(|provider| {
+                    TaskService::new(Self::APP_ID, *provider)
+                        .title()
+                        .to_string()
+                })
+                .collect(),
+            trash_retentions: TRASH_RETENTIONS
+                .iter()
+                .map(|days| match days {
+                    0 => fl!("never"),
+                    days => fl!("days", count = days),
+                })
+                .collect(),
             context_page: ContextPage::Settings,
             key_binds: key_binds(),
             modifiers: Modifiers::empty(),
             dialog_pages: VecDeque::new(),
             dialog_text_input: widget::Id::unique(),
+            import_lists: Vec::new(),
+            import_targets: Vec::new(),
+            diagnostics: Vec::new(),
         };
 
-        let commands = vec![Command::perform(TaskService::migrate(Self::APP_ID), |_| {
+        let mut commands = vec![Command::perform(TaskService::migrate(Self::APP_ID), |_| {
             message::app(Message::FetchLists)
         })];
+        commands.push(app.update(Message::PurgeTrash));
 
         (app, Command::batch(commands))
     }
@@ -394,12 +993,29 @@ impl Application for Tasks {
                     );
                 dialog
             }
-            DialogPage::Export(contents) => {
+            DialogPage::Export {
+                list,
+                tasks,
+                detail,
+                markdown,
+            } => {
                 let dialog = widget::dialog(fl!("export"))
                     .control(
-                        widget::container(scrollable(widget::text(contents)).width(Length::Fill))
+                        widget::column::with_children(vec![
+                            widget::checkbox(
+                                fl!("export-details"),
+                                *detail == Detail::Full,
+                                Message::ExportDetail,
+                            )
+                            .into(),
+                            widget::container(
+                                scrollable(widget::text(markdown)).width(Length::Fill),
+                            )
                             .height(Length::Fixed(200.0))
-                            .width(Length::Fill),
+                            .width(Length::Fill)
+                            .into(),
+                        ])
+                        .spacing(spacing.space_xxs),
                     )
                     .primary_action(
                         widget::button::suggested(fl!("copy"))
@@ -407,10 +1023,95 @@ impl Application for Tasks {
                     )
                     .secondary_action(
                         widget::button::standard(fl!("cancel")).on_press(Message::DialogCancel),
-                    );
+                    )
+                    .tertiary_action(widget::button::text(fl!("save-as")).on_press(
+                        Message::SaveFile(
+                            format!("{}.md", list.name),
+                            vec![(list.clone(), tasks.clone())],
+                            *detail,
+                        ),
+                    ));
 
                 dialog
             }
+            DialogPage::Import { lists, target } => {
+                let count: usize = lists
+                    .iter()
+                    .flat_map(|list| &list.tasks)
+                    .map(count_tasks)
+                    .sum();
+                let body = if lists.is_empty() {
+                    fl!("import-hint")
+                } else {
+                    fl!("import-body", count = count)
+                };
+                let lists = lists.clone();
+                widget::dialog(fl!("import"))
+                    .body(body)
+                    .control(
+                        widget::column::with_children(vec![
+                            widget::row::with_children(vec![
+                                widget::button::standard(fl!("choose-file"))
+                                    .on_press(Message::PickImportFile)
+                                    .into(),
+                                widget::button::standard(fl!("paste"))
+                                    .on_press(Message::PasteImport)
+                                    .into(),
+                            ])
+                            .spacing(spacing.space_xxs)
+                            .into(),
+                            widget::text::body(fl!("import-into")).into(),
+                            widget::dropdown(&self.import_targets, Some(*target), move |target| {
+                                Message::DialogUpdate(DialogPage::Import {
+                                    lists: lists.clone(),
+                                    target,
+                                })
+                            })
+                            .into(),
+                        ])
+                        .spacing(spacing.space_xxs),
+                    )
+                    .primary_action(
+                        widget::button::suggested(fl!("import-confirm"))
+                            .on_press_maybe((count > 0).then_some(Message::DialogComplete)),
+                    )
+                    .secondary_action(
+                        widget::button::standard(fl!("cancel")).on_press(Message::DialogCancel),
+                    )
+            }
+            DialogPage::Migrate { from, summary } => {
+                let source = TaskService::new(Self::APP_ID, *from).title().to_string();
+                let body = if summary.is_empty() {
+                    fl!("copy-data-up-to-date", provider = source)
+                } else {
+                    fl!(
+                        "copy-data-summary",
+                        provider = source,
+                        lists_created = summary.lists_created,
+                        lists_updated = summary.lists_updated,
+                        tasks_created = summary.tasks_created,
+                        tasks_updated = summary.tasks_updated
+                    )
+                };
+                widget::dialog(fl!("copy-data"))
+                    .body(body)
+                    .primary_action(
+                        widget::button::suggested(fl!("copy-data-confirm")).on_press_maybe(
+                            (!summary.is_empty()).then_some(Message::DialogComplete),
+                        ),
+                    )
+                    .secondary_action(
+                        widget::button::standard(fl!("cancel")).on_press(Message::DialogCancel),
+                    )
+            }
+            DialogPage::Conflict(conflict) => widget::dialog(fl!("conflict"))
+                .body(fl!("conflict-body", task = conflict.theirs.title.clone()))
+                .primary_action(
+                    widget::button::suggested(fl!("keep-mine")).on_press(Message::DialogComplete),
+                )
+                .secondary_action(
+                    widget::button::standard(fl!("keep-theirs")).on_press(Message::KeepTheirs),
+                ),
         };
 
         Some(dialog.into())
@@ -421,7 +1122,14 @@ impl Application for Tasks {
     }
 
     fn header_center(&self) -> Vec<Element<Self::Message>> {
-        vec![]
+        vec![
+            widget::search_input(fl!("search-tasks"), &self.search_query)
+                .id(self.search_input.clone())
+                .on_input(Message::SearchInput)
+                .on_clear(Message::ClearSearch)
+                .width(Length::Fixed(300.0))
+                .into(),
+        ]
     }
 
     fn header_end(&self) -> Vec<Element<Self::Message>> {
@@ -432,12 +1140,18 @@ impl Application for Tasks {
         &self,
         id: widget::nav_bar::Id,
     ) -> Option<Vec<widget::menu::Tree<CosmicMessage<Self::Message>>>> {
+        self.nav_model.data::<List>(id)?;
         Some(cosmic::widget::menu::items(
             &HashMap::new(),
             vec![
                 cosmic::widget::menu::Item::Button(fl!("rename"), NavMenuAction::Rename(id)),
                 cosmic::widget::menu::Item::Button(fl!("icon"), NavMenuAction::SetIcon(id)),
                 cosmic::widget::menu::Item::Button(fl!("delete"), NavMenuAction::Delete(id)),
+                cosmic::widget::menu::Item::Divider,
+                cosmic::widget::menu::Item::Button(
+                    fl!("set-default-list"),
+                    NavMenuAction::SetDefault(id),
+                ),
             ],
         ))
     }
@@ -459,6 +1173,7 @@ impl Application for Tasks {
     fn on_nav_select(&mut self, entity: Entity) -> Command<CosmicMessage<Self::Message>> {
         let mut commands = vec![];
         self.nav_model.activate(entity);
+        self.search_query.clear();
         let location_opt = self.nav_model.data::<List>(entity);
 
         if let Some(list) = location_opt {
@@ -468,6 +1183,30 @@ impl Application for Tasks {
             return self.update(message);
         }
 
+        if let Some(NavPage::SmartList(smart_list)) = self.nav_model.data::<NavPage>(entity) {
+            let smart_list = *smart_list;
+            let window_title = format!(
+                "{} - {}",
+                content::smart_list_title(smart_list),
+                fl!("tasks")
+            );
+            commands.push(self.set_window_title(window_title, self.main_window_id()));
+            let message = content::Message::SmartList(smart_list, self.default_list());
+            commands.push(self.update(Message::Content(message)));
+        }
+
+        if let Some(NavPage::Trash) = self.nav_model.data::<NavPage>(entity) {
+            let window_title = format!("{} - {}", fl!("trash"), fl!("tasks"));
+            commands.push(self.set_window_title(window_title, self.main_window_id()));
+            let lists = self
+                .nav_model
+                .iter()
+                .filter_map(|entity| self.nav_model.data::<List>(entity).cloned())
+                .collect();
+            self.trash.update(trash::Message::SetLists(lists));
+            commands.push(self.perform(Operation::FetchDeletedTasks));
+        }
+
         Command::batch(commands)
     }
 
@@ -519,6 +1258,9 @@ impl Application for Tasks {
         ];
 
         subscriptions.push(self.content.subscription().map(Message::Content));
+        subscriptions.push(self.reminders());
+        subscriptions.push(self.server());
+        subscriptions.push(self.storage());
 
         Subscription::batch(subscriptions)
     }
@@ -555,20 +1297,18 @@ impl Application for Tasks {
 
         match message {
             Message::Content(message) => {
+                if let content::Message::SetItems(tasks) = &message {
+                    self.history.track(tasks);
+                }
                 let content_commands = self.content.update(message);
                 for content_command in content_commands {
                     match content_command {
                         content::Command::Iced(command) => return command,
+                        content::Command::GetSmartList(smart_list) => {
+                            commands.push(self.perform(Operation::FetchSmartList(smart_list)));
+                        }
                         content::Command::GetTasks(list_id) => {
-                            commands.push(Command::perform(
-                                todo::fetch_tasks(list_id, self.service.clone()),
-                                |result| match result {
-                                    Ok(data) => message::app(Message::Content(
-                                        content::Message::SetItems(data),
-                                    )),
-                                    Err(_) => message::none(),
-                                },
-                            ));
+                            commands.push(self.perform(Operation::FetchTasks(list_id)));
                         }
                         content::Command::DisplayTask(task) => {
                             let entity =
@@ -590,39 +1330,29 @@ impl Application for Tasks {
                             );
                         }
                         content::Command::UpdateTask(task) => {
-                            self.details.task = Some(task.clone());
-                            let command = Command::perform(
-                                todo::update_task(task, self.service.clone().clone()),
-                                |result| match result {
-                                    Ok(()) | Err(_) => message::none(),
-                                },
-                            );
-                            commands.push(command);
+                            commands.push(self.save_task(task));
                         }
-                        content::Command::Delete(id) => {
-                            if let Some(list) = self.nav_model.data::<List>(self.nav_model.active())
-                            {
-                                let command = Command::perform(
-                                    todo::delete_task(
-                                        list.id().clone(),
-                                        id.clone(),
-                                        self.service.clone().clone(),
-                                    ),
-                                    |result| match result {
-                                        Ok(()) | Err(_) => message::none(),
-                                    },
+                        content::Command::Delete(task) => {
+                            if let Some(index) = &mut self.search_index {
+                                index.remove(task.id());
+                            }
+                            if let Some(task) = self.history.saved(task.id()).cloned() {
+                                self.history.record(Change::DeleteTask(task));
+                                commands.push(
+                                    self.show_toast(fl!("task-deleted"), Some(ToastAction::Undo)),
                                 );
-                                commands.push(command);
                             }
+                            commands.push(self.perform(Operation::DeleteTask(task)));
                         }
                         content::Command::CreateTask(task) => {
-                            let command = Command::perform(
-                                todo::create_task(task, self.service.clone()),
-                                |result| match result {
-                                    Ok(()) | Err(_) => message::none(),
-                                },
-                            );
-                            commands.push(command);
+                            self.history.record(Change::CreateTask(task.clone()));
+                            if let Some(index) = &mut self.search_index {
+                                index.insert(task.clone());
+                            }
+                            commands.push(self.perform(Operation::CreateTask(task)));
+                        }
+                        content::Command::CreateOccurrence(task) => {
+                            commands.push(self.perform(Operation::CreateOccurrence(task)));
                         }
                         content::Command::Export(tasks) => {
                             commands.push(self.update(Message::Export(tasks)));
@@ -649,6 +1379,57 @@ impl Application for Tasks {
                     }
                 }
             }
+            Message::Trash(message) => {
+                for trash_command in self.trash.update(message) {
+                    match trash_command {
+                        trash::Command::Restore(task) => {
+                            self.history.record(Change::RestoreTask(task.clone()));
+                            self.search_index = None;
+                            commands.push(self.perform(Operation::RestoreTask(task)));
+                        }
+                        trash::Command::Purge(task) => {
+                            commands.push(self.perform(Operation::PurgeTask(task)));
+                        }
+                        trash::Command::Empty => {
+                            commands.push(self.perform(Operation::PurgeTrash(Utc::now())));
+                        }
+                    }
+                }
+            }
+            Message::Search(message) => {
+                for search_command in self.search.update(message) {
+                    match search_command {
+                        search::Command::Open(task) => {
+                            let entity = self.nav_model.iter().find(|entity| {
+                                self.nav_model
+                                    .data::<List>(*entity)
+                                    .is_some_and(|list| *list.id() == task.parent)
+                            });
+                            if let Some(entity) = entity {
+                                commands.push(self.on_nav_select(entity));
+                            }
+                            commands.push(
+                                self.update(Message::Content(content::Message::Select(task))),
+                            );
+                        }
+                    }
+                }
+            }
+            Message::SearchInput(query) => {
+                self.search_query = query;
+                commands.push(self.run_search());
+            }
+            Message::ClearSearch => {
+                self.search_query.clear();
+            }
+            Message::FocusSearch => {
+                return widget::text_input::focus(self.search_input.clone());
+            }
+            Message::SetSearchIndex(index) => {
+                self.search_index = Some(index);
+                self.indexing = false;
+                commands.push(self.run_search());
+            }
             Message::NavMenuAction(action) => match action {
                 NavMenuAction::Rename(entity) => {
                     if self.nav_model.data::<List>(entity).is_some() {
@@ -665,6 +1446,12 @@ impl Application for Tasks {
                         commands.push(self.update(Message::OpenDeleteListDialog));
                     }
                 }
+                NavMenuAction::SetDefault(entity) => {
+                    if let Some(list) = self.nav_model.data::<List>(entity) {
+                        let id = list.id().clone();
+                        config_set!(default_list, Some(id));
+                    }
+                }
             },
             Message::ToggleContextPage(context_page) => {
                 if self.context_page == context_page {
@@ -707,26 +1494,141 @@ impl Application for Tasks {
             Message::SystemThemeModeChange => {
                 return self.update_config();
             }
+            Message::Provider(index) => {
+                let Some(provider) = TaskService::services().get(index).copied() else {
+                    return Command::none();
+                };
+                if provider == self.config.provider {
+                    return Command::none();
+                }
+                config_set!(provider, provider);
+                self.service = TaskService::new(Self::APP_ID, provider);
+                match provider {
+                    Provider::Sqlite => commands.push(self.perform(Operation::MigrateToSqlite)),
+                    Provider::Computer | Provider::CalDav | Provider::TodoTxt => {
+                        commands.push(self.update(Message::ReloadLists))
+                    }
+                }
+            }
+            Message::CalDavUrl(url) => self.caldav_account.url = url,
+            Message::CalDavUsername(username) => self.caldav_account.username = username,
+            Message::CalDavPassword(password) => self.caldav_account.password = password,
+            Message::SaveCalDavAccount => {
+                let account = self.caldav_account.clone();
+                commands.push(self.perform(Operation::SaveCalDavAccount(account)));
+            }
+            Message::ChooseTodoTxt => {
+                return Command::perform(todo::pick_todo_txt(fl!("todo-txt-file")), |path| {
+                    path.map_or(message::none(), |path| {
+                        message::app(Message::SetTodoTxt(path))
+                    })
+                });
+            }
+            Message::ChooseDoneTxt => {
+                return Command::perform(todo::pick_todo_txt(fl!("done-txt-file")), |path| {
+                    path.map_or(message::none(), |path| {
+                        message::app(Message::SetDoneTxt(path))
+                    })
+                });
+            }
+            Message::SetTodoTxt(path) => {
+                // A done.txt usually sits next to the todo.txt it belongs to.
+                self.todo_txt_files = TodoTxtFiles::new(path);
+                commands.push(self.update(Message::SaveTodoTxtFiles));
+            }
+            Message::SetDoneTxt(path) => {
+                self.todo_txt_files.done = path;
+                commands.push(self.update(Message::SaveTodoTxtFiles));
+            }
+            Message::SaveTodoTxtFiles => match self.todo_txt_files.save(Self::APP_ID) {
+                Ok(()) => commands.push(self.update(Message::ReloadLists)),
+                Err(err) => log::error!("failed to save the todo.txt files: {err}"),
+            },
+            Message::TrashRetention(index) => {
+                let Some(days) = TRASH_RETENTIONS.get(index).copied() else {
+                    return Command::none();
+                };
+                config_set!(trash_retention_days, days);
+                commands.push(self.update(Message::PurgeTrash));
+            }
+            Message::PurgeTrash => {
+                let days = self.config.trash_retention_days;
+                if days > 0 {
+                    let before = Utc::now() - Duration::days(i64::from(days));
+                    commands.push(self.perform(Operation::PurgeTrash(before)));
+                }
+            }
+            Message::PreviewMigration(from) => {
+                commands.push(self.perform(Operation::PreviewCopy(from)));
+            }
+            Message::OpenMigrationDialog(from, summary) => {
+                self.dialog_pages
+                    .push_back(DialogPage::Migrate { from, summary });
+            }
             Message::FetchLists => {
-                commands.push(Command::perform(
-                    todo::fetch_lists(self.service.clone()),
-                    |result| match result {
-                        Ok(data) => message::app(Message::PopulateLists(data)),
-                        Err(_) => message::none(),
-                    },
-                ));
+                commands.push(self.perform(Operation::FetchLists));
+            }
+            Message::ReloadLists => {
+                self.nav_model.clear();
+                self.search_index = None;
+                commands.push(self.update(Message::Content(content::Message::List(None))));
+                commands.push(self.update(Message::FetchLists));
             }
             Message::PopulateLists(lists) => {
+                for smart_list in SmartList::all() {
+                    self.nav_model
+                        .insert()
+                        .text(format!(
+                            "{} {}",
+                            smart_list.icon(),
+                            content::smart_list_title(smart_list)
+                        ))
+                        .data(NavPage::SmartList(smart_list));
+                }
                 for list in lists {
                     self.create_nav_item(&list);
                 }
-                let Some(entity) = self.nav_model.iter().next() else {
-                    return Command::none();
+                self.nav_model
+                    .insert()
+                    .text(fl!("trash"))
+                    .icon(IconCache::get("user-trash-full-symbolic", 16))
+                    .data(NavPage::Trash);
+                commands.push(self.update(Message::FetchDiagnostics));
+                let Some(entity) = self
+                    .nav_model
+                    .iter()
+                    .find(|entity| self.nav_model.data::<List>(*entity).is_some())
+                else {
+                    return Command::batch(commands);
                 };
                 self.nav_model.activate(entity);
                 let command = self.on_nav_select(entity);
                 commands.push(command);
             }
+            Message::SetTasks(tasks, diagnostics) => {
+                if diagnostics != self.diagnostics {
+                    commands.push(self.update(Message::SetDiagnostics(diagnostics)));
+                }
+                let message = content::Message::SetItems(tasks);
+                commands.push(self.update(Message::Content(message)));
+            }
+            Message::FetchDiagnostics => {
+                commands.push(self.perform(Operation::FetchDiagnostics));
+            }
+            Message::SetDiagnostics(diagnostics) => {
+                for diagnostic in &diagnostics {
+                    log::warn!(
+                        "quarantined {}: {}",
+                        diagnostic.path.display(),
+                        diagnostic.message
+                    );
+                }
+                self.diagnostics = diagnostics;
+            }
+            Message::DismissDiagnostics => {
+                let diagnostics = std::mem::take(&mut self.diagnostics);
+                commands.push(self.perform(Operation::AcknowledgeDiagnostics(diagnostics)));
+            }
             Message::Key(modifiers, key) => {
                 for (key_bind, action) in &self.key_binds {
                     if key_bind.matches(modifiers, &key) {
@@ -738,32 +1640,254 @@ impl Application for Tasks {
                 self.modifiers = modifiers;
             }
             Message::AddList(list) => {
-                self.create_nav_item(&list);
-                let Some(entity) = self.nav_model.iter().last() else {
-                    return Command::none();
-                };
+                self.history.record(Change::CreateList {
+                    list: list.clone(),
+                    tasks: vec![],
+                });
+                let entity = self.create_nav_item(&list);
                 let command = self.on_nav_select(entity);
                 commands.push(command);
+                commands.push(self.publish(Signal::ListsChanged));
             }
             Message::DeleteList => {
                 if let Some(list) = self.nav_model.data::<List>(self.nav_model.active()) {
-                    let command = Command::perform(
-                        todo::delete_list(list.id().clone(), self.service.clone()),
-                        |result| match result {
-                            Ok(()) | Err(_) => message::none(),
-                        },
-                    );
+                    let command = self.perform(Operation::DeleteList(list.clone()));
 
                     commands.push(self.update(Message::Content(content::Message::List(None))));
 
                     commands.push(command);
+                    self.nav_model.remove(self.nav_model.active());
+                }
+            }
+            Message::ListDeleted(list, tasks) => {
+                if let Some(index) = &mut self.search_index {
+                    index.remove_list(list.id());
+                }
+                let message = fl!("list-deleted", list = list.name.clone());
+                self.history.record(Change::DeleteList { list, tasks });
+                commands.push(self.show_toast(message, Some(ToastAction::Undo)));
+                commands.push(self.publish(Signal::ListsChanged));
+            }
+            Message::TaskSaved(task) => {
+                if let Some(Some(waiting)) = self.saving.remove(task.id()) {
+                    // The stored copy is now the one that was just saved.
+                    self.saving.insert(waiting.id().clone(), None);
+                    let expected = *task.last_modified_date_time();
+                    commands.push(self.perform(Operation::UpdateTask {
+                        task: waiting,
+                        expected,
+                    }));
+                }
+                commands.push(self.publish(Signal::TaskUpdated(task)));
+            }
+            Message::OccurrenceCreated(task) => {
+                self.history.record(Change::CreateTask(task.clone()));
+                commands.push(self.publish(Signal::TaskCreated(task.clone())));
+                commands.push(self.task_changed(task));
+            }
+            Message::Undo => {
+                if let Some(change) = self.history.undo() {
+                    commands.push(self.apply_change(change));
+                }
+            }
+            Message::Redo => {
+                if let Some(change) = self.history.redo() {
+                    commands.push(self.apply_change(change));
+                }
+            }
+            Message::Refresh => {
+                commands.push(self.on_nav_select(self.nav_model.active()));
+            }
+            Message::Reminder(event) => match event {
+                ReminderEvent::Shown(_) => {}
+                ReminderEvent::Completed(task) | ReminderEvent::Snoozed(task) => {
+                    commands.push(self.publish(Signal::TaskUpdated(task.clone())));
+                    commands.push(self.task_changed(task));
+                }
+            },
+            Message::ServerReady(server) => {
+                self.server = Some(server);
+            }
+            Message::Remote(signal) => match signal {
+                Signal::TaskCreated(task)
+                | Signal::TaskUpdated(task)
+                | Signal::TaskDeleted(task) => {
+                    commands.push(self.task_changed(task));
+                }
+                Signal::ListsChanged => {
+                    commands.push(self.update(Message::ReloadLists));
+                }
+            },
+            Message::Publish(signal) => {
+                commands.push(self.publish(signal));
+            }
+            Message::StorageChanged(changes) => {
+                log::debug!("data changed on disk: {changes:?}");
+                if changes
+                    .iter()
+                    .any(|change| matches!(change, StorageChange::Lists | StorageChange::All))
+                {
+                    commands.push(self.perform(Operation::SyncLists));
+                }
+                if changes.iter().any(|change| self.shows(change)) {
+                    commands.push(self.reload());
+                } else if changes
+                    .iter()
+                    .any(|change| matches!(change, StorageChange::Tasks(_)))
+                {
+                    self.search_index = None;
+                    commands.push(self.run_search());
+                }
+            }
+            Message::SyncLists(lists) => {
+                // Lists are updated in place rather than reloaded, so the
+                // selected one stays selected.
+                let entities: Vec<Entity> = self
+                    .nav_model
+                    .iter()
+                    .filter(|entity| self.nav_model.data::<List>(*entity).is_some())
+                    .collect();
+                let mut removed_active = false;
+                let mut updated_active = None;
+                for entity in entities {
+                    let Some(current) = self.nav_model.data::<List>(entity).cloned() else {
+                        continue;
+                    };
+                    match lists.iter().find(|list| list.id() == current.id()) {
+                        Some(list) if *list != current => {
+                            self.nav_model.text_set(entity, nav_text(list));
+                            self.nav_model.data_set(entity, list.clone());
+                            if self.nav_model.is_active(entity) {
+                                updated_active = Some(list.clone());
+                            }
+                        }
+                        Some(_) => {}
+                        None => {
+                            removed_active |= self.nav_model.is_active(entity);
+                            self.nav_model.remove(entity);
+                        }
+                    }
+                }
+                for list in &lists {
+                    let known = self.nav_model.iter().any(|entity| {
+                        self.nav_model
+                            .data::<List>(entity)
+                            .is_some_and(|known| known.id() == list.id())
+                    });
+                    if !known {
+                        self.create_nav_item(list);
+                    }
+                }
+                if removed_active {
+                    commands.push(self.update(Message::Content(content::Message::List(None))));
+                } else if let Some(list) = updated_active {
+                    let window_title = format!("{} - {}", list.name, fl!("tasks"));
+                    commands.push(self.set_window_title(window_title, self.main_window_id()));
+                    let message = content::Message::List(Some(list));
+                    commands.push(self.update(Message::Content(message)));
+                }
+            }
+            Message::Conflict(conflict) => {
+                log::warn!("{conflict}");
+                self.saving.remove(conflict.ours.id());
+                self.dialog_pages.push_back(DialogPage::Conflict(conflict));
+            }
+            Message::KeepTheirs => {
+                if let Some(DialogPage::Conflict(conflict)) = self.dialog_pages.pop_front() {
+                    let task = conflict.keep_theirs();
+                    self.content.update(content::Message::Replace(task.clone()));
+                    commands.push(self.task_changed(task));
+                }
+            }
+            Message::Failed(operation, error) => {
+                let operation = match *operation {
+                    Operation::BuildSearchIndex => {
+                        self.indexing = false;
+                        Operation::BuildSearchIndex
+                    }
+                    // Trying again saves the latest edit of the task.
+                    Operation::UpdateTask { task, expected } => {
+                        let task = self.saving.remove(task.id()).flatten().unwrap_or(task);
+                        Operation::UpdateTask { task, expected }
+                    }
+                    operation => operation,
+                };
+                self.show_error(operation, error);
+            }
+            Message::Retry(id) => {
+                if let Some(Toast {
+                    action: Some(ToastAction::Retry(operation)),
+                    ..
+                }) = self.toast.take_if(|toast| toast.id == id)
+                {
+                    if let Operation::UpdateTask { task, .. } = &operation {
+                        self.saving.entry(task.id().clone()).or_default();
+                    }
+                    commands.push(self.perform(operation));
+                }
+            }
+            Message::DismissToast(id) => {
+                if self.toast.as_ref().is_some_and(|toast| toast.id == id) {
+                    self.toast = None;
                 }
-                self.nav_model.remove(self.nav_model.active());
             }
             Message::Export(tasks) => {
-                if let Some(list) = self.nav_model.data::<List>(self.nav_model.active()) {
-                    let exported_markdown = todo::export_list(list, &tasks);
-                    commands.push(self.update(Message::OpenExportDialog(exported_markdown)));
+                let list = match self.nav_model.active_data::<NavPage>() {
+                    Some(NavPage::SmartList(smart_list)) => {
+                        Some(List::new(&content::smart_list_title(*smart_list)))
+                    }
+                    _ => self.nav_model.active_data::<List>().cloned(),
+                };
+                if let Some(list) = list {
+                    commands.push(self.update(Message::OpenExportDialog(list, tasks)));
+                }
+            }
+            Message::ExportAll => {
+                commands.push(self.perform(Operation::ExportAll));
+            }
+            Message::ExportFolder => {
+                commands.push(self.perform(Operation::ExportFolder));
+            }
+            Message::ExportDetail(full) => {
+                if let Some(DialogPage::Export {
+                    list,
+                    tasks,
+                    detail,
+                    markdown,
+                }) = self.dialog_pages.front_mut()
+                {
+                    *detail = if full { Detail::Full } else { Detail::Plain };
+                    *markdown = todo::export_list(list, tasks, *detail);
+                }
+            }
+            Message::SaveFile(name, lists, detail) => {
+                if let Some(DialogPage::Export { .. }) = self.dialog_pages.front() {
+                    self.dialog_pages.pop_front();
+                }
+                commands.push(self.perform(Operation::SaveFile {
+                    name,
+                    lists,
+                    detail,
+                }));
+            }
+            Message::FileSaved(path) => {
+                let message = fl!("file-saved", path = path.display().to_string());
+                commands.push(self.show_toast(message, None));
+            }
+            Message::PickImportFile => {
+                commands.push(self.perform(Operation::OpenFile));
+            }
+            Message::PasteImport => {
+                commands.push(self.perform(Operation::Paste));
+            }
+            Message::Imported(count, new_lists) => {
+                self.search_index = None;
+                commands.push(self.show_toast(fl!("tasks-imported", count = count), None));
+                commands.push(self.publish(Signal::ListsChanged));
+                if new_lists {
+                    commands.push(self.update(Message::ReloadLists));
+                } else {
+                    commands.push(self.update(Message::Refresh));
                 }
             }
             Message::OpenNewListDialog => {
@@ -800,8 +1924,37 @@ impl Application for Tasks {
                 self.dialog_pages
                     .push_back(DialogPage::Calendar(Local::now().date_naive()));
             }
-            Message::OpenExportDialog(content) => {
-                self.dialog_pages.push_back(DialogPage::Export(content));
+            Message::OpenExportDialog(list, tasks) => {
+                self.dialog_pages.push_back(DialogPage::Export {
+                    markdown: todo::export_list(&list, &tasks, Detail::Plain),
+                    list,
+                    tasks,
+                    detail: Detail::Plain,
+                });
+            }
+            Message::OpenImportDialog(lists) => {
+                // Lists read while the dialog is open replace the ones in it.
+                if let Some(DialogPage::Import { target, .. }) = self.dialog_pages.front() {
+                    let target = *target;
+                    self.dialog_pages[0] = DialogPage::Import { lists, target };
+                    return Command::none();
+                }
+                self.import_lists = self
+                    .nav_model
+                    .iter()
+                    .filter_map(|entity| self.nav_model.data::<List>(entity).cloned())
+                    .collect();
+                self.import_targets = std::iter::once(fl!("new-list"))
+                    .chain(self.import_lists.iter().map(|list| list.name.clone()))
+                    .collect();
+                // Start with the list being looked at, if any.
+                let target = self
+                    .nav_model
+                    .active_data::<List>()
+                    .and_then(|active| self.import_lists.iter().position(|list| list == active))
+                    .map_or(0, |index| index + 1);
+                self.dialog_pages
+                    .push_back(DialogPage::Import { lists, target });
             }
             Message::DialogCancel => {
                 self.dialog_pages.pop_front();
@@ -811,24 +1964,20 @@ impl Application for Tasks {
                     match dialog_page {
                         DialogPage::New(name) => {
                             let list = List::new(&name);
-                            commands.push(Command::perform(
-                                todo::create_list(list, self.service.clone()),
-                                |result| match result {
-                                    Ok(list) => message::app(Message::AddList(list)),
-                                    Err(_) => message::none(),
-                                },
-                            ));
+                            commands.push(self.perform(Operation::CreateList(list)));
                         }
                         DialogPage::Rename { to: name } => {
                             let entity = self.nav_model.active();
                             self.nav_model.text_set(entity, name.clone());
                             if let Some(list) = self.nav_model.active_data_mut::<List>() {
+                                let before = list.clone();
                                 list.name.clone_from(&name);
-                                let command = Command::perform(
-                                    todo::update_list(list.clone(), self.service.clone()),
-                                    |_| message::none(),
-                                );
-                                commands.push(command);
+                                let after = list.clone();
+                                self.history.record(Change::UpdateList {
+                                    before,
+                                    after: after.clone(),
+                                });
+                                commands.push(self.perform(Operation::UpdateList(after)));
                             }
                         }
                         DialogPage::Delete => {
@@ -841,20 +1990,37 @@ impl Application for Tasks {
                                 self.nav_model.text_set(entity, title);
                             }
                             if let Some(list) = self.nav_model.active_data_mut::<List>() {
+                                let before = list.clone();
                                 list.icon = Some(icon);
-                                let command = Command::perform(
-                                    todo::update_list(list.clone(), self.service.clone()),
-                                    |_| message::none(),
-                                );
-                                commands.push(command);
+                                let after = list.clone();
+                                self.history.record(Change::UpdateList {
+                                    before,
+                                    after: after.clone(),
+                                });
+                                commands.push(self.perform(Operation::UpdateList(after)));
                             }
                         }
                         DialogPage::Calendar(date) => {
                             self.details.update(details::Message::SetDueDate(date));
                         }
-                        DialogPage::Export(content) => {
+                        DialogPage::Export { markdown, .. } => {
                             let mut clipboard = ClipboardContext::new().unwrap();
-                            clipboard.set_contents(content).unwrap();
+                            clipboard.set_contents(markdown).unwrap();
+                        }
+                        DialogPage::Import { lists, target } => {
+                            let list = target
+                                .checked_sub(1)
+                                .and_then(|index| self.import_lists.get(index))
+                                .cloned();
+                            commands.push(self.perform(Operation::Import { lists, list }));
+                        }
+                        DialogPage::Conflict(conflict) => {
+                            // Undoing keeping ours goes back to theirs.
+                            self.history.track(std::slice::from_ref(&conflict.theirs));
+                            commands.push(self.save_task(conflict.keep_mine()));
+                        }
+                        DialogPage::Migrate { from, .. } => {
+                            commands.push(self.perform(Operation::Copy(from)));
                         }
                     }
                 }
@@ -874,7 +2040,43 @@ impl Application for Tasks {
     }
 
     fn view(&self) -> Element<Self::Message> {
-        let content_view = self.content.view().map(Message::Content);
-        content_view
+        let content_view = if !self.search_query.trim().is_empty() {
+            self.search.view().map(Message::Search)
+        } else {
+            match self.nav_model.active_data::<NavPage>() {
+                Some(NavPage::Trash) => self.trash.view().map(Message::Trash),
+                Some(NavPage::SmartList(_)) | None => self.content.view().map(Message::Content),
+            }
+        };
+
+        let spacing = theme::active().cosmic().spacing;
+        let mut column = widget::column::with_capacity(3).spacing(spacing.space_xxs);
+        if !self.diagnostics.is_empty() {
+            column = column.push(
+                widget::warning(fl!("quarantined-files", count = self.diagnostics.len()))
+                    .on_close(Message::DismissDiagnostics),
+            );
+        }
+        column = column.push(content_view);
+        if let Some(toast) = &self.toast {
+            column = column.push(self.toast_view(toast));
+        }
+        column.into()
     }
 }
+
+/// The label of a list in the navigation: its icon followed by its name.
+fn nav_text(list: &List) -> String {
+    format!(
+        "{} {}",
+        list.icon
+            .clone()
+            .unwrap_or(emojis::get_by_shortcode("pencil").unwrap().to_string()),
+        list.name
+    )
+}
+
+/// How many tasks there are in a task's tree, counting the task itself.
+fn count_tasks(task: &Task) -> usize {
+    1 + task.sub_tasks.iter().map(count_tasks).sum::<usize>()
+}
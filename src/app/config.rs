@@ -4,12 +4,29 @@ use cosmic::{
     theme, Application,
 };
 use serde::{Deserialize, Serialize};
+use tasks_core::service::Provider;
 
 pub const CONFIG_VERSION: u64 = 1;
 
-#[derive(Clone, Default, Debug, Eq, PartialEq, Deserialize, Serialize, CosmicConfigEntry)]
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize, CosmicConfigEntry)]
 pub struct TasksConfig {
     pub app_theme: AppTheme,
+    pub provider: Provider,
+    /// Days a deleted task stays in the trash before it is purged, zero keeps it forever.
+    pub trash_retention_days: u32,
+    /// Id of the list that tasks created from a smart list are added to.
+    pub default_list: Option<String>,
+}
+
+impl Default for TasksConfig {
+    fn default() -> Self {
+        Self {
+            app_theme: AppTheme::default(),
+            provider: Provider::default(),
+            trash_retention_days: 30,
+            default_list: None,
+        }
+    }
 }
 
 impl TasksConfig {
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use tasks_core::models::list::List;
+use tasks_core::models::task::Task;
+
+/// How many changes can be undone.
+const CAPACITY: usize = 100;
+
+/// Edits to the same task closer together than this are undone as one step,
+/// so typing a title or notes does not have to be undone one key at a time.
+const COALESCE_WINDOW: Duration = Duration::from_secs(1);
+
+/// A single mutation of the stored tasks and lists.
+#[derive(Debug, Clone)]
+pub enum Change {
+    CreateTask(Task),
+    PurgeTask(Task),
+    DeleteTask(Task),
+    RestoreTask(Task),
+    UpdateTask { before: Box<Task>, after: Box<Task> },
+    CreateList { list: List, tasks: Vec<Task> },
+    DeleteList { list: List, tasks: Vec<Task> },
+    UpdateList { before: List, after: List },
+}
+
+impl Change {
+    /// The change that reverts this one.
+    pub fn inverse(&self) -> Change {
+        match self.clone() {
+            Change::CreateTask(task) => Change::PurgeTask(task),
+            Change::PurgeTask(task) => Change::CreateTask(task),
+            Change::DeleteTask(task) => Change::RestoreTask(task),
+            Change::RestoreTask(task) => Change::DeleteTask(task),
+            Change::UpdateTask { before, after } => Change::UpdateTask {
+                before: after,
+                after: before,
+            },
+            Change::CreateList { list, tasks } => Change::DeleteList { list, tasks },
+            Change::DeleteList { list, tasks } => Change::CreateList { list, tasks },
+            Change::UpdateList { before, after } => Change::UpdateList {
+                before: after,
+                after: before,
+            },
+        }
+    }
+
+    pub fn is_list_change(&self) -> bool {
+        matches!(
+            self,
+            Change::CreateList { .. } | Change::DeleteList { .. } | Change::UpdateList { .. }
+        )
+    }
+}
+
+struct Entry {
+    change: Change,
+    recorded: Instant,
+}
+
+/// Undo and redo stacks for the changes made in this session.
+///
+/// Every task that is loaded or written is remembered as last saved, which is
+/// what an update is reverted to.
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Entry>,
+    redo: Vec<Change>,
+    saved: HashMap<String, Task>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remembers `tasks` as they are stored.
+    pub fn track(&mut self, tasks: &[Task]) {
+        for task in tasks {
+            self.saved.insert(task.id().clone(), task.clone());
+        }
+    }
+
+    /// The last saved version of the task with `id`.
+    pub fn saved(&self, id: &str) -> Option<&Task> {
+        self.saved.get(id)
+    }
+
+    /// Records a change that was just made and drops everything that could be redone.
+    pub fn record(&mut self, change: Change) {
+        self.remember(&change);
+        self.redo.clear();
+
+        if let (
+            Change::UpdateTask { after, .. },
+            Some(Entry {
+                change: Change::UpdateTask { after: last, .. },
+                recorded,
+            }),
+        ) = (&change, self.undo.last_mut())
+        {
+            if last.id() == after.id() && recorded.elapsed() < COALESCE_WINDOW {
+                *last = after.clone();
+                *recorded = Instant::now();
+                return;
+            }
+        }
+
+        self.undo.push(Entry {
+            change,
+            recorded: Instant::now(),
+        });
+        if self.undo.len() > CAPACITY {
+            self.undo.remove(0);
+        }
+    }
+
+    /// Pops the last change and returns what has to be applied to revert it.
+    pub fn undo(&mut self) -> Option<Change> {
+        let entry = self.undo.pop()?;
+        let inverse = entry.change.inverse();
+        self.remember(&inverse);
+        self.redo.push(entry.change);
+        Some(inverse)
+    }
+
+    /// Pops the last undone change and returns it so it can be applied again.
+    pub fn redo(&mut self) -> Option<Change> {
+        let change = self.redo.pop()?;
+        self.remember(&change);
+        self.undo.push(Entry {
+            change: change.clone(),
+            recorded: Instant::now(),
+        });
+        Some(change)
+    }
+
+    fn remember(&mut self, change: &Change) {
+        match change {
+            Change::CreateTask(task) | Change::RestoreTask(task) => {
+                self.saved.insert(task.id().clone(), task.clone());
+            }
+            Change::UpdateTask { after, .. } => {
+                self.saved.insert(after.id().clone(), *after.clone());
+            }
+            Change::PurgeTask(task) | Change::DeleteTask(task) => {
+                self.saved.remove(task.id());
+            }
+            Change::CreateList { tasks, .. } => self.track(tasks),
+            Change::DeleteList { .. } | Change::UpdateList { .. } => {}
+        }
+    }
+}
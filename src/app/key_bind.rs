@@ -29,6 +29,9 @@ pub fn key_binds() -> HashMap<KeyBind, Action> {
     bind!([Ctrl, Shift], Key::Character("n".into()), WindowNew);
     bind!([Ctrl], Key::Character(",".into()), Settings);
     bind!([Ctrl], Key::Character("i".into()), About);
+    bind!([Ctrl], Key::Character("f".into()), Search);
+    bind!([Ctrl], Key::Character("z".into()), Undo);
+    bind!([Ctrl, Shift], Key::Character("z".into()), Redo);
 
     key_binds
 }
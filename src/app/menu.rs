@@ -24,6 +24,10 @@ pub fn menu_bar<'a>(key_binds: &HashMap<KeyBind, Action>) -> Element<'a, Message
                     Item::Divider,
                     Item::Button(fl!("new-list"), Action::NewList),
                     Item::Divider,
+                    Item::Button(fl!("menu-import"), Action::Import),
+                    Item::Button(fl!("menu-export-all"), Action::ExportAll),
+                    Item::Button(fl!("menu-export-folder"), Action::ExportFolder),
+                    Item::Divider,
                     Item::Button(fl!("quit"), Action::WindowClose),
                 ],
             ),
@@ -33,6 +37,11 @@ pub fn menu_bar<'a>(key_binds: &HashMap<KeyBind, Action>) -> Element<'a, Message
             items(
                 key_binds,
                 vec![
+                    Item::Button(fl!("undo"), Action::Undo),
+                    Item::Button(fl!("redo"), Action::Redo),
+                    Item::Divider,
+                    Item::Button(fl!("search"), Action::Search),
+                    Item::Divider,
                     Item::Button(fl!("rename"), Action::RenameList),
                     Item::Divider,
                     Item::Button(fl!("icon"), Action::Icon),
@@ -0,0 +1,269 @@
+use std::error::Error;
+
+use chrono::{DateTime, Utc};
+use tasks_core::dbus::{Signal, TasksServer};
+use tasks_core::import::ImportedList;
+use tasks_core::markdown::Detail;
+use tasks_core::models::diagnostic::Diagnostic;
+use tasks_core::models::list::List;
+use tasks_core::models::smart_list::SmartList;
+use tasks_core::models::task::Task;
+use tasks_core::service::{Provider, TaskService};
+use tasks_core::services::caldav::CalDavAccount;
+
+use crate::app::history::Change;
+use crate::app::Message;
+use crate::{content, fl, todo, trash};
+
+/// A call to the storage, kept around so it can be run again if it fails.
+#[derive(Debug, Clone)]
+pub enum Operation {
+    FetchLists,
+    SyncLists,
+    FetchTasks(String),
+    FetchSmartList(SmartList),
+    FetchDeletedTasks,
+    FetchDiagnostics,
+    AcknowledgeDiagnostics(Vec<Diagnostic>),
+    BuildSearchIndex,
+    CreateList(List),
+    UpdateList(List),
+    DeleteList(List),
+    CreateTask(Task),
+    CreateOccurrence(Task),
+    UpdateTask {
+        task: Task,
+        expected: DateTime<Utc>,
+    },
+    DeleteTask(Task),
+    RestoreTask(Task),
+    PurgeTask(Task),
+    PurgeTrash(DateTime<Utc>),
+    ApplyChange(Change),
+    ExportAll,
+    ExportFolder,
+    SaveFile {
+        name: String,
+        lists: Vec<(List, Vec<Task>)>,
+        detail: Detail,
+    },
+    OpenFile,
+    Paste,
+    Import {
+        lists: Vec<ImportedList>,
+        list: Option<List>,
+    },
+    MigrateToSqlite,
+    PreviewCopy(Provider),
+    Copy(Provider),
+    SaveCalDavAccount(CalDavAccount),
+}
+
+impl Operation {
+    /// What the operation was trying to do, for the log.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Operation::FetchLists | Operation::SyncLists => "load the lists",
+            Operation::FetchTasks(_) | Operation::FetchSmartList(_) => "load the tasks",
+            Operation::FetchDeletedTasks => "load the trash",
+            Operation::FetchDiagnostics => "check for damaged files",
+            Operation::AcknowledgeDiagnostics(_) => "dismiss the damaged files",
+            Operation::BuildSearchIndex => "build the search index",
+            Operation::CreateList(_) => "create a list",
+            Operation::UpdateList(_) => "save a list",
+            Operation::DeleteList(_) => "delete a list",
+            Operation::CreateTask(_) => "create a task",
+            Operation::CreateOccurrence(_) => "create the next occurrence",
+            Operation::UpdateTask { .. } => "save a task",
+            Operation::DeleteTask(_) => "delete a task",
+            Operation::RestoreTask(_) => "restore a task",
+            Operation::PurgeTask(_) => "delete a task permanently",
+            Operation::PurgeTrash(_) => "empty the trash",
+            Operation::ApplyChange(_) => "undo or redo a change",
+            Operation::ExportAll | Operation::ExportFolder => "export the lists",
+            Operation::SaveFile { .. } => "save the file",
+            Operation::OpenFile => "open the file",
+            Operation::Paste => "read the clipboard",
+            Operation::Import { .. } => "import the tasks",
+            Operation::MigrateToSqlite => "move the data to SQLite",
+            Operation::PreviewCopy(_) => "check what would be copied",
+            Operation::Copy(_) => "copy the data",
+            Operation::SaveCalDavAccount(_) => "save the CalDAV account",
+        }
+    }
+
+    /// Whether the operation changes the stored data rather than only reading it.
+    pub fn is_write(&self) -> bool {
+        !matches!(
+            self,
+            Operation::FetchLists
+                | Operation::SyncLists
+                | Operation::FetchTasks(_)
+                | Operation::FetchSmartList(_)
+                | Operation::FetchDeletedTasks
+                | Operation::FetchDiagnostics
+                | Operation::BuildSearchIndex
+                | Operation::ExportAll
+                | Operation::ExportFolder
+                | Operation::OpenFile
+                | Operation::Paste
+                | Operation::PreviewCopy(_)
+        )
+    }
+
+    /// Runs the operation and returns the message that shows its result, if any.
+    pub async fn run(
+        self,
+        service: TaskService,
+        server: Option<TasksServer>,
+    ) -> Result<Option<Message>, Box<dyn Error>> {
+        let message = match self {
+            Operation::FetchLists => Message::PopulateLists(todo::fetch_lists(service).await?),
+            Operation::SyncLists => Message::SyncLists(todo::fetch_lists(service).await?),
+            Operation::FetchTasks(list_id) => {
+                let tasks = todo::fetch_tasks(list_id, service.clone()).await?;
+                // Damaged task files are set aside while the tasks are read.
+                Message::SetTasks(tasks, todo::fetch_diagnostics(service).await?)
+            }
+            Operation::FetchSmartList(smart_list) => {
+                let tasks = todo::fetch_smart_list(smart_list, service.clone()).await?;
+                Message::SetTasks(tasks, todo::fetch_diagnostics(service).await?)
+            }
+            Operation::FetchDeletedTasks => {
+                let tasks = todo::fetch_deleted_tasks(service).await?;
+                Message::Trash(trash::Message::SetItems(tasks))
+            }
+            Operation::FetchDiagnostics => {
+                Message::SetDiagnostics(todo::fetch_diagnostics(service).await?)
+            }
+            Operation::AcknowledgeDiagnostics(diagnostics) => {
+                todo::acknowledge_diagnostics(diagnostics, service).await?;
+                return Ok(None);
+            }
+            Operation::BuildSearchIndex => {
+                Message::SetSearchIndex(todo::build_search_index(service).await?)
+            }
+            Operation::CreateList(list) => {
+                Message::AddList(todo::create_list(list, service).await?)
+            }
+            Operation::UpdateList(list) => {
+                todo::update_list(list, service).await?;
+                Message::Publish(Signal::ListsChanged)
+            }
+            Operation::DeleteList(list) => {
+                let tasks = todo::delete_list(list.id().clone(), service).await?;
+                Message::ListDeleted(list, tasks)
+            }
+            Operation::CreateTask(task) => {
+                todo::create_task(task.clone(), service).await?;
+                Message::Publish(Signal::TaskCreated(task))
+            }
+            Operation::CreateOccurrence(task) => {
+                match todo::create_next_occurrence(task, service).await? {
+                    Some(next) => Message::OccurrenceCreated(next),
+                    None => return Ok(None),
+                }
+            }
+            Operation::UpdateTask { task, expected } => {
+                todo::update_task(task.clone(), expected, service).await?;
+                Message::TaskSaved(task)
+            }
+            Operation::DeleteTask(task) => {
+                todo::delete_task(task.parent.clone(), task.id().clone(), service).await?;
+                Message::Publish(Signal::TaskDeleted(task))
+            }
+            Operation::RestoreTask(task) => {
+                todo::restore_task(task.parent.clone(), task.id().clone(), service).await?;
+                Message::Publish(Signal::TaskCreated(task))
+            }
+            Operation::PurgeTask(task) => {
+                todo::purge_task(task.parent.clone(), task.id().clone(), service).await?;
+                Message::Publish(Signal::TaskDeleted(task))
+            }
+            Operation::PurgeTrash(before) => {
+                let purged = todo::purge_trash(before, service).await?;
+                if purged > 0 {
+                    log::info!("purged {purged} tasks from the trash");
+                }
+                return Ok(None);
+            }
+            Operation::ApplyChange(change) => {
+                let list_change = change.is_list_change();
+                let signal = match &change {
+                    Change::CreateTask(task) | Change::RestoreTask(task) => {
+                        Signal::TaskCreated(task.clone())
+                    }
+                    Change::PurgeTask(task) | Change::DeleteTask(task) => {
+                        Signal::TaskDeleted(task.clone())
+                    }
+                    Change::UpdateTask { after, .. } => Signal::TaskUpdated(*after.clone()),
+                    Change::CreateList { .. }
+                    | Change::DeleteList { .. }
+                    | Change::UpdateList { .. } => Signal::ListsChanged,
+                };
+                todo::apply_change(change, service).await?;
+                if let Some(server) = server {
+                    if let Err(err) = server.emit(signal).await {
+                        log::warn!("failed to announce change: {err}");
+                    }
+                }
+                if list_change {
+                    Message::ReloadLists
+                } else {
+                    Message::Refresh
+                }
+            }
+            Operation::ExportAll => {
+                let lists = todo::export_all(service).await?;
+                Message::SaveFile(format!("{}.ics", fl!("tasks")), lists, Detail::Full)
+            }
+            Operation::ExportFolder => {
+                let lists = todo::export_all(service).await?;
+                let Some(path) = todo::save_folder(lists).await? else {
+                    return Ok(None);
+                };
+                Message::FileSaved(path)
+            }
+            Operation::SaveFile {
+                name,
+                lists,
+                detail,
+            } => {
+                let Some(path) = todo::save_file(name, lists, detail).await? else {
+                    return Ok(None);
+                };
+                Message::FileSaved(path)
+            }
+            Operation::OpenFile => {
+                let Some(lists) = todo::open_file().await? else {
+                    return Ok(None);
+                };
+                Message::OpenImportDialog(lists)
+            }
+            Operation::Paste => Message::OpenImportDialog(todo::paste()?),
+            Operation::Import { lists, list } => {
+                let new_lists = list.is_none();
+                let count = todo::import(lists, list, service).await?;
+                Message::Imported(count, new_lists)
+            }
+            Operation::MigrateToSqlite => {
+                TaskService::migrate_to_sqlite(&service.app_id).await?;
+                Message::ReloadLists
+            }
+            Operation::PreviewCopy(from) => {
+                let summary =
+                    TaskService::copy(&service.app_id, from, service.provider, true).await?;
+                Message::OpenMigrationDialog(from, summary)
+            }
+            Operation::Copy(from) => {
+                TaskService::copy(&service.app_id, from, service.provider, false).await?;
+                Message::ReloadLists
+            }
+            Operation::SaveCalDavAccount(account) => {
+                account.save(&service.app_id).await?;
+                Message::ReloadLists
+            }
+        };
+        Ok(Some(message))
+    }
+}
@@ -0,0 +1,523 @@
+use std::error::Error;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use chrono::{Days, Local, NaiveDate, TimeZone, Utc};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use cosmic::cosmic_config::{Config, ConfigGet};
+use tasks_core::ical;
+use tasks_core::import;
+use tasks_core::markdown::{self, Detail};
+use tasks_core::models::list::List;
+use tasks_core::models::priority::Priority;
+use tasks_core::models::query::{TaskQuery, TaskSort};
+use tasks_core::models::status::Status;
+use tasks_core::models::task::Task;
+use tasks_core::service::{Provider, TaskService};
+use tasks_core::todo_txt;
+
+mod print;
+
+/// The app whose storage and settings the command line shares.
+const APP_ID: &str = "dev.edfloreshz.Tasks";
+const CONFIG_VERSION: u64 = 1;
+
+/// Manage the lists and tasks of Tasks from a terminal or a script.
+#[derive(Parser)]
+#[command(name = "tasks-cli", version)]
+struct Cli {
+    /// Print JSON instead of text.
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show every list.
+    Lists,
+    /// Show the tasks that match the filters, leaving out completed tasks by default.
+    Tasks(Filter),
+    /// Add a task.
+    Add(Add),
+    /// Mark tasks as completed.
+    Complete {
+        /// Ids of the tasks, or unique prefixes of them.
+        #[arg(required = true)]
+        ids: Vec<String>,
+        /// Mark the tasks as not completed instead.
+        #[arg(long)]
+        reopen: bool,
+    },
+    /// Change a task.
+    Edit(Edit),
+    /// Move tasks to the trash.
+    Delete {
+        /// Ids of the tasks, or unique prefixes of them.
+        #[arg(required = true)]
+        ids: Vec<String>,
+        /// Delete the tasks permanently instead.
+        #[arg(long)]
+        purge: bool,
+    },
+    /// Print lists and their tasks as Markdown, iCalendar or todo.txt.
+    Export {
+        /// Only this list, by name or id.
+        #[arg(long, short)]
+        list: Option<String>,
+        #[arg(long, short, value_enum, default_value_t = FormatArg::Markdown)]
+        format: FormatArg,
+        /// Write every field of the tasks into Markdown, the way the Obsidian
+        /// Tasks plugin does.
+        #[arg(long)]
+        details: bool,
+        /// Write a Markdown file for each list into this folder instead.
+        #[arg(long, conflicts_with = "format")]
+        dir: Option<PathBuf>,
+    },
+    /// Add the tasks of an iCalendar, todo.txt or Markdown file, in a new list
+    /// for each calendar, project or heading in it.
+    Import {
+        file: PathBuf,
+        /// Add the tasks to this list instead, by name or id.
+        #[arg(long, short)]
+        list: Option<String>,
+    },
+}
+
+#[derive(Args)]
+struct Filter {
+    /// Only tasks in this list, by name or id.
+    #[arg(long, short)]
+    list: Option<String>,
+    /// Include completed tasks.
+    #[arg(long, short)]
+    all: bool,
+    /// Only completed tasks.
+    #[arg(long, conflicts_with = "all")]
+    completed: bool,
+    /// Only tasks with at least this priority.
+    #[arg(long, short, value_enum)]
+    priority: Option<PriorityArg>,
+    /// Only tasks with this tag, can be repeated.
+    #[arg(long = "tag", short)]
+    tags: Vec<String>,
+    /// Only tasks due before this date.
+    #[arg(long, value_parser = parse_date)]
+    due_before: Option<NaiveDate>,
+    /// Only tasks due after this date.
+    #[arg(long, value_parser = parse_date)]
+    due_after: Option<NaiveDate>,
+    /// Only favorite tasks.
+    #[arg(long)]
+    favorite: bool,
+    /// Only tasks marked for today.
+    #[arg(long)]
+    today: bool,
+    /// Only tasks whose title or notes contain this text.
+    #[arg(long, short)]
+    search: Option<String>,
+    #[arg(long, value_enum)]
+    sort: Option<SortArg>,
+    /// Sort in descending order.
+    #[arg(long)]
+    reverse: bool,
+    /// Show at most this many tasks.
+    #[arg(long)]
+    limit: Option<usize>,
+}
+
+#[derive(Args)]
+struct Add {
+    title: String,
+    /// The list to add the task to, by name or id. Defaults to the default list of the app.
+    #[arg(long, short)]
+    list: Option<String>,
+    #[arg(long, short, value_parser = parse_date)]
+    due: Option<NaiveDate>,
+    #[arg(long, short, value_enum)]
+    priority: Option<PriorityArg>,
+    /// A tag, can be repeated.
+    #[arg(long = "tag", short)]
+    tags: Vec<String>,
+    #[arg(long, short)]
+    notes: Option<String>,
+    #[arg(long)]
+    favorite: bool,
+    /// Mark the task for today.
+    #[arg(long)]
+    today: bool,
+}
+
+#[derive(Args)]
+struct Edit {
+    /// Id of the task, or a unique prefix of it.
+    id: String,
+    #[arg(long)]
+    title: Option<String>,
+    #[arg(long, value_parser = parse_date, conflicts_with = "no_due")]
+    due: Option<NaiveDate>,
+    /// Remove the due date.
+    #[arg(long)]
+    no_due: bool,
+    #[arg(long, short, value_enum)]
+    priority: Option<PriorityArg>,
+    /// Add a tag, can be repeated.
+    #[arg(long = "tag", short)]
+    tags: Vec<String>,
+    /// Remove a tag, can be repeated.
+    #[arg(long = "untag")]
+    untags: Vec<String>,
+    #[arg(long, short)]
+    notes: Option<String>,
+    #[arg(long, action = ArgAction::Set)]
+    favorite: Option<bool>,
+    #[arg(long, action = ArgAction::Set)]
+    today: Option<bool>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PriorityArg {
+    Low,
+    Normal,
+    High,
+}
+
+impl From<PriorityArg> for Priority {
+    fn from(value: PriorityArg) -> Self {
+        match value {
+            PriorityArg::Low => Priority::Low,
+            PriorityArg::Normal => Priority::Normal,
+            PriorityArg::High => Priority::High,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FormatArg {
+    Markdown,
+    Ics,
+    TodoTxt,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SortArg {
+    Title,
+    Due,
+    Priority,
+    Created,
+    Modified,
+    Completed,
+}
+
+impl From<SortArg> for TaskSort {
+    fn from(value: SortArg) -> Self {
+        match value {
+            SortArg::Title => TaskSort::Title,
+            SortArg::Due => TaskSort::DueDate,
+            SortArg::Priority => TaskSort::Priority,
+            SortArg::Created => TaskSort::CreatedDate,
+            SortArg::Modified => TaskSort::ModifiedDate,
+            SortArg::Completed => TaskSort::CompletedDate,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match runtime.block_on(run(cli)) {
+        Ok(()) => ExitCode::SUCCESS,
+        // The output was piped into a command that stopped reading, such as `head`.
+        Err(err)
+            if err
+                .downcast_ref::<io::Error>()
+                .is_some_and(|err| err.kind() == io::ErrorKind::BrokenPipe) =>
+        {
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let config = Config::new(APP_ID, CONFIG_VERSION).ok();
+    let provider: Provider = config
+        .as_ref()
+        .and_then(|config| config.get("provider").ok())
+        .unwrap_or_default();
+    let mut service = TaskService::new(APP_ID, provider)
+        .get_service()
+        .ok_or("the storage of the tasks could not be opened")?;
+
+    match cli.command {
+        Command::Lists => {
+            let lists = service.get_lists().await?;
+            let mut counts = Vec::with_capacity(lists.len());
+            for list in &lists {
+                counts.push(service.get_tasks_from_list(list.id().clone()).await?.len());
+            }
+            print::lists(&lists, &counts, cli.json)?;
+        }
+        Command::Tasks(filter) => {
+            let lists = service.get_lists().await?;
+            let mut query = TaskQuery {
+                list: filter
+                    .list
+                    .as_deref()
+                    .map(|list| find_list(&lists, list).map(|list| list.id().clone()))
+                    .transpose()?,
+                status: if filter.completed {
+                    Some(Status::Completed)
+                } else if filter.all {
+                    None
+                } else {
+                    Some(Status::NotStarted)
+                },
+                min_priority: filter.priority.map(Priority::from),
+                due_before: filter.due_before.map(to_utc),
+                due_after: filter.due_after.map(to_utc),
+                tags: filter.tags,
+                favorite: filter.favorite.then_some(true),
+                today: filter.today.then_some(true),
+                text: filter.search,
+                descending: filter.reverse,
+                limit: filter.limit,
+                ..Default::default()
+            };
+            if let Some(sort) = filter.sort {
+                query.sort = sort.into();
+            }
+            let tasks = service.query(&query).await?;
+            print::tasks(&tasks, &lists, cli.json)?;
+        }
+        Command::Add(add) => {
+            let lists = service.get_lists().await?;
+            let list = match &add.list {
+                Some(list) => find_list(&lists, list)?,
+                None => {
+                    let default: Option<String> = config
+                        .as_ref()
+                        .and_then(|config| config.get("default_list").ok())
+                        .flatten();
+                    default
+                        .and_then(|id| lists.iter().find(|list| *list.id() == id))
+                        .or(lists.first())
+                        .ok_or("there are no lists, create one in the app first")?
+                }
+            };
+            let mut task = Task::new(add.title, list.id().clone());
+            task.due_date = add.due.map(to_utc);
+            task.priority = add.priority.map(Priority::from).unwrap_or_default();
+            task.tags = add.tags;
+            task.notes = add.notes.unwrap_or_default();
+            task.favorite = add.favorite;
+            task.today = add.today;
+            let task = service.create_task(task).await?;
+            print::task(&task, &lists, cli.json)?;
+        }
+        Command::Complete { ids, reopen } => {
+            let lists = service.get_lists().await?;
+            let all = service.query(&every_task()).await?;
+            for id in ids {
+                let mut task = find_task(&all, &id)?.clone();
+                let was_completed = task.status == Status::Completed;
+                task.set_status(if reopen {
+                    Status::NotStarted
+                } else {
+                    Status::Completed
+                });
+                service.update_task(task.clone()).await?;
+                print::task(&task, &lists, cli.json)?;
+
+                // Completing a recurring task schedules its next occurrence, as in the app.
+                if !reopen && !was_completed {
+                    if let Some(next) = service.create_next_occurrence(&task).await? {
+                        print::task(&next, &lists, cli.json)?;
+                    }
+                }
+            }
+        }
+        Command::Edit(edit) => {
+            let lists = service.get_lists().await?;
+            let all = service.query(&every_task()).await?;
+            let mut task = find_task(&all, &edit.id)?.clone();
+            if let Some(title) = edit.title {
+                task.title = title;
+            }
+            if edit.no_due {
+                task.due_date = None;
+            } else if let Some(due) = edit.due {
+                task.due_date = Some(to_utc(due));
+            }
+            if let Some(priority) = edit.priority {
+                task.priority = priority.into();
+            }
+            task.tags.retain(|tag| !edit.untags.contains(tag));
+            for tag in edit.tags {
+                if !task.tags.contains(&tag) {
+                    task.tags.push(tag);
+                }
+            }
+            if let Some(notes) = edit.notes {
+                task.notes = notes;
+            }
+            if let Some(favorite) = edit.favorite {
+                task.favorite = favorite;
+            }
+            if let Some(today) = edit.today {
+                task.today = today;
+            }
+            service.update_task(task.clone()).await?;
+            print::task(&task, &lists, cli.json)?;
+        }
+        Command::Delete { ids, purge } => {
+            let all = service.query(&every_task()).await?;
+            for id in ids {
+                let task = find_task(&all, &id)?;
+                if purge {
+                    service
+                        .purge_task(task.parent.clone(), task.id().clone())
+                        .await?;
+                } else {
+                    service
+                        .delete_task(task.parent.clone(), task.id().clone())
+                        .await?;
+                }
+                print::deleted(task, cli.json)?;
+            }
+        }
+        Command::Export {
+            list,
+            format,
+            details,
+            dir,
+        } => {
+            let lists = service.get_lists().await?;
+            let lists = match list {
+                Some(list) => vec![find_list(&lists, &list)?.clone()],
+                None => lists,
+            };
+            let mut exported = Vec::with_capacity(lists.len());
+            for list in lists {
+                let tasks = service.get_tasks_from_list(list.id().clone()).await?;
+                exported.push((list, tasks));
+            }
+            let detail = if details { Detail::Full } else { Detail::Plain };
+            if let Some(dir) = dir {
+                let written = markdown::export_folder(&dir, &exported, detail)?;
+                print::written(&written, cli.json)?;
+            } else if cli.json {
+                print::export_json(&exported)?;
+            } else {
+                match format {
+                    FormatArg::Markdown => {
+                        let documents: Vec<String> = exported
+                            .iter()
+                            .map(|(list, tasks)| markdown::export_list(list, tasks, detail))
+                            .collect();
+                        print::markdown(&documents.join("\n"))?;
+                    }
+                    FormatArg::Ics => print::document(&ical::export_lists(&exported))?,
+                    FormatArg::TodoTxt => print::document(&todo_txt::export(&exported))?,
+                }
+            }
+        }
+        Command::Import { file, list } => {
+            let contents = std::fs::read_to_string(&file)?;
+            let imported_lists = import::read(&contents, Some(&file))?;
+            let lists = service.get_lists().await?;
+            let target = list
+                .as_deref()
+                .map(|list| find_list(&lists, list).cloned())
+                .transpose()?;
+            let mut imported = vec![];
+            for imported_list in imported_lists {
+                let list = match &target {
+                    Some(list) => list.clone(),
+                    None => {
+                        let name = imported_list.name.unwrap_or_else(|| {
+                            file.file_stem()
+                                .map(|stem| stem.to_string_lossy().into_owned())
+                                .unwrap_or_default()
+                        });
+                        let mut list = List::new(&name);
+                        list.description = imported_list.description.unwrap_or_default();
+                        service.create_list(list).await?
+                    }
+                };
+                let count = import::save(service.as_mut(), &list, imported_list.tasks).await?;
+                imported.push((list, count));
+            }
+            print::imported(&imported, cli.json)?;
+        }
+    }
+    Ok(())
+}
+
+/// Every task, including the ones in the trash, so any of them can be addressed by id.
+fn every_task() -> TaskQuery {
+    TaskQuery {
+        include_deleted: true,
+        ..Default::default()
+    }
+}
+
+/// The task whose id is `id` or starts with it.
+fn find_task<'a>(tasks: &'a [Task], id: &str) -> Result<&'a Task, Box<dyn Error>> {
+    if let Some(task) = tasks.iter().find(|task| task.id() == id) {
+        return Ok(task);
+    }
+    let mut matches = tasks.iter().filter(|task| task.id().starts_with(id));
+    match (matches.next(), matches.next()) {
+        (Some(task), None) => Ok(task),
+        (Some(_), Some(_)) => Err(format!("more than one task id starts with {id}").into()),
+        (None, _) => Err(format!("no task with id {id}").into()),
+    }
+}
+
+/// The list with the id `name`, or named `name` ignoring case.
+fn find_list<'a>(lists: &'a [List], name: &str) -> Result<&'a List, Box<dyn Error>> {
+    lists
+        .iter()
+        .find(|list| list.id() == name)
+        .or_else(|| {
+            lists
+                .iter()
+                .find(|list| list.name.to_lowercase() == name.to_lowercase())
+        })
+        .ok_or_else(|| format!("no list named {name}").into())
+}
+
+/// Reads `YYYY-MM-DD`, `today` or `tomorrow`.
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    let today = Local::now().date_naive();
+    match value {
+        "today" => Ok(today),
+        "tomorrow" => today
+            .checked_add_days(Days::new(1))
+            .ok_or_else(|| "invalid date".to_string()),
+        _ => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map_err(|_| format!("{value} is not a date like 2024-12-31, today or tomorrow")),
+    }
+}
+
+/// Due dates are stored as midnight UTC of the day, as the date picker of the app does.
+fn to_utc(date: NaiveDate) -> chrono::DateTime<Utc> {
+    Utc.from_utc_datetime(&date.into())
+}
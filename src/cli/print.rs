@@ -0,0 +1,197 @@
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tasks_core::models::list::List;
+use tasks_core::models::priority::Priority;
+use tasks_core::models::status::Status;
+use tasks_core::models::task::Task;
+
+/// How many characters of an id are shown in text output, enough to address a task.
+const SHORT_ID: usize = 8;
+
+#[derive(Serialize)]
+struct ListSummary<'a> {
+    #[serde(flatten)]
+    list: &'a List,
+    tasks: usize,
+}
+
+#[derive(Serialize)]
+struct ExportedList<'a> {
+    #[serde(flatten)]
+    list: &'a List,
+    tasks: &'a [Task],
+}
+
+#[derive(Serialize)]
+struct Imported<'a> {
+    #[serde(flatten)]
+    list: &'a List,
+    imported: usize,
+}
+
+#[derive(Serialize)]
+struct Deleted<'a> {
+    id: &'a str,
+    deleted: bool,
+}
+
+pub fn lists(lists: &[List], counts: &[usize], json: bool) -> Result<(), Box<dyn Error>> {
+    if json {
+        let lists: Vec<ListSummary> = lists
+            .iter()
+            .zip(counts)
+            .map(|(list, tasks)| ListSummary {
+                list,
+                tasks: *tasks,
+            })
+            .collect();
+        print_json(&lists)?;
+        return Ok(());
+    }
+    for (list, count) in lists.iter().zip(counts) {
+        writeln!(
+            io::stdout().lock(),
+            "{}  {} {} ({count})",
+            short_id(list.id()),
+            list.icon.as_deref().unwrap_or_default(),
+            list.name
+        )?;
+    }
+    Ok(())
+}
+
+pub fn tasks(tasks: &[Task], lists: &[List], json: bool) -> Result<(), Box<dyn Error>> {
+    if json {
+        print_json(tasks)?;
+        return Ok(());
+    }
+    for task in tasks {
+        writeln!(io::stdout().lock(), "{}", line(task, lists))?;
+    }
+    Ok(())
+}
+
+pub fn task(task: &Task, lists: &[List], json: bool) -> Result<(), Box<dyn Error>> {
+    if json {
+        print_json(task)?;
+    } else {
+        writeln!(io::stdout().lock(), "{}", line(task, lists))?;
+    }
+    Ok(())
+}
+
+pub fn deleted(task: &Task, json: bool) -> Result<(), Box<dyn Error>> {
+    if json {
+        let deleted = Deleted {
+            id: task.id(),
+            deleted: true,
+        };
+        print_json(&deleted)?;
+    } else {
+        writeln!(
+            io::stdout().lock(),
+            "{}  deleted {}",
+            short_id(task.id()),
+            task.title
+        )?;
+    }
+    Ok(())
+}
+
+pub fn export_json(exported: &[(List, Vec<Task>)]) -> Result<(), Box<dyn Error>> {
+    let exported: Vec<ExportedList> = exported
+        .iter()
+        .map(|(list, tasks)| ExportedList { list, tasks })
+        .collect();
+    print_json(&exported)?;
+    Ok(())
+}
+
+fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<(), Box<dyn Error>> {
+    writeln!(
+        io::stdout().lock(),
+        "{}",
+        serde_json::to_string_pretty(value)?
+    )?;
+    Ok(())
+}
+
+pub fn markdown(markdown: &str) -> Result<(), Box<dyn Error>> {
+    writeln!(io::stdout().lock(), "{markdown}")?;
+    Ok(())
+}
+
+/// iCalendar and todo.txt already end each line, including the last one.
+pub fn document(document: &str) -> Result<(), Box<dyn Error>> {
+    write!(io::stdout().lock(), "{document}")?;
+    Ok(())
+}
+
+/// The files an export wrote.
+pub fn written(paths: &[PathBuf], json: bool) -> Result<(), Box<dyn Error>> {
+    if json {
+        print_json(paths)?;
+        return Ok(());
+    }
+    let mut stdout = io::stdout().lock();
+    for path in paths {
+        writeln!(stdout, "wrote {}", path.display())?;
+    }
+    Ok(())
+}
+
+/// How many tasks went into each list during an import.
+pub fn imported(imported: &[(List, usize)], json: bool) -> Result<(), Box<dyn Error>> {
+    if json {
+        let imported: Vec<Imported> = imported
+            .iter()
+            .map(|(list, count)| Imported {
+                list,
+                imported: *count,
+            })
+            .collect();
+        print_json(&imported)?;
+        return Ok(());
+    }
+    let mut stdout = io::stdout().lock();
+    for (list, count) in imported {
+        writeln!(stdout, "imported {count} tasks into {}", list.name)?;
+    }
+    Ok(())
+}
+
+/// A task on one line: its state, short id, title, due date, priority, tags and list.
+fn line(task: &Task, lists: &[List]) -> String {
+    let mut line = format!(
+        "[{}] {}  {}",
+        if task.status == Status::Completed {
+            "x"
+        } else {
+            " "
+        },
+        short_id(task.id()),
+        task.title
+    );
+    if let Some(due) = task.due_date {
+        line.push_str(&format!("  due {}", due.format("%Y-%m-%d")));
+    }
+    match task.priority {
+        Priority::Low => {}
+        Priority::Normal => line.push_str("  !"),
+        Priority::High => line.push_str("  !!"),
+    }
+    for tag in &task.tags {
+        line.push_str(&format!("  #{tag}"));
+    }
+    if let Some(list) = lists.iter().find(|list| *list.id() == task.parent) {
+        line.push_str(&format!("  ({})", list.name));
+    }
+    line
+}
+
+fn short_id(id: &str) -> &str {
+    id.get(..SHORT_ID).unwrap_or(id)
+}
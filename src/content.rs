@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use crate::app::icon_cache::IconCache;
 use cosmic::iced::alignment::{Horizontal, Vertical};
 use cosmic::iced::{Alignment, Length, Subscription};
@@ -5,6 +7,7 @@ use cosmic::iced_widget::row;
 use cosmic::{theme, widget, Apply, Element};
 use slotmap::{DefaultKey, SecondaryMap, SlotMap};
 use tasks_core::models::list::List;
+use tasks_core::models::smart_list::SmartList;
 use tasks_core::models::status::Status;
 use tasks_core::models::task::Task;
 
@@ -12,6 +15,7 @@ use crate::fl;
 
 pub struct Content {
     list: Option<List>,
+    smart_list: Option<SmartList>,
     tasks: SlotMap<DefaultKey, Task>,
     editing: SecondaryMap<DefaultKey, bool>,
     task_input_ids: SecondaryMap<DefaultKey, widget::Id>,
@@ -27,6 +31,8 @@ pub enum Message {
     Export(Vec<Task>),
     Input(String),
     List(Option<List>),
+    SmartList(SmartList, Option<List>),
+    Replace(Task),
     Select(Task),
     SetItems(Vec<Task>),
     TitleSubmit(DefaultKey),
@@ -37,10 +43,12 @@ pub enum Message {
 pub enum Command {
     Iced(cosmic::app::Command<super::app::Message>),
     GetTasks(String),
+    GetSmartList(SmartList),
     DisplayTask(Task),
     UpdateTask(Task),
-    Delete(String),
+    Delete(Task),
     CreateTask(Task),
+    CreateOccurrence(Task),
     Export(Vec<Task>),
 }
 
@@ -48,6 +56,7 @@ impl Content {
     pub fn new() -> Self {
         Self {
             list: None,
+            smart_list: None,
             tasks: SlotMap::new(),
             editing: SecondaryMap::new(),
             task_input_ids: SecondaryMap::new(),
@@ -61,15 +70,20 @@ impl Content {
             .style(theme::Button::Suggested)
             .padding(spacing.space_xxs)
             .on_press(Message::Export(self.tasks.values().cloned().collect()));
-        let default_icon = emojis::get_by_shortcode("pencil").unwrap().to_string();
-        let icon = list.icon.clone().unwrap_or(default_icon);
+        let (icon, name) = match self.smart_list {
+            Some(smart_list) => (smart_list.icon(), smart_list_title(smart_list)),
+            None => {
+                let default_icon = emojis::get_by_shortcode("pencil").unwrap().to_string();
+                (list.icon.clone().unwrap_or(default_icon), list.name.clone())
+            }
+        };
 
         widget::row::with_capacity(3)
             .align_items(Alignment::Center)
             .spacing(spacing.space_s)
             .padding([spacing.space_none, spacing.space_xxs])
             .push(widget::text(icon).size(spacing.space_m))
-            .push(widget::text::title3(&list.name).width(Length::Fill))
+            .push(widget::text::title3(name).width(Length::Fill))
             .push(export_button)
             .into()
     }
@@ -187,10 +201,16 @@ impl Content {
         match message {
             Message::List(list) => {
                 self.list.clone_from(&list);
+                self.smart_list = None;
                 if let Some(list) = list {
                     commands.push(Command::GetTasks(list.id().clone()));
                 }
             }
+            Message::SmartList(smart_list, default_list) => {
+                self.list = default_list;
+                self.smart_list = Some(smart_list);
+                commands.push(Command::GetSmartList(smart_list));
+            }
             Message::TitleUpdate(id, title) => {
                 if let Some(task) = self.tasks.get_mut(id) {
                     task.title = title;
@@ -204,7 +224,7 @@ impl Content {
             }
             Message::Delete(id) => {
                 if let Some(task) = self.tasks.remove(id) {
-                    commands.push(Command::Delete(task.id().clone()));
+                    commands.push(Command::Delete(task));
                 }
             }
             Message::EditMode(id, editing) => {
@@ -218,10 +238,30 @@ impl Content {
                 }
             }
             Message::SetItems(tasks) => {
+                // Tasks whose title is being edited keep what was typed, so
+                // reloading after a change on disk does not discard it.
+                let mut editing: HashMap<String, (Task, widget::Id)> = HashMap::new();
+                for (id, task) in &self.tasks {
+                    if self.editing.get(id).copied().unwrap_or(false) {
+                        let input_id = self.task_input_ids[id].clone();
+                        editing.insert(task.id().clone(), (task.clone(), input_id));
+                    }
+                }
                 self.tasks.clear();
+                self.editing.clear();
+                self.task_input_ids.clear();
                 for task in tasks {
-                    let id = self.tasks.insert(task);
-                    self.task_input_ids.insert(id, widget::Id::unique());
+                    match editing.remove(task.id()) {
+                        Some((task, input_id)) => {
+                            let id = self.tasks.insert(task);
+                            self.editing.insert(id, true);
+                            self.task_input_ids.insert(id, input_id);
+                        }
+                        None => {
+                            let id = self.tasks.insert(task);
+                            self.task_input_ids.insert(id, widget::Id::unique());
+                        }
+                    }
                 }
             }
             Message::Select(task) => {
@@ -230,19 +270,30 @@ impl Content {
             Message::Complete(id, complete) => {
                 let task = self.tasks.get_mut(id);
                 if let Some(task) = task {
-                    task.status = if complete {
+                    let was_completed = task.status == Status::Completed;
+                    task.set_status(if complete {
                         Status::Completed
                     } else {
                         Status::NotStarted
-                    };
+                    });
                     commands.push(Command::UpdateTask(task.clone()));
+
+                    // Completing a recurring task schedules its next occurrence.
+                    if complete && !was_completed && !task.recurrence.is_empty() {
+                        commands.push(Command::CreateOccurrence(task.clone()));
+                    }
                 }
             }
             Message::Input(input) => self.input = input,
             Message::AddTask => {
                 if let Some(list) = &self.list {
                     if !self.input.is_empty() {
-                        let task = Task::new(self.input.clone(), list.id().clone());
+                        let mut task = Task::new(self.input.clone(), list.id().clone());
+                        match self.smart_list {
+                            Some(SmartList::Today) => task.today = true,
+                            Some(SmartList::Favorites) => task.favorite = true,
+                            _ => {}
+                        }
                         commands.push(Command::CreateTask(task.clone()));
                         let id = self.tasks.insert(task);
                         self.task_input_ids.insert(id, widget::Id::unique());
@@ -260,6 +311,16 @@ impl Content {
                     commands.push(Command::UpdateTask(task.clone()));
                 }
             }
+            Message::Replace(updated_task) => {
+                // Only the shown copy is swapped, the task is saved by the caller.
+                if let Some(task) = self
+                    .tasks
+                    .values_mut()
+                    .find(|t| t.id() == updated_task.id())
+                {
+                    *task = updated_task;
+                }
+            }
             Message::Export(tasks) => {
                 commands.push(Command::Export(tasks));
             }
@@ -301,3 +362,13 @@ impl Content {
         Subscription::none()
     }
 }
+
+pub fn smart_list_title(smart_list: SmartList) -> String {
+    match smart_list {
+        SmartList::Today => fl!("today"),
+        SmartList::Favorites => fl!("favorites"),
+        SmartList::Scheduled => fl!("scheduled"),
+        SmartList::All => fl!("all"),
+        SmartList::Completed => fl!("completed"),
+    }
+}
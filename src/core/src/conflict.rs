@@ -0,0 +1,39 @@
+use std::fmt;
+
+use crate::models::task::Task;
+
+/// A task could not be saved because the stored copy was changed somewhere
+/// else, such as another window, after the copy being saved was read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// The version that was about to be saved.
+    pub ours: Task,
+    /// The version that is stored.
+    pub theirs: Task,
+}
+
+impl Conflict {
+    /// Our version, marked as based on the stored one so saving it replaces it.
+    pub fn keep_mine(self) -> Task {
+        let mut task = self.ours;
+        task.last_modified_date_time = self.theirs.last_modified_date_time;
+        task
+    }
+
+    /// The stored version, dropping our changes.
+    pub fn keep_theirs(self) -> Task {
+        self.theirs
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Task \"{}\" was changed somewhere else",
+            self.theirs.title
+        )
+    }
+}
+
+impl std::error::Error for Conflict {}
@@ -0,0 +1,583 @@
+use chrono::{DateTime, Utc};
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use serde::{Deserialize, Serialize};
+use zbus::fdo::{self, DBusProxy};
+use zbus::object_server::SignalContext;
+use zbus::zvariant::Type;
+use zbus::Connection;
+
+use crate::{
+    error::{Error, Result},
+    models::{list::List, priority::Priority, recurrence::Recurrence, status::Status, task::Task},
+    service::TaskService,
+    task_service::TasksProvider,
+};
+
+/// The name of the interface other applications talk to.
+pub const INTERFACE: &str = "dev.edfloreshz.Tasks1";
+
+/// The object the interface is served at.
+pub const PATH: &str = "/dev/edfloreshz/Tasks";
+
+/// A list as it is sent over D-Bus.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct ListInfo {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub icon: String,
+}
+
+/// A task as it is sent over D-Bus.
+///
+/// Dates are Unix timestamps in seconds and are zero when not set. The
+/// priority is 0 for low, 1 for normal and 2 for high, and the recurrence is
+/// an RRULE, empty when the task does not repeat. Neither sub-tasks nor
+/// whether a task repeats after it is completed are sent, and updating a
+/// task keeps them as they were.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct TaskInfo {
+    pub id: String,
+    pub list_id: String,
+    pub title: String,
+    pub notes: String,
+    pub completed: bool,
+    pub favorite: bool,
+    pub today: bool,
+    pub priority: u8,
+    pub tags: Vec<String>,
+    pub due_date: i64,
+    pub reminder_date: i64,
+    pub recurrence: String,
+}
+
+impl From<&List> for ListInfo {
+    fn from(list: &List) -> Self {
+        Self {
+            id: list.id.clone(),
+            name: list.name.clone(),
+            description: list.description.clone(),
+            icon: list.icon.clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&Task> for TaskInfo {
+    fn from(task: &Task) -> Self {
+        Self {
+            id: task.id.clone(),
+            list_id: task.parent.clone(),
+            title: task.title.clone(),
+            notes: task.notes.clone(),
+            completed: task.status == Status::Completed,
+            favorite: task.favorite,
+            today: task.today,
+            priority: task.priority as u8,
+            tags: task.tags.clone(),
+            due_date: task.due_date.map_or(0, |date| date.timestamp()),
+            reminder_date: task.reminder_date.map_or(0, |date| date.timestamp()),
+            recurrence: task.recurrence.to_string(),
+        }
+    }
+}
+
+impl TaskInfo {
+    /// Copies the fields that are sent over D-Bus onto `task`.
+    fn apply(self, task: &mut Task) -> fdo::Result<()> {
+        let priority = Priority::try_from(i32::from(self.priority))
+            .map_err(|_| fdo::Error::InvalidArgs(format!("invalid priority {}", self.priority)))?;
+        let recurrence = if self.recurrence.is_empty() {
+            Recurrence::default()
+        } else {
+            let mut recurrence: Recurrence = self.recurrence.parse().map_err(|_| {
+                fdo::Error::InvalidArgs(format!("invalid RRULE {}", self.recurrence))
+            })?;
+            recurrence.after_completion |= task.recurrence.after_completion;
+            recurrence
+        };
+        task.set_status(if self.completed {
+            Status::Completed
+        } else {
+            Status::NotStarted
+        });
+        task.title = self.title;
+        task.notes = self.notes;
+        task.favorite = self.favorite;
+        task.today = self.today;
+        task.priority = priority;
+        task.tags = self.tags;
+        task.due_date = timestamp(self.due_date)?;
+        task.reminder_date = timestamp(self.reminder_date)?;
+        task.recurrence = recurrence;
+        Ok(())
+    }
+}
+
+fn timestamp(seconds: i64) -> fdo::Result<Option<DateTime<Utc>>> {
+    if seconds == 0 {
+        return Ok(None);
+    }
+    DateTime::from_timestamp(seconds, 0)
+        .map(Some)
+        .ok_or_else(|| fdo::Error::InvalidArgs(format!("invalid timestamp {seconds}")))
+}
+
+/// A change to the tasks that is announced to other applications.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Signal {
+    /// A task was added to a list, or restored from the trash.
+    TaskCreated(Task),
+    /// A task was edited or completed.
+    TaskUpdated(Task),
+    /// A task was moved to the trash or deleted for good.
+    TaskDeleted(Task),
+    /// A list was created, renamed or deleted.
+    ListsChanged,
+}
+
+impl Signal {
+    async fn emit(&self, context: &SignalContext<'_>) -> zbus::Result<()> {
+        match self {
+            Signal::TaskCreated(task) => {
+                TasksInterface::task_created(context, TaskInfo::from(task)).await
+            }
+            Signal::TaskUpdated(task) => {
+                TasksInterface::task_updated(context, TaskInfo::from(task)).await
+            }
+            Signal::TaskDeleted(task) => {
+                TasksInterface::task_deleted(context, &task.parent, &task.id).await
+            }
+            Signal::ListsChanged => TasksInterface::lists_changed(context).await,
+        }
+    }
+}
+
+/// Reads and writes the tasks on behalf of other applications.
+struct TasksInterface {
+    service: TaskService,
+    changes: UnboundedSender<Signal>,
+}
+
+impl TasksInterface {
+    fn provider(&self) -> fdo::Result<Box<dyn TasksProvider>> {
+        self.service.get_service().ok_or_else(|| {
+            fdo::Error::Failed(format!(
+                "the {} provider is not available",
+                self.service.title()
+            ))
+        })
+    }
+
+    /// Announces a change made through the interface, both on the bus and to
+    /// the process serving it.
+    async fn announce(&self, context: &SignalContext<'_>, signal: Signal) -> fdo::Result<()> {
+        signal.emit(context).await?;
+        let _ = self.changes.unbounded_send(signal);
+        Ok(())
+    }
+}
+
+fn failed(err: Error) -> fdo::Error {
+    match err {
+        Error::TaskNotFound(_) | Error::ListNotFound(_) => {
+            fdo::Error::UnknownObject(err.to_string())
+        }
+        err => fdo::Error::Failed(err.to_string()),
+    }
+}
+
+/// Takes the changes of the processes of the app that are queued for the
+/// bus name, to announce them from the one that owns it: clients only listen
+/// to the signals of the owner.
+struct RelayInterface {
+    changes: UnboundedSender<Signal>,
+}
+
+#[zbus::interface(name = "dev.edfloreshz.Tasks1.Relay")]
+impl RelayInterface {
+    /// Announces a change, given as the RON of a [`Signal`].
+    async fn announce(
+        &self,
+        #[zbus(signal_context)] context: SignalContext<'_>,
+        signal: String,
+    ) -> fdo::Result<()> {
+        let signal: Signal =
+            ron::from_str(&signal).map_err(|err| fdo::Error::InvalidArgs(err.to_string()))?;
+        signal.emit(&context).await?;
+        let _ = self.changes.unbounded_send(signal);
+        Ok(())
+    }
+}
+
+#[zbus::proxy(
+    interface = "dev.edfloreshz.Tasks1.Relay",
+    default_path = "/dev/edfloreshz/Tasks"
+)]
+trait Relay {
+    fn announce(&self, signal: &str) -> zbus::Result<()>;
+}
+
+#[zbus::interface(name = "dev.edfloreshz.Tasks1")]
+impl TasksInterface {
+    async fn get_lists(&self) -> fdo::Result<Vec<ListInfo>> {
+        let lists = self.provider()?.get_lists().await.map_err(failed)?;
+        Ok(lists.iter().map(ListInfo::from).collect())
+    }
+
+    async fn get_tasks(&self, list_id: String) -> fdo::Result<Vec<TaskInfo>> {
+        let tasks = self
+            .provider()?
+            .get_tasks_from_list(list_id)
+            .await
+            .map_err(failed)?;
+        Ok(tasks.iter().map(TaskInfo::from).collect())
+    }
+
+    /// Creates a task in `task.list_id` and returns it with its new id.
+    async fn create_task(
+        &self,
+        #[zbus(signal_context)] context: SignalContext<'_>,
+        task: TaskInfo,
+    ) -> fdo::Result<TaskInfo> {
+        let mut service = self.provider()?;
+        service
+            .get_list(task.list_id.clone())
+            .await
+            .map_err(|_| fdo::Error::InvalidArgs(format!("no list with id {}", task.list_id)))?;
+        let mut created = Task::new(String::new(), task.list_id.clone());
+        task.apply(&mut created)?;
+        let created = service.create_task(created).await.map_err(failed)?;
+        let info = TaskInfo::from(&created);
+        self.announce(&context, Signal::TaskCreated(created))
+            .await?;
+        Ok(info)
+    }
+
+    async fn update_task(
+        &self,
+        #[zbus(signal_context)] context: SignalContext<'_>,
+        task: TaskInfo,
+    ) -> fdo::Result<()> {
+        let mut service = self.provider()?;
+        let mut updated = service
+            .get_task(task.list_id.clone(), task.id.clone())
+            .await
+            .map_err(failed)?;
+        task.apply(&mut updated)?;
+        service.update_task(updated.clone()).await.map_err(failed)?;
+        self.announce(&context, Signal::TaskUpdated(updated)).await
+    }
+
+    /// Completes a task, creating its next occurrence if it repeats.
+    async fn complete_task(
+        &self,
+        #[zbus(signal_context)] context: SignalContext<'_>,
+        list_id: String,
+        task_id: String,
+    ) -> fdo::Result<()> {
+        let mut service = self.provider()?;
+        let mut task = service.get_task(list_id, task_id).await.map_err(failed)?;
+        if task.status == Status::Completed {
+            return Ok(());
+        }
+        task.set_status(Status::Completed);
+        service.update_task(task.clone()).await.map_err(failed)?;
+        let next = service
+            .create_next_occurrence(&task)
+            .await
+            .map_err(failed)?;
+        self.announce(&context, Signal::TaskUpdated(task)).await?;
+        if let Some(next) = next {
+            self.announce(&context, Signal::TaskCreated(next)).await?;
+        }
+        Ok(())
+    }
+
+    #[zbus(signal)]
+    async fn task_created(context: &SignalContext<'_>, task: TaskInfo) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn task_updated(context: &SignalContext<'_>, task: TaskInfo) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn task_deleted(
+        context: &SignalContext<'_>,
+        list_id: &str,
+        task_id: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn lists_changed(context: &SignalContext<'_>) -> zbus::Result<()>;
+}
+
+/// A client for the interface, for applets and tests.
+#[zbus::proxy(
+    interface = "dev.edfloreshz.Tasks1",
+    default_service = "dev.edfloreshz.Tasks",
+    default_path = "/dev/edfloreshz/Tasks"
+)]
+pub trait Tasks {
+    fn get_lists(&self) -> zbus::Result<Vec<ListInfo>>;
+
+    fn get_tasks(&self, list_id: &str) -> zbus::Result<Vec<TaskInfo>>;
+
+    fn create_task(&self, task: &TaskInfo) -> zbus::Result<TaskInfo>;
+
+    fn update_task(&self, task: &TaskInfo) -> zbus::Result<()>;
+
+    fn complete_task(&self, list_id: &str, task_id: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn task_created(&self, task: TaskInfo) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn task_updated(&self, task: TaskInfo) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn task_deleted(&self, list_id: String, task_id: String) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn lists_changed(&self) -> zbus::Result<()>;
+}
+
+/// Serves the lists and tasks of a [`TaskService`] on D-Bus, so panel
+/// applets, launchers and scripts can read and change them.
+///
+/// The server owns the app id as its bus name, queueing behind the app or the
+/// daemon when one of them already serves it, and exports [`INTERFACE`] at
+/// [`PATH`]. Changes made through the interface are announced with signals;
+/// the process serving it announces its own ones with [`TasksServer::emit`].
+/// A server that is queued hands its changes to the owner of the name, which
+/// announces them and passes them on as changes made by another application.
+#[derive(Debug, Clone)]
+pub struct TasksServer {
+    connection: Connection,
+    name: String,
+}
+
+impl TasksServer {
+    /// Serves `service` on `connection`, returning the server along with the
+    /// changes other applications make through it.
+    pub async fn new(
+        service: TaskService,
+        connection: &Connection,
+    ) -> Result<(Self, UnboundedReceiver<Signal>)> {
+        let (sender, changes) = mpsc::unbounded();
+        let name = service.app_id.clone();
+        let relay = RelayInterface {
+            changes: sender.clone(),
+        };
+        let interface = TasksInterface {
+            service,
+            changes: sender,
+        };
+        connection.object_server().at(PATH, interface).await?;
+        connection.object_server().at(PATH, relay).await?;
+        connection
+            .request_name_with_flags(name.as_str(), Default::default())
+            .await?;
+        let server = Self {
+            connection: connection.clone(),
+            name,
+        };
+        Ok((server, changes))
+    }
+
+    /// Serves `service` on the session bus.
+    pub async fn session(service: TaskService) -> Result<(Self, UnboundedReceiver<Signal>)> {
+        let connection = Connection::session().await?;
+        Self::new(service, &connection).await
+    }
+
+    /// Serves the tasks of another provider from now on.
+    pub async fn set_service(&self, service: TaskService) -> Result<()> {
+        let interface = self.interface().await?;
+        interface.get_mut().await.service = service;
+        Ok(())
+    }
+
+    /// Announces a change made by the process serving the interface, or
+    /// hands it to the owner of the name while this server is queued.
+    pub async fn emit(&self, signal: Signal) -> Result<()> {
+        if !self.owns_name().await? {
+            let relay = RelayProxy::builder(&self.connection)
+                .destination(self.name.as_str())?
+                .build()
+                .await?;
+            relay.announce(&ron::to_string(&signal)?).await?;
+            return Ok(());
+        }
+        let interface = self.interface().await?;
+        signal.emit(interface.signal_context()).await?;
+        Ok(())
+    }
+
+    async fn owns_name(&self) -> Result<bool> {
+        let Some(unique_name) = self.connection.unique_name() else {
+            // A peer-to-peer connection has nobody else to defer to.
+            return Ok(true);
+        };
+        let owner = DBusProxy::new(&self.connection)
+            .await?
+            .get_name_owner(self.name.as_str().try_into().map_err(zbus::Error::from)?)
+            .await?;
+        Ok(owner.as_str() == unique_name.as_str())
+    }
+
+    async fn interface(&self) -> Result<zbus::InterfaceRef<TasksInterface>> {
+        Ok(self
+            .connection
+            .object_server()
+            .interface::<_, TasksInterface>(PATH)
+            .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::{Stream, StreamExt};
+
+    use super::*;
+    use crate::testing;
+
+    /// The next item of a signal stream, failing the test if none comes.
+    async fn next<S: Stream + Unpin>(stream: &mut S) -> S::Item {
+        tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .expect("timed out waiting for a signal")
+            .expect("the signal stream ended")
+    }
+
+    #[tokio::test]
+    async fn serves_lists_and_tasks() -> Result<()> {
+        let service = testing::service();
+        let list = service
+            .get_service()
+            .unwrap()
+            .create_list(List::new("Groceries"))
+            .await?;
+        let (bus, connection) = testing::peers().await?;
+        let (server, mut changes) = TasksServer::new(service.clone(), &bus).await?;
+        let tasks = TasksProxy::builder(&connection)
+            .destination(service.app_id.as_str())?
+            .build()
+            .await?;
+        let mut created = tasks.receive_task_created().await?;
+        let mut updated = tasks.receive_task_updated().await?;
+        let mut lists_changed = tasks.receive_lists_changed().await?;
+
+        assert_eq!(tasks.get_lists().await?, [ListInfo::from(&list)]);
+
+        let task = tasks
+            .create_task(&TaskInfo {
+                list_id: list.id.clone(),
+                title: "Milk".into(),
+                priority: Priority::High as u8,
+                ..Default::default()
+            })
+            .await?;
+        assert!(!task.id.is_empty());
+        assert_eq!(task.title, "Milk");
+        assert_eq!(next(&mut created).await.args()?.task, task);
+        let stored = service
+            .get_service()
+            .unwrap()
+            .get_task(list.id.clone(), task.id.clone())
+            .await?;
+        assert_eq!(next(&mut changes).await, Signal::TaskCreated(stored));
+        assert_eq!(
+            tasks.get_tasks(&list.id).await?,
+            std::slice::from_ref(&task)
+        );
+
+        let renamed = TaskInfo {
+            title: "Oat milk".into(),
+            ..task.clone()
+        };
+        tasks.update_task(&renamed).await?;
+        assert_eq!(next(&mut updated).await.args()?.task, renamed);
+        assert_eq!(
+            tasks.get_tasks(&list.id).await?,
+            std::slice::from_ref(&renamed)
+        );
+
+        tasks.complete_task(&list.id, &task.id).await?;
+        let completed = next(&mut updated).await.args()?.task;
+        assert!(completed.completed);
+        assert_eq!(tasks.get_tasks(&list.id).await?, [completed]);
+
+        let missing = tasks.complete_task(&list.id, "missing").await;
+        assert!(missing.is_err());
+
+        server.emit(Signal::ListsChanged).await?;
+        next(&mut lists_changed).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn completing_a_repeating_task_creates_the_next_one() -> Result<()> {
+        let service = testing::service();
+        let list = service
+            .get_service()
+            .unwrap()
+            .create_list(List::new("Chores"))
+            .await?;
+        let (bus, connection) = testing::peers().await?;
+        let _server = TasksServer::new(service.clone(), &bus).await?;
+        let tasks = TasksProxy::builder(&connection)
+            .destination(service.app_id.as_str())?
+            .build()
+            .await?;
+        let mut created = tasks.receive_task_created().await?;
+
+        // A due date without a time of day, which repeats on UTC dates.
+        let tomorrow = Utc::now().date_naive() + chrono::Days::new(1);
+        let due = tomorrow.and_time(Default::default()).and_utc();
+        let task = tasks
+            .create_task(&TaskInfo {
+                list_id: list.id.clone(),
+                title: "Water the plants".into(),
+                due_date: due.timestamp(),
+                recurrence: "FREQ=WEEKLY".into(),
+                ..Default::default()
+            })
+            .await?;
+        next(&mut created).await;
+
+        tasks.complete_task(&list.id, &task.id).await?;
+        let next_task = next(&mut created).await.args()?.task;
+        assert_eq!(next_task.title, task.title);
+        assert!(!next_task.completed);
+        assert_eq!(
+            next_task.due_date,
+            (due + chrono::Duration::weeks(1)).timestamp()
+        );
+        assert_eq!(tasks.get_tasks(&list.id).await?.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn announces_relayed_changes() -> Result<()> {
+        let service = testing::service();
+        let (bus, connection) = testing::peers().await?;
+        let (_server, mut changes) = TasksServer::new(service.clone(), &bus).await?;
+        let tasks = TasksProxy::builder(&connection)
+            .destination(service.app_id.as_str())?
+            .build()
+            .await?;
+        let relay = RelayProxy::builder(&connection)
+            .destination(service.app_id.as_str())?
+            .build()
+            .await?;
+        let mut updated = tasks.receive_task_updated().await?;
+
+        let task = Task::new("Milk".into(), "list".into());
+        let signal = Signal::TaskUpdated(task.clone());
+        relay.announce(&ron::to_string(&signal)?).await?;
+        assert_eq!(next(&mut updated).await.args()?.task, TaskInfo::from(&task));
+        assert_eq!(next(&mut changes).await, signal);
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+
+use crate::conflict::Conflict;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Why a provider could not read or write tasks and lists.
+#[derive(Debug)]
+pub enum Error {
+    /// There is no task with this id.
+    TaskNotFound(String),
+    /// There is no list with this id.
+    ListNotFound(String),
+    /// A task or list with this id already exists.
+    AlreadyExists(String),
+    /// The task was changed somewhere else after it was read.
+    Conflict(Box<Conflict>),
+    /// Stored data could not be understood, such as a damaged file or a
+    /// database row holding a value out of range.
+    Corrupt(String),
+    /// The files holding the data could not be read or written.
+    Io(std::io::Error),
+    /// The database could not be read or written.
+    Database(sqlx::Error),
+    /// The server could not be reached.
+    Network(reqwest::Error),
+    /// The server answered a request with an error status.
+    Server { status: u16, url: String },
+    /// The keyring holding the password of an account could not be used.
+    Keyring(zbus::Error),
+    /// The session bus, or a service on it such as the notification daemon,
+    /// could not be used.
+    Bus(zbus::Error),
+    /// The data directory could not be watched for changes.
+    Watch(notify::Error),
+    /// The provider cannot do this.
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::TaskNotFound(id) => write!(f, "Task {id} does not exist"),
+            Error::ListNotFound(id) => write!(f, "List {id} does not exist"),
+            Error::AlreadyExists(id) => write!(f, "{id} already exists"),
+            Error::Conflict(conflict) => conflict.fmt(f),
+            Error::Corrupt(message) => write!(f, "Corrupt data: {message}"),
+            Error::Io(err) => err.fmt(f),
+            Error::Database(err) => err.fmt(f),
+            Error::Network(err) => err.fmt(f),
+            Error::Server { status, url } => write!(f, "The server answered {status} to {url}"),
+            Error::Keyring(err) => write!(f, "The keyring could not be used: {err}"),
+            Error::Bus(err) => err.fmt(f),
+            Error::Watch(err) => err.fmt(f),
+            Error::Unsupported(operation) => write!(f, "{operation} is not supported"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Conflict(conflict) => Some(conflict.as_ref()),
+            Error::Io(err) => Some(err),
+            Error::Database(err) => Some(err),
+            Error::Network(err) => Some(err),
+            Error::Keyring(err) | Error::Bus(err) => Some(err),
+            Error::Watch(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<Conflict> for Error {
+    fn from(conflict: Conflict) -> Self {
+        Error::Conflict(Box::new(conflict))
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::Io(err) => Error::Io(err),
+            sqlx::Error::Decode(err) | sqlx::Error::ColumnDecode { source: err, .. } => {
+                Error::Corrupt(err.to_string())
+            }
+            err => Error::Database(err),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Network(err)
+    }
+}
+
+impl From<zbus::Error> for Error {
+    fn from(err: zbus::Error) -> Self {
+        Error::Bus(err)
+    }
+}
+
+impl From<zbus::fdo::Error> for Error {
+    fn from(err: zbus::fdo::Error) -> Self {
+        Error::Bus(err.into())
+    }
+}
+
+impl From<notify::Error> for Error {
+    fn from(err: notify::Error) -> Self {
+        Error::Watch(err)
+    }
+}
+
+impl From<quick_xml::Error> for Error {
+    fn from(err: quick_xml::Error) -> Self {
+        Error::Corrupt(err.to_string())
+    }
+}
+
+impl From<ron::error::SpannedError> for Error {
+    fn from(err: ron::error::SpannedError) -> Self {
+        Error::Corrupt(err.to_string())
+    }
+}
+
+impl From<ron::Error> for Error {
+    fn from(err: ron::Error) -> Self {
+        Error::Corrupt(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Corrupt(err.to_string())
+    }
+}
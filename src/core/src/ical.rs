@@ -0,0 +1,805 @@
+//! Tasks as iCalendar VTODO components, following RFC 5545.
+//!
+//! Sub-tasks are separate components pointing at their parent with
+//! `RELATED-TO`, so a task is written as one component per task in its tree
+//! and [`nest`] puts the tree back together when reading.
+
+use std::fmt::Write;
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+
+use crate::{
+    error::{Error, Result},
+    import::ImportedList,
+    models::{list::List, priority::Priority, recurrence::Recurrence, status::Status, task::Task},
+};
+
+const PRODID: &str = "-//edfloreshz//Tasks//EN";
+
+const CALENDAR_NAME: &str = "X-WR-CALNAME";
+const CALENDAR_DESCRIPTION: &str = "X-WR-CALDESC";
+
+const FAVORITE: &str = "X-COSMIC-TASKS-FAVORITE";
+const TODAY: &str = "X-COSMIC-TASKS-TODAY";
+/// Set on tasks whose rule repeats after they are completed.
+const AFTER_COMPLETION: &str = "X-COSMIC-TASKS-AFTER-COMPLETION";
+
+/// The properties of a VTODO that are written from a task. Any other
+/// property of a component that is updated is kept as it was.
+const TASK_PROPERTIES: [&str; 17] = [
+    "UID",
+    "DTSTAMP",
+    "CREATED",
+    "LAST-MODIFIED",
+    "SUMMARY",
+    "DESCRIPTION",
+    "STATUS",
+    "COMPLETED",
+    "PERCENT-COMPLETE",
+    "PRIORITY",
+    "DUE",
+    "CATEGORIES",
+    "RRULE",
+    "RELATED-TO",
+    FAVORITE,
+    TODAY,
+    AFTER_COMPLETION,
+];
+
+/// A `BEGIN`/`END` block, such as a VCALENDAR or one of the VTODOs in it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Component {
+    pub name: String,
+    pub properties: Vec<Property>,
+    pub components: Vec<Component>,
+}
+
+/// A content line, such as `DUE;VALUE=DATE:20240131`. The value is kept as
+/// written, with its escapes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Property {
+    pub name: String,
+    pub params: Vec<(String, String)>,
+    pub value: String,
+}
+
+impl Property {
+    fn new(name: &str, value: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            params: vec![],
+            value: value.into(),
+        }
+    }
+
+    fn text(name: &str, value: &str) -> Self {
+        Self::new(name, escape(value))
+    }
+
+    fn date_time(name: &str, date: DateTime<Utc>) -> Self {
+        Self::new(name, date.format("%Y%m%dT%H%M%SZ").to_string())
+    }
+
+    fn param(mut self, name: &str, value: &str) -> Self {
+        self.params.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn get_param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl Component {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// A VCALENDAR holding `components`.
+    pub fn calendar(components: Vec<Component>) -> Self {
+        let mut calendar = Self::new("VCALENDAR");
+        calendar.properties = vec![
+            Property::new("VERSION", "2.0"),
+            Property::new("PRODID", PRODID),
+        ];
+        calendar.components = components;
+        calendar
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Property> {
+        self.properties
+            .iter()
+            .find(|property| property.name.eq_ignore_ascii_case(name))
+    }
+
+    fn text(&self, name: &str) -> Option<String> {
+        self.get(name).map(|property| unescape(&property.value))
+    }
+
+    /// Every VTODO in this component and the ones nested in it.
+    pub fn todos(&self) -> Vec<&Component> {
+        if self.name.eq_ignore_ascii_case("VTODO") {
+            return vec![self];
+        }
+        self.components.iter().flat_map(Component::todos).collect()
+    }
+
+    /// The component as iCalendar text, with lines folded at 75 octets.
+    pub fn to_ics(&self) -> String {
+        let mut ics = String::new();
+        self.write(&mut ics);
+        ics
+    }
+
+    fn write(&self, ics: &mut String) {
+        fold(ics, &format!("BEGIN:{}", self.name));
+        for property in &self.properties {
+            let mut line = property.name.clone();
+            for (name, value) in &property.params {
+                if value.contains([':', ';', ',']) {
+                    let _ = write!(line, ";{name}=\"{value}\"");
+                } else {
+                    let _ = write!(line, ";{name}={value}");
+                }
+            }
+            let _ = write!(line, ":{}", property.value);
+            fold(ics, &line);
+        }
+        for component in &self.components {
+            component.write(ics);
+        }
+        fold(ics, &format!("END:{}", self.name));
+    }
+}
+
+/// Reads the components of an iCalendar file, usually a single VCALENDAR.
+pub fn parse(ics: &str) -> Result<Vec<Component>> {
+    let unfolded = ics
+        .replace("\r\n ", "")
+        .replace("\r\n\t", "")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+    let mut stack: Vec<Component> = vec![];
+    let mut components = vec![];
+    for line in unfolded.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            continue;
+        }
+        let property = parse_line(line)?;
+        if property.name.eq_ignore_ascii_case("BEGIN") {
+            stack.push(Component::new(&property.value.to_ascii_uppercase()));
+        } else if property.name.eq_ignore_ascii_case("END") {
+            let component = stack
+                .pop()
+                .filter(|component| component.name.eq_ignore_ascii_case(&property.value))
+                .ok_or_else(|| Error::Corrupt(format!("Unexpected END:{}", property.value)))?;
+            match stack.last_mut() {
+                Some(parent) => parent.components.push(component),
+                None => components.push(component),
+            }
+        } else if let Some(component) = stack.last_mut() {
+            component.properties.push(property);
+        } else {
+            return Err(Error::Corrupt(format!(
+                "Property outside of a component: {line}"
+            )));
+        }
+    }
+    if let Some(component) = stack.pop() {
+        return Err(Error::Corrupt(format!("Missing END:{}", component.name)));
+    }
+    Ok(components)
+}
+
+fn parse_line(line: &str) -> Result<Property> {
+    // The value starts at the first colon that is not inside a quoted parameter.
+    let mut quoted = false;
+    let colon = line
+        .char_indices()
+        .find(|(_, c)| {
+            if *c == '"' {
+                quoted = !quoted;
+            }
+            *c == ':' && !quoted
+        })
+        .map(|(index, _)| index)
+        .ok_or_else(|| Error::Corrupt(format!("Invalid content line: {line}")))?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = split_unquoted(head, ';').into_iter();
+    let name = parts.next().unwrap_or_default().to_ascii_uppercase();
+    let params = parts
+        .filter_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            Some((
+                name.to_ascii_uppercase(),
+                value.trim_matches('"').to_string(),
+            ))
+        })
+        .collect();
+    Ok(Property {
+        name,
+        params,
+        value: value.to_string(),
+    })
+}
+
+fn split_unquoted(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut quoted = false;
+    let mut start = 0;
+    for (index, c) in value.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(&value[start..index]);
+            start = index + c.len_utf8();
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+/// Appends a content line, folding it so no line is longer than 75 octets.
+fn fold(ics: &mut String, line: &str) {
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            ics.push_str("\r\n ");
+            length = 1;
+        }
+        ics.push(c);
+        length += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => text.push('\n'),
+            Some(c) => text.push(c),
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+/// Splits a list value on the commas that are not escaped.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    for (index, c) in value.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            ',' if !escaped => {
+                items.push(unescape(&value[start..index]));
+                start = index + 1;
+            }
+            _ => escaped = false,
+        }
+    }
+    items.push(unescape(&value[start..]));
+    items
+}
+
+/// Reads a DATE or DATE-TIME value. Dates are midnight UTC, the way due dates
+/// are picked in the app. Times with a `TZID` or no zone at all are taken as
+/// local time.
+fn date_time(property: &Property) -> Option<DateTime<Utc>> {
+    let value = property.value.trim();
+    if is_date(property) {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some(date.and_hms_opt(0, 0, 0)?.and_utc());
+    }
+    if let Some(value) = value.strip_suffix('Z') {
+        let date = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+        return Some(date.and_utc());
+    }
+    let date = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Local
+        .from_local_datetime(&date)
+        .earliest()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// Whether a property holds a DATE rather than a DATE-TIME.
+fn is_date(property: &Property) -> bool {
+    property
+        .get_param("VALUE")
+        .is_some_and(|value| value.eq_ignore_ascii_case("DATE"))
+        || property.value.trim().len() == 8
+}
+
+/// Reads a DURATION value such as `-PT15M` or `P1DT2H`.
+fn duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let value = value.strip_prefix('P')?;
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    let mut time = false;
+    for c in value.chars() {
+        match c {
+            'T' => time = true,
+            '0'..='9' => number.push(c),
+            _ => {
+                let amount: i64 = number.parse().ok()?;
+                number.clear();
+                total += match (c, time) {
+                    ('W', false) => Duration::weeks(amount),
+                    ('D', false) => Duration::days(amount),
+                    ('H', true) => Duration::hours(amount),
+                    ('M', true) => Duration::minutes(amount),
+                    ('S', true) => Duration::seconds(amount),
+                    _ => return None,
+                };
+            }
+        }
+    }
+    Some(if negative { -total } else { total })
+}
+
+/// Reads a VTODO into a task without sub-tasks in `list_id`, along with the
+/// uid of its parent task if it is a sub-task.
+pub fn task_from_component(todo: &Component, list_id: &str) -> Result<(Task, Option<String>)> {
+    let uid = todo
+        .text("UID")
+        .filter(|uid| !uid.is_empty())
+        .ok_or_else(|| Error::Corrupt("VTODO without UID".to_string()))?;
+    let date = |name: &str| todo.get(name).and_then(date_time);
+
+    let mut task = Task::new(
+        todo.text("SUMMARY").unwrap_or_default(),
+        list_id.to_string(),
+    );
+    task.id = uid;
+    task.notes = todo.text("DESCRIPTION").unwrap_or_default();
+    let completed = todo
+        .get("STATUS")
+        .is_some_and(|status| status.value.eq_ignore_ascii_case("COMPLETED"));
+    task.status = if completed || todo.get("COMPLETED").is_some() {
+        Status::Completed
+    } else {
+        Status::NotStarted
+    };
+    task.completion_date = date("COMPLETED");
+    task.priority = match todo
+        .get("PRIORITY")
+        .and_then(|p| p.value.trim().parse::<u8>().ok())
+    {
+        Some(1..=4) => Priority::High,
+        Some(5) => Priority::Normal,
+        _ => Priority::Low,
+    };
+    task.due_date = date("DUE");
+    task.tags = todo
+        .properties
+        .iter()
+        .filter(|property| property.name == "CATEGORIES")
+        .flat_map(|property| split_list(&property.value))
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    if let Some(rule) = todo.get("RRULE") {
+        match rule.value.parse::<Recurrence>() {
+            Ok(recurrence) => task.recurrence = recurrence,
+            Err(err) => tracing::warn!("ignoring the rule of task {}: {err}", task.id),
+        }
+        task.recurrence.after_completion |= todo
+            .get(AFTER_COMPLETION)
+            .is_some_and(|value| value.value.eq_ignore_ascii_case("TRUE"));
+    }
+    task.favorite = todo
+        .get(FAVORITE)
+        .is_some_and(|value| value.value.eq_ignore_ascii_case("TRUE"));
+    task.today = todo
+        .get(TODAY)
+        .is_some_and(|value| value.value.eq_ignore_ascii_case("TRUE"));
+    task.reminder_date = todo
+        .components
+        .iter()
+        .filter(|alarm| alarm.name == "VALARM")
+        .find_map(|alarm| reminder(alarm, todo));
+    if let Some(created) = date("CREATED") {
+        task.created_date_time = created;
+    }
+    task.last_modified_date_time = date("LAST-MODIFIED")
+        .or_else(|| date("DTSTAMP"))
+        .unwrap_or(task.created_date_time);
+
+    let parent = todo
+        .properties
+        .iter()
+        .filter(|property| property.name == "RELATED-TO")
+        .find(|property| {
+            property
+                .get_param("RELTYPE")
+                .is_none_or(|reltype| reltype.eq_ignore_ascii_case("PARENT"))
+        })
+        .map(|property| unescape(&property.value));
+    Ok((task, parent))
+}
+
+/// When an alarm goes off, either at a set time or relative to the start or
+/// due date of its VTODO.
+fn reminder(alarm: &Component, todo: &Component) -> Option<DateTime<Utc>> {
+    let trigger = alarm.get("TRIGGER")?;
+    if trigger
+        .get_param("VALUE")
+        .is_some_and(|value| value.eq_ignore_ascii_case("DATE-TIME"))
+    {
+        return date_time(trigger);
+    }
+    let offset = duration(&trigger.value)?;
+    let related = match trigger.get_param("RELATED") {
+        Some(related) if related.eq_ignore_ascii_case("END") => "DUE",
+        _ => "DTSTART",
+    };
+    let anchor = todo
+        .get(related)
+        .or_else(|| todo.get("DUE"))
+        .and_then(date_time)?;
+    Some(anchor + offset)
+}
+
+/// Writes a task without its sub-tasks as a VTODO. When it updates an
+/// existing component, the properties tasks don't use and the alarms other
+/// than the reminder are kept.
+pub fn component_from_task(
+    task: &Task,
+    parent: Option<&str>,
+    existing: Option<&Component>,
+) -> Component {
+    let mut todo = Component::new("VTODO");
+    todo.properties.push(Property::text("UID", &task.id));
+    todo.properties
+        .push(Property::date_time("DTSTAMP", task.last_modified_date_time));
+    todo.properties
+        .push(Property::date_time("CREATED", task.created_date_time));
+    todo.properties.push(Property::date_time(
+        "LAST-MODIFIED",
+        task.last_modified_date_time,
+    ));
+    todo.properties.push(Property::text("SUMMARY", &task.title));
+    if !task.notes.is_empty() {
+        todo.properties
+            .push(Property::text("DESCRIPTION", &task.notes));
+    }
+    match task.status {
+        Status::Completed => {
+            todo.properties.push(Property::new("STATUS", "COMPLETED"));
+            todo.properties
+                .push(Property::new("PERCENT-COMPLETE", "100"));
+            let completed = task.completion_date.unwrap_or(task.last_modified_date_time);
+            todo.properties
+                .push(Property::date_time("COMPLETED", completed));
+        }
+        Status::NotStarted => todo
+            .properties
+            .push(Property::new("STATUS", "NEEDS-ACTION")),
+    }
+    match task.priority {
+        Priority::High => todo.properties.push(Property::new("PRIORITY", "1")),
+        Priority::Normal => todo.properties.push(Property::new("PRIORITY", "5")),
+        Priority::Low => {}
+    }
+    if let Some(due) = task.due_date {
+        let due = if due.num_seconds_from_midnight() == 0 {
+            Property::new("DUE", due.format("%Y%m%d").to_string()).param("VALUE", "DATE")
+        } else {
+            Property::date_time("DUE", due)
+        };
+        let has_start = existing.is_some_and(|existing| existing.get("DTSTART").is_some());
+        if !task.recurrence.is_empty() && !has_start {
+            // Recurrence rules are expanded from the start of a VTODO.
+            let mut start = due.clone();
+            start.name = "DTSTART".to_string();
+            todo.properties.push(start);
+        }
+        todo.properties.push(due);
+    }
+    if !task.tags.is_empty() {
+        let tags: Vec<String> = task.tags.iter().map(|tag| escape(tag)).collect();
+        todo.properties
+            .push(Property::new("CATEGORIES", tags.join(",")));
+    }
+    if !task.recurrence.is_empty() {
+        let all_day = todo
+            .get("DTSTART")
+            .or_else(|| existing.and_then(|existing| existing.get("DTSTART")))
+            .is_some_and(is_date);
+        todo.properties
+            .push(Property::new("RRULE", task.recurrence.to_rrule(all_day)));
+        if task.recurrence.after_completion {
+            todo.properties
+                .push(Property::new(AFTER_COMPLETION, "TRUE"));
+        }
+    }
+    if let Some(parent) = parent {
+        todo.properties
+            .push(Property::text("RELATED-TO", parent).param("RELTYPE", "PARENT"));
+    }
+    if task.favorite {
+        todo.properties.push(Property::new(FAVORITE, "TRUE"));
+    }
+    if task.today {
+        todo.properties.push(Property::new(TODAY, "TRUE"));
+    }
+
+    if let Some(existing) = existing {
+        todo.properties.extend(
+            existing
+                .properties
+                .iter()
+                .filter(|property| !TASK_PROPERTIES.contains(&property.name.as_str()))
+                .cloned(),
+        );
+        todo.components.extend(
+            existing
+                .components
+                .iter()
+                .filter(|component| reminder(component, existing).is_none())
+                .cloned(),
+        );
+    }
+    if let Some(reminder) = task.reminder_date {
+        let mut alarm = Component::new("VALARM");
+        alarm.properties = vec![
+            Property::new("ACTION", "DISPLAY"),
+            Property::date_time("TRIGGER", reminder).param("VALUE", "DATE-TIME"),
+            Property::text("DESCRIPTION", &task.title),
+        ];
+        todo.components.push(alarm);
+    }
+    todo
+}
+
+/// A task and each of its sub-tasks, without their own sub-tasks, along with
+/// the id of the task each one belongs to.
+pub fn flatten(task: &Task) -> Vec<(Task, Option<String>)> {
+    let mut tasks = vec![];
+    flatten_into(task, None, &mut tasks);
+    tasks
+}
+
+fn flatten_into(task: &Task, parent: Option<String>, tasks: &mut Vec<(Task, Option<String>)>) {
+    let mut flat = task.clone();
+    flat.sub_tasks.clear();
+    tasks.push((flat, parent));
+    for sub_task in &task.sub_tasks {
+        flatten_into(sub_task, Some(task.id.clone()), tasks);
+    }
+}
+
+/// Puts tasks read from separate VTODOs back into trees, following the parent
+/// of each one. Tasks whose parent is missing are kept at the top level.
+pub fn nest(tasks: Vec<(Task, Option<String>)>) -> Vec<Task> {
+    let ids: Vec<String> = tasks.iter().map(|(task, _)| task.id.clone()).collect();
+    let (roots, mut children): (Vec<_>, Vec<_>) = tasks
+        .into_iter()
+        .partition(|(_, parent)| parent.as_ref().is_none_or(|parent| !ids.contains(parent)));
+    let mut roots: Vec<Task> = roots.into_iter().map(|(task, _)| task).collect();
+    for root in &mut roots {
+        adopt(root, &mut children);
+    }
+    // Whatever is left forms a cycle, which is broken at the top level.
+    roots.extend(children.into_iter().map(|(task, _)| task));
+    roots
+}
+
+fn adopt(task: &mut Task, children: &mut Vec<(Task, Option<String>)>) {
+    let (mine, rest): (Vec<_>, Vec<_>) = children
+        .drain(..)
+        .partition(|(_, parent)| parent.as_deref() == Some(task.id.as_str()));
+    *children = rest;
+    for (mut sub_task, _) in mine {
+        sub_task.parent = task.id.clone();
+        adopt(&mut sub_task, children);
+        task.sub_tasks.push(sub_task);
+    }
+}
+
+/// Reads every VCALENDAR in an iCalendar file, with sub-tasks nested under
+/// their parents.
+pub fn import(ics: &str) -> Result<Vec<ImportedList>> {
+    let mut calendars = vec![];
+    for component in parse(ics)? {
+        let mut tasks = vec![];
+        for todo in component.todos() {
+            tasks.push(task_from_component(todo, "")?);
+        }
+        calendars.push(ImportedList {
+            name: component.text(CALENDAR_NAME),
+            description: component.text(CALENDAR_DESCRIPTION),
+            tasks: nest(tasks),
+        });
+    }
+    Ok(calendars)
+}
+
+/// Writes a list and its tasks as a VCALENDAR.
+pub fn export_list(list: &List, tasks: &[Task]) -> String {
+    calendar_from_list(list, tasks).to_ics()
+}
+
+/// Writes each list and its tasks as a VCALENDAR of its own, one after the
+/// other in the same file.
+pub fn export_lists(lists: &[(List, Vec<Task>)]) -> String {
+    lists
+        .iter()
+        .map(|(list, tasks)| export_list(list, tasks))
+        .collect()
+}
+
+fn calendar_from_list(list: &List, tasks: &[Task]) -> Component {
+    let todos = tasks
+        .iter()
+        .flat_map(flatten)
+        .map(|(task, parent)| component_from_task(&task, parent.as_deref(), None))
+        .collect();
+    let mut calendar = Component::calendar(todos);
+    calendar
+        .properties
+        .push(Property::new(CALENDAR_NAME, escape(&list.name)));
+    if !list.description.is_empty() {
+        calendar.properties.push(Property::new(
+            CALENDAR_DESCRIPTION,
+            escape(&list.description),
+        ));
+    }
+    calendar
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_folded_lines_and_parameters() -> Result<()> {
+        let ics = "BEGIN:VCALENDAR\r\n\
+                   VERSION:2.0\r\n\
+                   BEGIN:VTODO\r\n\
+                   UID:1\r\n\
+                   SUMMARY:Call the plumber\\, then\r\n \
+                   \x20the electrician\r\n\
+                   DESCRIPTION:First line\\nSecond\\; line\r\n\
+                   DUE;TZID=\"Europe/Paris: CET\":20240105T093000\r\n\
+                   BEGIN:VALARM\r\n\
+                   TRIGGER;RELATED=END:-PT15M\r\n\
+                   END:VALARM\r\n\
+                   END:VTODO\r\n\
+                   END:VCALENDAR\r\n";
+        let components = parse(ics)?;
+        assert_eq!(components.len(), 1);
+        let todos = components[0].todos();
+        let todo = todos[0];
+        assert_eq!(
+            todo.text("SUMMARY").as_deref(),
+            Some("Call the plumber, then the electrician")
+        );
+        assert_eq!(
+            todo.text("DESCRIPTION").as_deref(),
+            Some("First line\nSecond; line")
+        );
+        let due = todo.get("DUE").unwrap();
+        assert_eq!(due.get_param("TZID"), Some("Europe/Paris: CET"));
+        assert_eq!(due.value, "20240105T093000");
+        assert_eq!(todo.components[0].name, "VALARM");
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_unbalanced_components() {
+        assert!(parse("BEGIN:VCALENDAR\nBEGIN:VTODO\nEND:VCALENDAR\n").is_err());
+        assert!(parse("BEGIN:VCALENDAR\nBEGIN:VTODO\n").is_err());
+        assert!(parse("UID:1\n").is_err());
+    }
+
+    #[test]
+    fn round_trips_tasks() -> Result<()> {
+        let mut task = Task::new("Taxes; 2024, part 1".to_string(), String::new());
+        task.notes = "Receipts are\nin the drawer".to_string();
+        task.tags = vec!["home".to_string(), "money, paper".to_string()];
+        task.priority = Priority::High;
+        task.favorite = true;
+        task.today = true;
+        task.due_date = Some(at(2024, 4, 15, 0));
+        task.reminder_date = Some(at(2024, 4, 14, 9));
+        task.recurrence = "FREQ=YEARLY;UNTIL=20300415".parse()?;
+        task.recurrence.after_completion = true;
+        task.created_date_time = at(2024, 1, 1, 8);
+        task.last_modified_date_time = at(2024, 1, 2, 8);
+        let mut sub_task = Task::new("Find the receipts".to_string(), task.id.clone());
+        sub_task.set_status(Status::Completed);
+        sub_task.completion_date = Some(at(2024, 1, 3, 8));
+        sub_task.created_date_time = at(2024, 1, 1, 8);
+        sub_task.last_modified_date_time = at(2024, 1, 3, 8);
+        task.sub_tasks.push(sub_task);
+
+        let mut list = List::new("Home & garden");
+        list.description = "Chores, mostly".to_string();
+        let ics = export_list(&list, std::slice::from_ref(&task));
+        assert!(ics.lines().all(|line| line.len() <= 75));
+
+        let imported = import(&ics)?;
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].name.as_deref(), Some("Home & garden"));
+        assert_eq!(imported[0].description.as_deref(), Some("Chores, mostly"));
+        assert_eq!(imported[0].tasks, [task]);
+        Ok(())
+    }
+
+    #[test]
+    fn writes_standard_rules() -> Result<()> {
+        let mut task = Task::new("Water the plants".to_string(), String::new());
+        task.recurrence = "FREQ=WEEKLY;UNTIL=20240131".parse()?;
+        task.recurrence.after_completion = true;
+
+        task.due_date = Some(at(2024, 1, 3, 0));
+        let todo = component_from_task(&task, None, None);
+        assert_eq!(todo.get("DTSTART").unwrap().value, "20240103");
+        assert_eq!(
+            todo.get("RRULE").unwrap().value,
+            "FREQ=WEEKLY;UNTIL=20240131"
+        );
+        assert_eq!(todo.get(AFTER_COMPLETION).unwrap().value, "TRUE");
+
+        // A start that already has a time of day keeps UNTIL a date and time.
+        task.due_date = Some(at(2024, 1, 3, 18));
+        let updated = component_from_task(&task, None, Some(&todo));
+        assert_eq!(
+            updated.get("RRULE").unwrap().value,
+            "FREQ=WEEKLY;UNTIL=20240131"
+        );
+        let todo = component_from_task(&task, None, None);
+        assert_eq!(
+            todo.get("RRULE").unwrap().value,
+            "FREQ=WEEKLY;UNTIL=20240131T235959Z"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_what_tasks_does_not_use() -> Result<()> {
+        let ics = "BEGIN:VTODO\nUID:1\nSUMMARY:Old\nLOCATION:Office\nX-OTHER-APP:1\nEND:VTODO\n";
+        let existing = parse(ics)?.remove(0);
+        let (mut task, _) = task_from_component(&existing, "")?;
+        task.title = "New".to_string();
+        let todo = component_from_task(&task, None, Some(&existing));
+        assert_eq!(todo.text("SUMMARY").as_deref(), Some("New"));
+        assert_eq!(todo.get("LOCATION").unwrap().value, "Office");
+        assert_eq!(todo.get("X-OTHER-APP").unwrap().value, "1");
+        Ok(())
+    }
+}
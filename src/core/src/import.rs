@@ -0,0 +1,179 @@
+//! Tasks read from files written by other apps, such as iCalendar,
+//! todo.txt or Markdown files, and saving them into lists.
+
+use std::{collections::HashSet, path::Path};
+
+use uuid::Uuid;
+
+use crate::{
+    error::Result,
+    ical, markdown,
+    models::{list::List, query::TaskQuery, task::Task},
+    task_service::TasksProvider,
+    todo_txt,
+};
+
+/// A group of tasks read from a file, such as one calendar of an iCalendar
+/// file, along with the name and description it was given there.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImportedList {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// The top-level tasks, which don't belong to a list yet.
+    pub tasks: Vec<Task>,
+}
+
+/// The formats tasks can be imported from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    ICalendar,
+    TodoTxt,
+    Markdown,
+}
+
+impl Format {
+    /// The format of a file, from its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "ics" => Some(Self::ICalendar),
+            "txt" => Some(Self::TodoTxt),
+            "md" | "markdown" => Some(Self::Markdown),
+            _ => None,
+        }
+    }
+
+    /// The format of text that didn't come with a file name, such as text
+    /// pasted: Markdown when it has headings or checkbox items, and todo.txt
+    /// when it has neither.
+    pub fn detect(text: &str) -> Self {
+        if text.trim_start().starts_with("BEGIN:VCALENDAR") {
+            Self::ICalendar
+        } else if !markdown::import(text).is_empty() {
+            Self::Markdown
+        } else {
+            Self::TodoTxt
+        }
+    }
+}
+
+/// Reads the lists in `text`, in the format of the file at `path` if known,
+/// or else in the one the text looks like.
+pub fn read(text: &str, path: Option<&Path>) -> Result<Vec<ImportedList>> {
+    let format = path
+        .and_then(Format::from_path)
+        .unwrap_or_else(|| Format::detect(text));
+    match format {
+        Format::ICalendar => ical::import(text),
+        Format::TodoTxt => Ok(todo_txt::import(text)),
+        Format::Markdown => Ok(markdown::import(text)),
+    }
+}
+
+/// Saves imported tasks into `list` and returns how many were saved.
+///
+/// Tasks that are already in the list are updated, and taken out of the
+/// trash first if they were in it. The others are created, with a new id
+/// when theirs is taken by a task elsewhere.
+pub async fn save(
+    provider: &mut dyn TasksProvider,
+    list: &List,
+    tasks: Vec<Task>,
+) -> Result<usize> {
+    let query = TaskQuery {
+        include_deleted: true,
+        ..Default::default()
+    };
+    let stored = provider.query(&query).await?;
+    let in_list: HashSet<String> = stored
+        .iter()
+        .filter(|task| task.parent == list.id)
+        .map(|task| task.id.clone())
+        .collect();
+    // Some providers keep the trash apart and only update tasks in the list.
+    let trashed: HashSet<String> = stored
+        .iter()
+        .filter(|task| task.parent == list.id && task.deletion_date.is_some())
+        .map(|task| task.id.clone())
+        .collect();
+    let mut taken = HashSet::new();
+    for task in &stored {
+        collect_ids(task, &mut taken);
+    }
+
+    let mut saved = 0;
+    for mut task in tasks {
+        saved += 1 + count_sub_tasks(&task);
+        task.parent = list.id.clone();
+        if in_list.contains(&task.id) {
+            if trashed.contains(&task.id) {
+                provider
+                    .restore_task(list.id.clone(), task.id.clone())
+                    .await?;
+            }
+            provider.update_task(task).await?;
+        } else {
+            renew_taken_ids(&mut task, &taken);
+            provider.create_task(task).await?;
+        }
+    }
+    Ok(saved)
+}
+
+fn collect_ids(task: &Task, ids: &mut HashSet<String>) {
+    ids.insert(task.id.clone());
+    for sub_task in &task.sub_tasks {
+        collect_ids(sub_task, ids);
+    }
+}
+
+fn count_sub_tasks(task: &Task) -> usize {
+    task.sub_tasks
+        .iter()
+        .map(|sub_task| 1 + count_sub_tasks(sub_task))
+        .sum()
+}
+
+fn renew_taken_ids(task: &mut Task, taken: &HashSet<String>) {
+    if taken.contains(&task.id) {
+        task.id = Uuid::new_v4().to_string();
+    }
+    for sub_task in &mut task.sub_tasks {
+        sub_task.parent = task.id.clone();
+        renew_taken_ids(sub_task, taken);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{service::Provider, services::todo_txt::TodoTxtFiles, testing};
+
+    #[tokio::test]
+    async fn brings_back_trashed_tasks() -> Result<()> {
+        // todo.txt keeps the trash apart from the tasks in the lists.
+        let mut service = testing::service();
+        service.provider = Provider::TodoTxt;
+        let data = dirs::data_local_dir().unwrap().join(&service.app_id);
+        std::fs::create_dir_all(&data)?;
+        TodoTxtFiles::new(data.join("todo.txt")).save(&service.app_id)?;
+        let mut provider = service.get_service().unwrap();
+
+        let list = provider.create_list(List::new("Chores")).await?;
+        let task = Task::new("Water the plants".into(), list.id.clone());
+        let task = provider.create_task(task).await?;
+        provider
+            .delete_task(list.id.clone(), task.id.clone())
+            .await?;
+
+        let imported = Task {
+            title: "Water the garden".into(),
+            deletion_date: None,
+            ..task.clone()
+        };
+        assert_eq!(save(provider.as_mut(), &list, vec![imported]).await?, 1);
+        assert!(provider.get_deleted_tasks().await?.is_empty());
+        let saved = provider.get_task(list.id.clone(), task.id).await?;
+        assert_eq!(saved.title, "Water the garden");
+        Ok(())
+    }
+}
@@ -1,4 +1,19 @@
+pub mod conflict;
+pub mod dbus;
+pub mod error;
+pub mod ical;
+pub mod import;
+pub mod markdown;
+pub mod migration;
 pub mod models;
+pub mod reminders;
+pub mod search;
 pub mod service;
 pub mod services;
 pub(crate) mod task_service;
+#[cfg(test)]
+mod testing;
+pub mod todo_txt;
+pub mod watch;
+
+pub use error::Error;
@@ -0,0 +1,586 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+use crate::{
+    error::Result,
+    ical,
+    import::ImportedList,
+    models::{
+        list::List,
+        priority::Priority,
+        recurrence::{Day, Frequency, Recurrence},
+        status::Status,
+        task::Task,
+    },
+    todo_txt::{local_midnight, utc_midnight},
+};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const REMINDER_FORMAT: &str = "%Y-%m-%d %H:%M";
+const UNTIL_FORMAT: &str = "%B %-d, %Y";
+
+// The emoji the Obsidian Tasks plugin marks fields with, along with the
+// reminder of the Reminder plugin and a star for favorites.
+const HIGHEST: &str = "🔺";
+const HIGH: &str = "⏫";
+const MEDIUM: &str = "🔼";
+const LOW: &str = "🔽";
+const LOWEST: &str = "⏬";
+const RECURRENCE: &str = "🔁";
+const CREATED: &str = "➕";
+const START: &str = "🛫";
+const SCHEDULED: &str = "⏳";
+const DUE: &str = "📅";
+const DONE: &str = "✅";
+const REMINDER: &str = "⏰";
+const FAVORITE: &str = "⭐";
+
+const SIGNIFIERS: [&str; 13] = [
+    HIGHEST, HIGH, MEDIUM, LOW, LOWEST, RECURRENCE, CREATED, START, SCHEDULED, DUE, DONE, REMINDER,
+    FAVORITE,
+];
+
+/// How much of each task goes into a Markdown document.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Detail {
+    /// Checkboxes and titles only.
+    #[default]
+    Plain,
+    /// Every field, the way the Obsidian Tasks plugin writes them: dates,
+    /// priority and recurrence after emoji, tags as `#tags`, and notes
+    /// quoted under the task.
+    Full,
+}
+
+pub trait Markdown {
+    fn markdown(&self) -> String;
+}
+
+impl Markdown for List {
+    fn markdown(&self) -> String {
+        format!("# {}\n", self.name)
+    }
+}
+
+impl Markdown for Task {
+    fn markdown(&self) -> String {
+        let mut markdown = String::new();
+        write_task(&mut markdown, self, 0, Detail::Plain);
+        markdown
+    }
+}
+
+/// Writes a task and, indented under it, its sub-tasks.
+fn write_task(markdown: &mut String, task: &Task, depth: usize, detail: Detail) {
+    let indent = "  ".repeat(depth);
+    let check = if task.status == Status::Completed {
+        "x"
+    } else {
+        " "
+    };
+    markdown.push_str(&format!("{indent}- [{check}] {}", task.title));
+    if detail == Detail::Full {
+        for field in fields(task) {
+            markdown.push(' ');
+            markdown.push_str(&field);
+        }
+        markdown.push('\n');
+        for line in task.notes.lines() {
+            if line.is_empty() {
+                markdown.push_str(&format!("{indent}  >\n"));
+            } else {
+                markdown.push_str(&format!("{indent}  > {line}\n"));
+            }
+        }
+    } else {
+        markdown.push('\n');
+    }
+    for sub_task in &task.sub_tasks {
+        write_task(markdown, sub_task, depth + 1, detail);
+    }
+}
+
+/// Everything about a task but its title and notes, in the order the
+/// Obsidian Tasks plugin writes them.
+fn fields(task: &Task) -> Vec<String> {
+    let mut fields: Vec<String> = task
+        .tags
+        .iter()
+        .map(|tag| format!("#{}", tag.split_whitespace().collect::<Vec<_>>().join("-")))
+        .collect();
+    if task.favorite {
+        fields.push(FAVORITE.to_string());
+    }
+    match task.priority {
+        Priority::High => fields.push(HIGH.to_string()),
+        Priority::Normal => fields.push(MEDIUM.to_string()),
+        Priority::Low => {}
+    }
+    if let Some(recurrence) = recurrence_text(&task.recurrence) {
+        fields.push(format!("{RECURRENCE} {recurrence}"));
+    }
+    let created = task.created_date_time.with_timezone(&Local);
+    fields.push(format!("{CREATED} {}", created.format(DATE_FORMAT)));
+    // Scheduled for today is the closest the plugin has to My Day.
+    if task.today {
+        fields.push(format!("{SCHEDULED} {}", Local::now().format(DATE_FORMAT)));
+    }
+    if let Some(reminder) = task.reminder_date {
+        let reminder = reminder.with_timezone(&Local);
+        fields.push(format!("{REMINDER} {}", reminder.format(REMINDER_FORMAT)));
+    }
+    if let Some(due) = task.due_date {
+        fields.push(format!("{DUE} {}", due.date_naive().format(DATE_FORMAT)));
+    }
+    if let Some(completed) = task.completion_date {
+        if task.status == Status::Completed {
+            let completed = completed.with_timezone(&Local);
+            fields.push(format!("{DONE} {}", completed.format(DATE_FORMAT)));
+        }
+    }
+    fields
+}
+
+/// The recurrence the way the plugin writes it, such as `every 2 weeks on
+/// Monday, Friday` or `every month on the 1st when done`.
+fn recurrence_text(recurrence: &Recurrence) -> Option<String> {
+    let unit = match recurrence.frequency? {
+        Frequency::Daily => "day",
+        Frequency::Weekly => "week",
+        Frequency::Monthly => "month",
+        Frequency::Yearly => "year",
+    };
+    let mut text = match recurrence.interval {
+        0 | 1 => format!("every {unit}"),
+        interval => format!("every {interval} {unit}s"),
+    };
+    if !recurrence.by_day.is_empty() {
+        let days: Vec<&str> = recurrence.by_day.iter().map(|day| day_name(*day)).collect();
+        text.push_str(&format!(" on {}", days.join(", ")));
+    }
+    if !recurrence.by_month_day.is_empty() {
+        let days: Vec<String> = recurrence
+            .by_month_day
+            .iter()
+            .map(|day| month_day_name(*day))
+            .collect();
+        text.push_str(&format!(" on the {}", days.join(", ")));
+    }
+    if let Some(count) = recurrence.count {
+        text.push_str(&format!(" for {count} times"));
+    }
+    if let Some(until) = recurrence.until {
+        text.push_str(&format!(
+            " until {}",
+            until.date_naive().format(UNTIL_FORMAT)
+        ));
+    }
+    if recurrence.after_completion {
+        text.push_str(" when done");
+    }
+    Some(text)
+}
+
+fn parse_recurrence(text: &str) -> Option<Recurrence> {
+    let mut recurrence = Recurrence::default();
+    let mut text = text.trim();
+    if let Some(rest) = text.strip_suffix("when done") {
+        recurrence.after_completion = true;
+        text = rest.trim_end();
+    }
+    if let Some((rest, until)) = text.split_once(" until ") {
+        let until = until.trim();
+        let until = NaiveDate::parse_from_str(until, "%B %d, %Y")
+            .or_else(|_| NaiveDate::parse_from_str(until, DATE_FORMAT))
+            .ok()?;
+        recurrence.until = Some(utc_midnight(until));
+        text = rest;
+    }
+    if let Some((rest, count)) = text.split_once(" for ") {
+        let count = count.trim();
+        let count = count
+            .strip_suffix("times")
+            .or_else(|| count.strip_suffix("time"))?;
+        recurrence.count = Some(count.trim().parse().ok()?);
+        text = rest;
+    }
+    let text = text.strip_prefix("every ")?;
+    let (period, on) = match text.split_once(" on ") {
+        Some((period, on)) => (period, Some(on)),
+        None => (text, None),
+    };
+    let mut words = period.split_whitespace();
+    let mut unit = words.next()?;
+    if let Ok(interval) = unit.parse() {
+        recurrence.interval = interval;
+        unit = words.next()?;
+    }
+    recurrence.frequency = Some(match unit.trim_end_matches('s') {
+        "day" => Frequency::Daily,
+        "week" => Frequency::Weekly,
+        "month" => Frequency::Monthly,
+        "year" => Frequency::Yearly,
+        _ => return None,
+    });
+    if let Some(on) = on {
+        let names = on.replace(" and ", ", ");
+        if let Some(days) = names.strip_prefix("the ") {
+            for day in days.split(", ") {
+                recurrence.by_month_day.push(parse_month_day(day.trim())?);
+            }
+        } else {
+            for day in names.split(", ") {
+                let day = day.trim();
+                let day = Day::all()
+                    .into_iter()
+                    .find(|known| day_name(*known).eq_ignore_ascii_case(day))?;
+                recurrence.by_day.push(day);
+            }
+        }
+    }
+    Some(recurrence)
+}
+
+fn day_name(day: Day) -> &'static str {
+    match day {
+        Day::Monday => "Monday",
+        Day::Tuesday => "Tuesday",
+        Day::Wednesday => "Wednesday",
+        Day::Thursday => "Thursday",
+        Day::Friday => "Friday",
+        Day::Saturday => "Saturday",
+        Day::Sunday => "Sunday",
+    }
+}
+
+/// A day of the month such as `1st`, or `last` and `2nd last` for the
+/// negative days that count from the end.
+fn month_day_name(day: i8) -> String {
+    let ordinal = |day: u8| {
+        let suffix = match (day % 10, day % 100) {
+            (_, 11..=13) => "th",
+            (1, _) => "st",
+            (2, _) => "nd",
+            (3, _) => "rd",
+            _ => "th",
+        };
+        format!("{day}{suffix}")
+    };
+    match day {
+        -1 => "last".to_string(),
+        day if day < 0 => format!("{} last", ordinal(day.unsigned_abs())),
+        day => ordinal(day.unsigned_abs()),
+    }
+}
+
+fn parse_month_day(name: &str) -> Option<i8> {
+    if name == "last" {
+        return Some(-1);
+    }
+    let (ordinal, from_end) = match name.strip_suffix(" last") {
+        Some(ordinal) => (ordinal, true),
+        None => (name, false),
+    };
+    let day: i8 = ordinal
+        .trim_end_matches(|c: char| c.is_ascii_alphabetic())
+        .parse()
+        .ok()?;
+    Some(if from_end { -day } else { day })
+}
+
+/// A list and its tasks as a Markdown document. With [`Detail::Full`] the
+/// list's description follows its name.
+pub fn export_list(list: &List, tasks: &[Task], detail: Detail) -> String {
+    let mut markdown = list.markdown();
+    if detail == Detail::Full && !list.description.is_empty() {
+        markdown.push_str(&format!("\n{}\n", list.description));
+    }
+    markdown.push('\n');
+    for task in tasks {
+        write_task(&mut markdown, task, 0, detail);
+    }
+    markdown
+}
+
+/// Writes each list as a Markdown file of its own in `directory`, named after
+/// the list, and returns the files written. Files already in `directory`,
+/// including those of lists with the same name, are left alone and the new
+/// file gets a number instead.
+pub fn export_folder(
+    directory: &Path,
+    lists: &[(List, Vec<Task>)],
+    detail: Detail,
+) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(directory)?;
+    let mut written: Vec<PathBuf> = vec![];
+    for (list, tasks) in lists {
+        let name = file_name(&list.name);
+        let mut path = directory.join(format!("{name}.md"));
+        let mut copy = 1;
+        let mut file = loop {
+            match File::create_new(&path) {
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    copy += 1;
+                    path = directory.join(format!("{name} ({copy}).md"));
+                }
+                result => break result?,
+            }
+        };
+        file.write_all(export_list(list, tasks, detail).as_bytes())?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// A list name as a file name that works on any system.
+fn file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    let name = name.trim().trim_start_matches('.');
+    if name.is_empty() {
+        "list".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Reads a Markdown document, such as one written by [`export_list`], into a
+/// list for each heading. Checkbox items are tasks and the ones indented
+/// under them their sub-tasks, with the fields the Obsidian Tasks plugin
+/// writes after their titles. Other text under a task becomes its notes,
+/// and text under a heading before the first task the list's description.
+///
+/// Tasks before the first heading go in a list without a name.
+pub fn import(text: &str) -> Vec<ImportedList> {
+    let mut lists = vec![];
+    let mut list = ImportedList::default();
+    let mut description = String::new();
+    let mut tasks: Vec<(Task, Option<String>)> = vec![];
+    // The items the next one may be nested under, with their indentation.
+    let mut parents: Vec<(usize, String)> = vec![];
+
+    for line in text.lines() {
+        if let Some(name) = heading(line) {
+            finish(&mut lists, list, description, tasks);
+            list = ImportedList {
+                name: Some(name.to_string()),
+                ..Default::default()
+            };
+            description = String::new();
+            tasks = vec![];
+            parents.clear();
+        } else if let Some((indent, completed, title)) = checkbox(line) {
+            while parents.last().is_some_and(|(parent, _)| *parent >= indent) {
+                parents.pop();
+            }
+            let mut task = Task::new(String::new(), String::new());
+            if completed {
+                task.status = Status::Completed;
+            }
+            read_fields(&mut task, title);
+            let parent = parents.last().map(|(_, id)| id.clone());
+            parents.push((indent, task.id.clone()));
+            tasks.push((task, parent));
+        } else {
+            let text = line.trim();
+            let text = text
+                .strip_prefix('>')
+                .map_or(text, |quoted| quoted.strip_prefix(' ').unwrap_or(quoted));
+            match tasks.last_mut() {
+                Some((task, _)) => append(&mut task.notes, text),
+                None => append(&mut description, text),
+            }
+        }
+    }
+    finish(&mut lists, list, description, tasks);
+    lists
+}
+
+fn finish(
+    lists: &mut Vec<ImportedList>,
+    mut list: ImportedList,
+    description: String,
+    mut tasks: Vec<(Task, Option<String>)>,
+) {
+    if list.name.is_none() && tasks.is_empty() {
+        return;
+    }
+    for (task, _) in &mut tasks {
+        task.notes = task.notes.trim_end().to_string();
+    }
+    let description = description.trim_end();
+    list.description = (!description.is_empty()).then(|| description.to_string());
+    list.tasks = ical::nest(tasks);
+    lists.push(list);
+}
+
+/// Reads the title of a task along with the fields after it. Tags are taken
+/// out of the title, and fields this app has no room for are left out.
+fn read_fields(task: &mut Task, text: &str) {
+    let next = |text: &str| {
+        SIGNIFIERS
+            .iter()
+            .filter_map(|signifier| text.find(signifier))
+            .min()
+            .unwrap_or(text.len())
+    };
+    let end = next(text);
+    let mut words: Vec<&str> = text[..end].split_whitespace().collect();
+    let mut rest = &text[end..];
+    while let Some(signifier) = SIGNIFIERS
+        .iter()
+        .find(|signifier| rest.starts_with(**signifier))
+    {
+        rest = rest[signifier.len()..].trim_start_matches('\u{fe0f}');
+        let end = next(rest);
+        let mut value = rest[..end].split_whitespace();
+        rest = &rest[end..];
+        let date = |word: Option<&str>| NaiveDate::parse_from_str(word?, DATE_FORMAT).ok();
+        match *signifier {
+            HIGHEST | HIGH => task.priority = Priority::High,
+            MEDIUM => task.priority = Priority::Normal,
+            LOW | LOWEST => task.priority = Priority::Low,
+            FAVORITE => task.favorite = true,
+            SCHEDULED => {
+                task.today = true;
+                value.next();
+            }
+            RECURRENCE => {
+                if let Some(recurrence) =
+                    parse_recurrence(&value.by_ref().collect::<Vec<_>>().join(" "))
+                {
+                    task.recurrence = recurrence;
+                }
+            }
+            CREATED => {
+                if let Some(created) = date(value.next()) {
+                    task.created_date_time = local_midnight(created);
+                }
+            }
+            DUE => task.due_date = date(value.next()).map(utc_midnight),
+            DONE => {
+                let completed = date(value.next()).map(local_midnight);
+                if task.status == Status::Completed {
+                    task.completion_date = completed;
+                }
+            }
+            REMINDER => {
+                let reminder = format!(
+                    "{} {}",
+                    value.next().unwrap_or_default(),
+                    value.next().unwrap_or_default()
+                );
+                task.reminder_date = NaiveDateTime::parse_from_str(&reminder, REMINDER_FORMAT)
+                    .ok()
+                    .and_then(|reminder| Local.from_local_datetime(&reminder).earliest())
+                    .map(|reminder| reminder.with_timezone(&Utc));
+            }
+            _ => {
+                value.next();
+            }
+        }
+        // Whatever follows the value, such as tags, goes with the title.
+        words.extend(value);
+    }
+
+    let is_tag = |word: &str| {
+        word.strip_prefix('#').is_some_and(|tag| {
+            tag.chars()
+                .next()
+                .is_some_and(|c| !c.is_ascii_digit() && c != '#')
+        })
+    };
+    task.tags = words
+        .iter()
+        .filter(|word| is_tag(word))
+        .map(|word| word[1..].to_string())
+        .collect();
+    words.retain(|word| !is_tag(word));
+    task.title = words.join(" ");
+}
+
+/// Adds a line of text, leaving out blank lines at the start.
+fn append(text: &mut String, line: &str) {
+    if text.is_empty() {
+        if !line.is_empty() {
+            text.push_str(line);
+        }
+    } else {
+        text.push('\n');
+        text.push_str(line);
+    }
+}
+
+/// The title of an ATX heading, such as `# Groceries`.
+fn heading(line: &str) -> Option<&str> {
+    let content = line.trim_start();
+    let level = content.chars().take_while(|c| *c == '#').count();
+    if !(1..=6).contains(&level) || line.len() - content.len() > 3 {
+        return None;
+    }
+    let title = &content[level..];
+    if !title.is_empty() && !title.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(title.trim().trim_end_matches('#').trim_end())
+}
+
+/// The indentation, whether it is checked and the title of a checkbox item,
+/// such as `  - [x] Milk`.
+fn checkbox(line: &str) -> Option<(usize, bool, &str)> {
+    let content = line.trim_start();
+    let indent = line[..line.len() - content.len()]
+        .chars()
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum();
+    let item = content
+        .strip_prefix(['-', '*', '+'])?
+        .strip_prefix(char::is_whitespace)?
+        .trim_start();
+    let completed = match item.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    Some((indent, completed, item[3..].trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_existing_files() -> Result<()> {
+        let directory = std::env::temp_dir().join(format!(
+            "tasks-core-markdown-{}",
+            uuid::Uuid::new_v4().simple()
+        ));
+        std::fs::create_dir_all(&directory)?;
+        std::fs::write(directory.join("Work.md"), "mine")?;
+        let list = List::new("Work");
+        let lists = vec![(list.clone(), vec![]), (list, vec![])];
+
+        let written = export_folder(&directory, &lists, Detail::Full)?;
+        assert_eq!(
+            written,
+            vec![directory.join("Work (2).md"), directory.join("Work (3).md")]
+        );
+        assert_eq!(std::fs::read_to_string(directory.join("Work.md"))?, "mine");
+
+        std::fs::remove_dir_all(directory)?;
+        Ok(())
+    }
+}
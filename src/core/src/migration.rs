@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use crate::{
+    error::Result,
+    models::{list::List, task::Task},
+    task_service::TasksProvider,
+};
+
+/// What a copy between two providers changed, or would change when run as a dry run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MigrationSummary {
+    pub lists_created: usize,
+    pub lists_updated: usize,
+    pub tasks_created: usize,
+    pub tasks_updated: usize,
+    pub unchanged: usize,
+}
+
+impl MigrationSummary {
+    pub fn is_empty(&self) -> bool {
+        self.lists_created + self.lists_updated + self.tasks_created + self.tasks_updated == 0
+    }
+}
+
+/// Copies every list and task from `source` into `target`.
+///
+/// Records keep their ids, timestamps, sub-tasks and order, and tasks in the
+/// trash stay in the trash. Records that already exist in `target` are
+/// overwritten when they differ, and records that only exist in `target` are
+/// left untouched. With `dry_run` nothing is written and the summary
+/// describes what would have been done.
+pub async fn copy(
+    source: &mut dyn TasksProvider,
+    target: &mut dyn TasksProvider,
+    dry_run: bool,
+) -> Result<MigrationSummary> {
+    let mut summary = MigrationSummary::default();
+
+    let existing_lists: HashMap<String, List> = target
+        .get_lists()
+        .await?
+        .into_iter()
+        .map(|list| (list.id.clone(), list))
+        .collect();
+    let mut existing_tasks: HashMap<String, Task> = HashMap::new();
+    for list_id in existing_lists.keys() {
+        for task in target.get_tasks_from_list(list_id.clone()).await? {
+            existing_tasks.insert(task.id.clone(), task);
+        }
+    }
+    for task in target.get_deleted_tasks().await? {
+        existing_tasks.insert(task.id.clone(), task);
+    }
+
+    for list in source.get_lists().await? {
+        match existing_lists.get(&list.id) {
+            Some(existing) if *existing == list => summary.unchanged += 1,
+            Some(_) => {
+                summary.lists_updated += 1;
+                if !dry_run {
+                    target.update_list(list.clone()).await?;
+                }
+            }
+            None => {
+                summary.lists_created += 1;
+                if !dry_run {
+                    target.create_list(list.clone()).await?;
+                }
+            }
+        }
+
+        for task in source.get_tasks_from_list(list.id).await? {
+            copy_task(target, &existing_tasks, task, dry_run, &mut summary).await?;
+        }
+    }
+
+    for task in source.get_deleted_tasks().await? {
+        copy_task(target, &existing_tasks, task, dry_run, &mut summary).await?;
+    }
+
+    Ok(summary)
+}
+
+async fn copy_task(
+    target: &mut dyn TasksProvider,
+    existing_tasks: &HashMap<String, Task>,
+    task: Task,
+    dry_run: bool,
+    summary: &mut MigrationSummary,
+) -> Result<()> {
+    match existing_tasks.get(&task.id) {
+        Some(existing) if *existing == task => summary.unchanged += 1,
+        Some(existing) => {
+            summary.tasks_updated += 1;
+            if !dry_run {
+                replace_task(target, existing, task).await?;
+            }
+        }
+        None => {
+            summary.tasks_created += 1;
+            if !dry_run {
+                target.create_task(task).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes `task` over the copy `existing` in `target`. Tasks that stay in
+/// their list are updated in place, which keeps their position. The others
+/// are created anew, and the stored copy is put back if that fails: tasks
+/// that moved to another list or in or out of the trash, and tasks older than
+/// the stored copy, whose timestamp an update would stamp anew.
+async fn replace_task(target: &mut dyn TasksProvider, existing: &Task, task: Task) -> Result<()> {
+    let in_place = task.parent == existing.parent
+        && task.deletion_date.is_none()
+        && existing.deletion_date.is_none()
+        && task.last_modified_date_time > existing.last_modified_date_time;
+    if in_place {
+        return target.update_task(task).await;
+    }
+    target
+        .purge_task(existing.parent.clone(), existing.id.clone())
+        .await?;
+    if let Err(err) = target.create_task(task).await {
+        if let Err(err) = target.create_task(existing.clone()).await {
+            tracing::error!("failed to put back task {}: {err}", existing.id);
+        }
+        return Err(err);
+    }
+    Ok(())
+}
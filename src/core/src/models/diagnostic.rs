@@ -0,0 +1,11 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// A problem found by a service while reading its data, such as a
+/// damaged file that had to be set aside.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub message: String,
+}
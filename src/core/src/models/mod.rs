@@ -7,3 +7,9 @@ pub mod priority;
 pub mod status;
 
 pub mod recurrence;
+
+pub mod diagnostic;
+
+pub mod smart_list;
+
+pub mod query;
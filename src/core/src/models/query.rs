@@ -0,0 +1,146 @@
+use std::cmp::Ordering;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{priority::Priority, status::Status, task::Task};
+
+/// Selects tasks across every list. Unset fields match everything, set fields
+/// must all match.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskQuery {
+    /// Only tasks in this list.
+    pub list: Option<String>,
+    pub status: Option<Status>,
+    pub min_priority: Option<Priority>,
+    pub max_priority: Option<Priority>,
+    /// Only tasks due before this date, tasks without a due date never match.
+    pub due_before: Option<DateTime<Utc>>,
+    /// Only tasks due after this date, tasks without a due date never match.
+    pub due_after: Option<DateTime<Utc>>,
+    /// Only tasks carrying all of these tags.
+    pub tags: Vec<String>,
+    pub favorite: Option<bool>,
+    pub today: Option<bool>,
+    /// Case-insensitive match on the title or the notes.
+    pub text: Option<String>,
+    /// Also return the tasks in the trash.
+    pub include_deleted: bool,
+    pub sort: TaskSort,
+    pub descending: bool,
+    /// Return at most this many tasks.
+    pub limit: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TaskSort {
+    /// The order the provider stores the tasks in.
+    #[default]
+    None,
+    Title,
+    DueDate,
+    Priority,
+    CreatedDate,
+    ModifiedDate,
+    /// Tasks that are not completed go last.
+    CompletedDate,
+}
+
+impl TaskQuery {
+    pub fn matches(&self, task: &Task) -> bool {
+        if !self.include_deleted && task.deletion_date.is_some() {
+            return false;
+        }
+        if self.list.as_ref().is_some_and(|list| *list != task.parent) {
+            return false;
+        }
+        if self.status.is_some_and(|status| status != task.status) {
+            return false;
+        }
+        if self.min_priority.is_some_and(|min| task.priority < min)
+            || self.max_priority.is_some_and(|max| task.priority > max)
+        {
+            return false;
+        }
+        if let Some(before) = self.due_before {
+            if task.due_date.is_none_or(|due| due >= before) {
+                return false;
+            }
+        }
+        if let Some(after) = self.due_after {
+            if task.due_date.is_none_or(|due| due <= after) {
+                return false;
+            }
+        }
+        if !self.tags.iter().all(|tag| task.tags.contains(tag)) {
+            return false;
+        }
+        if self
+            .favorite
+            .is_some_and(|favorite| favorite != task.favorite)
+            || self.today.is_some_and(|today| today != task.today)
+        {
+            return false;
+        }
+        if let Some(text) = &self.text {
+            let text = text.to_lowercase();
+            if !task.title.to_lowercase().contains(&text)
+                && !task.notes.to_lowercase().contains(&text)
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Whether enough matches have been found, so a provider can stop reading
+    /// early. Only possible when the results keep the order they were read in.
+    pub fn is_full(&self, found: usize) -> bool {
+        self.sort == TaskSort::None
+            && !self.descending
+            && self.limit.is_some_and(|limit| found >= limit)
+    }
+
+    /// Sorts and truncates the tasks that matched.
+    pub fn finish(&self, mut tasks: Vec<Task>) -> Vec<Task> {
+        if self.sort != TaskSort::None {
+            tasks.sort_by(|a, b| {
+                let ordering = self.compare(a, b);
+                if self.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+        } else if self.descending {
+            tasks.reverse();
+        }
+        if let Some(limit) = self.limit {
+            tasks.truncate(limit);
+        }
+        tasks
+    }
+
+    fn compare(&self, a: &Task, b: &Task) -> Ordering {
+        match self.sort {
+            TaskSort::None => Ordering::Equal,
+            TaskSort::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            // Tasks without a due date go last.
+            TaskSort::DueDate => match (a.due_date, b.due_date) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+            TaskSort::Priority => a.priority.cmp(&b.priority),
+            TaskSort::CreatedDate => a.created_date_time.cmp(&b.created_date_time),
+            TaskSort::ModifiedDate => a.last_modified_date_time.cmp(&b.last_modified_date_time),
+            TaskSort::CompletedDate => match (a.completion_date, b.completion_date) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+        }
+    }
+}
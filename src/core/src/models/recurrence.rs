@@ -1,5 +1,11 @@
+use chrono::{
+	DateTime, Datelike, Days, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone,
+	Timelike, Utc, Weekday,
+};
 use serde::{Deserialize, Serialize};
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
+
+use crate::error::Error;
 
 #[derive(
 	Clone,
@@ -23,6 +29,53 @@ pub enum Day {
 	Sunday,
 }
 
+impl Day {
+	pub fn all() -> [Day; 7] {
+		[
+			Day::Monday,
+			Day::Tuesday,
+			Day::Wednesday,
+			Day::Thursday,
+			Day::Friday,
+			Day::Saturday,
+			Day::Sunday,
+		]
+	}
+
+	/// The two letter code RRULE uses for the day.
+	pub fn as_str_name(&self) -> &'static str {
+		match self {
+			Day::Monday => "MO",
+			Day::Tuesday => "TU",
+			Day::Wednesday => "WE",
+			Day::Thursday => "TH",
+			Day::Friday => "FR",
+			Day::Saturday => "SA",
+			Day::Sunday => "SU",
+		}
+	}
+
+	pub fn from_str_name(value: &str) -> Option<Self> {
+		Day::all()
+			.into_iter()
+			.find(|day| day.as_str_name() == value)
+	}
+}
+
+impl From<Weekday> for Day {
+	fn from(value: Weekday) -> Self {
+		match value {
+			Weekday::Mon => Day::Monday,
+			Weekday::Tue => Day::Tuesday,
+			Weekday::Wed => Day::Wednesday,
+			Weekday::Thu => Day::Thursday,
+			Weekday::Fri => Day::Friday,
+			Weekday::Sat => Day::Saturday,
+			Weekday::Sun => Day::Sunday,
+		}
+	}
+}
+
 impl Display for Day {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		let str: String = match self {
@@ -42,7 +95,6 @@ impl Display for Day {
 	Clone,
 	Copy,
 	Debug,
-	Default,
 	PartialEq,
 	Eq,
 	Hash,
@@ -51,54 +103,662 @@ impl Display for Day {
 	Serialize,
 	Deserialize,
 )]
+pub enum Frequency {
+	Daily,
+	Weekly,
+	Monthly,
+	Yearly,
+}
+
+impl Frequency {
+	pub fn as_str_name(&self) -> &'static str {
+		match self {
+			Frequency::Daily => "DAILY",
+			Frequency::Weekly => "WEEKLY",
+			Frequency::Monthly => "MONTHLY",
+			Frequency::Yearly => "YEARLY",
+		}
+	}
+
+	pub fn from_str_name(value: &str) -> Option<Self> {
+		match value {
+			"DAILY" => Some(Self::Daily),
+			"WEEKLY" => Some(Self::Weekly),
+			"MONTHLY" => Some(Self::Monthly),
+			"YEARLY" => Some(Self::Yearly),
+			_ => None,
+		}
+	}
+}
+
+/// When a task repeats, following the RRULE semantics of RFC 5545.
+///
+/// A rule without a frequency means the task does not repeat. The first
+/// occurrence is the task's due date, later ones follow `frequency` every
+/// `interval` periods, restricted to `by_day` and `by_month_day` when set, up
+/// to `until` or `count` occurrences. With `after_completion` the rule is
+/// not tied to a calendar: the next occurrence is `interval` periods after
+/// the task was completed.
+///
+/// `by_day` only holds plain weekdays, ordinals such as `2MO` are not
+/// supported. Negative `by_month_day` values count from the end of the month.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(from = "RecurrenceRepr")]
 pub struct Recurrence {
-	pub monday: bool,
-	pub tuesday: bool,
-	pub wednesday: bool,
-	pub thursday: bool,
-	pub friday: bool,
-	pub saturday: bool,
-	pub sunday: bool,
+	pub frequency: Option<Frequency>,
+	pub interval: u32,
+	pub by_day: Vec<Day>,
+	pub by_month_day: Vec<i8>,
+	pub until: Option<DateTime<Utc>>,
+	pub count: Option<u32>,
+	pub after_completion: bool,
+}
+
+impl Default for Recurrence {
+	fn default() -> Self {
+		Self {
+			frequency: None,
+			interval: 1,
+			by_day: vec![],
+			by_month_day: vec![],
+			until: None,
+			count: None,
+			after_completion: false,
+		}
+	}
 }
 
+/// How many periods in a row may produce no occurrence before a rule is
+/// considered exhausted, for rules such as the 31st of every other February.
+const MAX_EMPTY_PERIODS: u32 = 1000;
+
 impl Recurrence {
-	pub fn from_string(value: String) -> Self {
+	pub fn new(frequency: Frequency) -> Self {
 		Self {
-			monday: value.contains(Day::Monday.to_string().as_str()),
-			tuesday: value.contains(Day::Tuesday.to_string().as_str()),
-			wednesday: value.contains(Day::Wednesday.to_string().as_str()),
-			thursday: value.contains(Day::Thursday.to_string().as_str()),
-			friday: value.contains(Day::Friday.to_string().as_str()),
-			saturday: value.contains(Day::Saturday.to_string().as_str()),
-			sunday: value.contains(Day::Sunday.to_string().as_str()),
+			frequency: Some(frequency),
+			..Default::default()
+		}
+	}
+
+	pub fn weekly(days: &[Day]) -> Self {
+		Self {
+			frequency: Some(Frequency::Weekly),
+			by_day: days.to_vec(),
+			..Default::default()
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.frequency.is_none()
+	}
+
+	pub fn contains(&self, day: Day) -> bool {
+		self.by_day.contains(&day)
+	}
+
+	/// Adds or removes a weekday. Picking a day on a task that does not repeat
+	/// makes it repeat weekly, and removing the last day of a plain weekly
+	/// rule makes it stop repeating.
+	pub fn set(&mut self, day: Day, value: bool) {
+		if value {
+			if self.frequency.is_none() {
+				*self = Self::weekly(&[]);
+			}
+			if !self.by_day.contains(&day) {
+				self.by_day.push(day);
+				self.by_day.sort();
+			}
+		} else {
+			self.by_day.retain(|d| *d != day);
+			if self.by_day.is_empty() && *self == Self::weekly(&[]) {
+				*self = Self::default();
+			}
+		}
+	}
+
+	/// Every occurrence of the rule for a series starting at `start`,
+	/// beginning with `start` itself.
+	///
+	/// A start at midnight UTC is a date without a time of day, the way due
+	/// dates are stored, and its occurrences are counted on UTC dates. Other
+	/// starts follow the local calendar.
+	pub fn occurrences(&self, start: DateTime<Utc>) -> Occurrences {
+		let all_day = is_all_day(start);
+		Occurrences {
+			rule: self.clone(),
+			start: to_naive(start, all_day),
+			all_day,
+			period: 0,
+			pending: vec![],
+			emitted: 0,
+			empty_periods: 0,
+			first: true,
+		}
+	}
+
+	/// The occurrence that follows a task that was due at `due` and
+	/// completed at `completed`, and how many occurrences it is after `due`.
+	///
+	/// Calendar rules skip the occurrences that are already in the past, so
+	/// a task completed late comes back on the next date that is still ahead.
+	pub fn next(
+		&self,
+		due: DateTime<Utc>,
+		completed: DateTime<Utc>,
+	) -> Option<(DateTime<Utc>, u32)> {
+		let frequency = self.frequency?;
+		let all_day = is_all_day(due);
+		if self.after_completion {
+			let mut from = completed.with_timezone(&Local).naive_local();
+			if all_day {
+				from = from.date().and_time(NaiveTime::MIN);
+			}
+			let next = advance(from, frequency, self.interval.max(1))?;
+			let next = from_naive(next, all_day)?;
+			return self
+				.until
+				.is_none_or(|until| next <= until)
+				.then_some((next, 1));
+		}
+
+		let today = Local::now().date_naive();
+		let after = to_naive(due, all_day).date().max(today);
+		self.occurrences(due)
+			.zip(0..)
+			.skip(1)
+			.find(|(occurrence, _)| to_naive(*occurrence, all_day).date() > after)
+	}
+
+	/// Reads a rule stored as RRULE text, or as the weekday names of the first
+	/// version of the app such as `Mon, Wed`.
+	pub fn from_string(value: String) -> Self {
+		if let Ok(rule) = value.parse() {
+			return rule;
+		}
+		let days: Vec<Day> = Day::all()
+			.into_iter()
+			.filter(|day| value.contains(day.to_string().as_str()))
+			.collect();
+		if days.is_empty() {
+			Self::default()
+		} else {
+			Self::weekly(&days)
+		}
+	}
+
+	/// The part of an RRULE after `RRULE:`, for example
+	/// `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH;COUNT=10`, for a rule that starts
+	/// on a date without a time of day when `all_day` is set. UNTIL is then
+	/// a date as well, as RFC 5545 wants it of the same kind as the start.
+	///
+	/// Whether the rule repeats after completion has no place in an RRULE
+	/// and is left out.
+	pub fn to_rrule(&self, all_day: bool) -> String {
+		let Some(frequency) = self.frequency else {
+			return String::new();
+		};
+		let mut parts = vec![format!("FREQ={}", frequency.as_str_name())];
+		if self.interval > 1 {
+			parts.push(format!("INTERVAL={}", self.interval));
+		}
+		if !self.by_day.is_empty() {
+			let days: Vec<&str> = self.by_day.iter().map(Day::as_str_name).collect();
+			parts.push(format!("BYDAY={}", days.join(",")));
+		}
+		if !self.by_month_day.is_empty() {
+			let days: Vec<String> = self.by_month_day.iter().map(i8::to_string).collect();
+			parts.push(format!("BYMONTHDAY={}", days.join(",")));
+		}
+		if let Some(until) = self.until {
+			let format = if all_day { "%Y%m%d" } else { "%Y%m%dT%H%M%SZ" };
+			parts.push(format!("UNTIL={}", until.format(format)));
+		}
+		if let Some(count) = self.count {
+			parts.push(format!("COUNT={}", count));
+		}
+		parts.join(";")
+	}
+}
+
+/// Iterator over the occurrences of a [`Recurrence`], see [`Recurrence::occurrences`].
+pub struct Occurrences {
+	rule: Recurrence,
+	start: NaiveDateTime,
+	all_day: bool,
+	period: u32,
+	pending: Vec<NaiveDateTime>,
+	emitted: u32,
+	empty_periods: u32,
+	first: bool,
+}
+
+impl Iterator for Occurrences {
+	type Item = DateTime<Utc>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let frequency = self.rule.frequency?;
+		if self.rule.count.is_some_and(|count| self.emitted >= count) {
+			return None;
+		}
+
+		let next = if self.first {
+			self.first = false;
+			self.start
+		} else if self.rule.after_completion {
+			let periods = self.rule.interval.max(1).checked_mul(self.emitted)?;
+			advance(self.start, frequency, periods)?
+		} else {
+			loop {
+				if let Some(next) = self.pending.pop() {
+					break next;
+				}
+				if self.empty_periods > MAX_EMPTY_PERIODS {
+					return None;
+				}
+				self.pending = self.period_dates(frequency)?;
+				// Pop from the back, earliest last.
+				self.pending.sort_by(|a, b| b.cmp(a));
+				self.empty_periods = if self.pending.is_empty() {
+					self.empty_periods + 1
+				} else {
+					0
+				};
+				self.period += self.rule.interval.max(1);
+			}
+		};
+
+		let next = from_naive(next, self.all_day)?;
+		if self.rule.until.is_some_and(|until| next > until) {
+			return None;
 		}
+		self.emitted += 1;
+		Some(next)
 	}
 }
 
+impl Occurrences {
+	/// The dates of the current period that come after the start.
+	fn period_dates(&self, frequency: Frequency) -> Option<Vec<NaiveDateTime>> {
+		let start = self.start.date();
+		let time = self.start.time();
+		let dates: Vec<NaiveDate> = match frequency {
+			Frequency::Daily => {
+				let date = start.checked_add_days(Days::new(self.period.into()))?;
+				vec![date]
+					.into_iter()
+					.filter(|date| self.matches_filters(*date))
+					.collect()
+			}
+			Frequency::Weekly => {
+				let monday = start
+					.checked_sub_days(Days::new(start.weekday().num_days_from_monday().into()))?
+					.checked_add_days(Days::new(u64::from(self.period) * 7))?;
+				let days = if self.rule.by_day.is_empty() {
+					vec![Day::from(start.weekday())]
+				} else {
+					self.rule.by_day.clone()
+				};
+				(0..7)
+					.filter_map(|offset| monday.checked_add_days(Days::new(offset)))
+					.filter(|date| days.contains(&date.weekday().into()))
+					.filter(|date| self.matches_month_day(*date))
+					.collect()
+			}
+			Frequency::Monthly => {
+				let first = start
+					.with_day(1)?
+					.checked_add_months(Months::new(self.period))?;
+				self.month_dates(first, start.day())
+			}
+			Frequency::Yearly => {
+				let first = start
+					.with_day(1)?
+					.checked_add_months(Months::new(self.period.checked_mul(12)?))?;
+				self.month_dates(first, start.day())
+			}
+		};
+		Some(
+			dates
+				.into_iter()
+				.filter(|date| *date > start)
+				.map(|date| date.and_time(time))
+				.collect(),
+		)
+	}
+
+	/// The dates of the month starting at `first` that match the rule, the
+	/// start's day of the month when neither days nor weekdays are given.
+	fn month_dates(&self, first: NaiveDate, start_day: u32) -> Vec<NaiveDate> {
+		let days = days_in_month(first);
+		let dates = (0..days).filter_map(|offset| first.checked_add_days(Days::new(offset.into())));
+		if self.rule.by_month_day.is_empty() && self.rule.by_day.is_empty() {
+			dates.filter(|date| date.day() == start_day).collect()
+		} else {
+			dates.filter(|date| self.matches_filters(*date)).collect()
+		}
+	}
+
+	fn matches_filters(&self, date: NaiveDate) -> bool {
+		(self.rule.by_day.is_empty() || self.rule.by_day.contains(&date.weekday().into()))
+			&& self.matches_month_day(date)
+	}
+
+	fn matches_month_day(&self, date: NaiveDate) -> bool {
+		if self.rule.by_month_day.is_empty() {
+			return true;
+		}
+		let days = days_in_month(date) as i64;
+		let day = date.day() as i64;
+		self.rule.by_month_day.iter().any(|month_day| {
+			let month_day = i64::from(*month_day);
+			month_day == day || (month_day < 0 && days + month_day + 1 == day)
+		})
+	}
+}
+
+fn days_in_month(date: NaiveDate) -> u32 {
+	let Some(first) = date.with_day(1) else {
+		return 0;
+	};
+	first
+		.checked_add_months(Months::new(1))
+		.map_or(31, |next| (next - first).num_days() as u32)
+}
+
+fn advance(from: NaiveDateTime, frequency: Frequency, periods: u32) -> Option<NaiveDateTime> {
+	match frequency {
+		Frequency::Daily => from.checked_add_days(Days::new(periods.into())),
+		Frequency::Weekly => from.checked_add_days(Days::new(u64::from(periods) * 7)),
+		Frequency::Monthly => from.checked_add_months(Months::new(periods)),
+		Frequency::Yearly => from.checked_add_months(Months::new(periods.checked_mul(12)?)),
+	}
+}
+
+/// Whether `date` has no time of day: dates are stored as midnight UTC.
+fn is_all_day(date: DateTime<Utc>) -> bool {
+	date.num_seconds_from_midnight() == 0
+}
+
+/// The calendar date and time of `date`, in UTC for all-day dates and in
+/// the local time zone otherwise.
+fn to_naive(date: DateTime<Utc>, all_day: bool) -> NaiveDateTime {
+	if all_day {
+		date.naive_utc()
+	} else {
+		date.with_timezone(&Local).naive_local()
+	}
+}
+
+/// The inverse of [`to_naive`].
+fn from_naive(date: NaiveDateTime, all_day: bool) -> Option<DateTime<Utc>> {
+	if all_day {
+		Some(date.and_utc())
+	} else {
+		to_utc(date)
+	}
+}
+
+fn to_utc(date: NaiveDateTime) -> Option<DateTime<Utc>> {
+	Local
+		.from_local_datetime(&date)
+		.earliest()
+		.map(|date| date.with_timezone(&Utc))
+}
+
+/// The rule as an RRULE with UNTIL as a date and time, see
+/// [`Recurrence::to_rrule`].
 impl Display for Recurrence {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		let mut rec = vec![];
-		if self.monday {
-			rec.push(Day::Monday.to_string())
-		}
-		if self.tuesday {
-			rec.push(Day::Tuesday.to_string())
+		f.write_str(&self.to_rrule(false))
+	}
+}
+
+/// Reads an RRULE, with or without the `RRULE:` prefix. Rules written by
+/// earlier versions may say they repeat after completion with an
+/// `X-AFTER-COMPLETION=TRUE` part.
+impl FromStr for Recurrence {
+	type Err = Error;
+
+	fn from_str(value: &str) -> Result<Self, Self::Err> {
+		let value = value.trim();
+		let value = value.strip_prefix("RRULE:").unwrap_or(value);
+		let mut rule = Recurrence::default();
+		for part in value.split(';').filter(|part| !part.is_empty()) {
+			let (name, value) = part
+				.split_once('=')
+				.ok_or_else(|| invalid(format!("Invalid rule part: {part}")))?;
+			match name.to_ascii_uppercase().as_str() {
+				"FREQ" => {
+					rule.frequency = Some(
+						Frequency::from_str_name(&value.to_ascii_uppercase())
+							.ok_or_else(|| invalid(format!("Unsupported frequency: {value}")))?,
+					)
+				}
+				"INTERVAL" => rule.interval = number(part, value)?,
+				"BYDAY" => {
+					rule.by_day = value
+						.split(',')
+						.map(|day| {
+							Day::from_str_name(&day.to_ascii_uppercase())
+								.ok_or_else(|| invalid(format!("Unsupported day: {day}")))
+						})
+						.collect::<Result<_, _>>()?
+				}
+				"BYMONTHDAY" => {
+					rule.by_month_day = value
+						.split(',')
+						.map(|day| number(part, day))
+						.collect::<Result<_, _>>()?
+				}
+				"UNTIL" => rule.until = Some(parse_until(value)?),
+				"COUNT" => rule.count = Some(number(part, value)?),
+				"X-AFTER-COMPLETION" => rule.after_completion = value.eq_ignore_ascii_case("TRUE"),
+				// Week start and other parts don't change the supported rules.
+				_ => {}
+			}
 		}
-		if self.wednesday {
-			rec.push(Day::Wednesday.to_string())
+		if rule.frequency.is_none() {
+			return Err(invalid(format!("Rule has no frequency: {value}")));
 		}
-		if self.thursday {
-			rec.push(Day::Thursday.to_string())
+		if rule.interval == 0 {
+			return Err(invalid(format!(
+				"Rule interval must be at least 1: {value}"
+			)));
 		}
-		if self.friday {
-			rec.push(Day::Friday.to_string())
+		Ok(rule)
+	}
+}
+
+fn invalid(message: String) -> Error {
+	Error::Corrupt(message)
+}
+
+fn number<T: FromStr>(part: &str, value: &str) -> Result<T, Error> {
+	value
+		.parse()
+		.map_err(|_| invalid(format!("Invalid rule part: {part}")))
+}
+
+fn parse_until(value: &str) -> Result<DateTime<Utc>, Error> {
+	let invalid_date = || invalid(format!("Invalid date: {value}"));
+	if let Ok(date) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ") {
+		return Ok(date.and_utc());
+	}
+	if let Ok(date) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+		return to_utc(date).ok_or_else(invalid_date);
+	}
+	// A date only goes with rules that start on a date, whose occurrences
+	// are UTC dates, so it ends with that day in UTC.
+	let date = NaiveDate::parse_from_str(value, "%Y%m%d").map_err(|_| invalid_date())?;
+	Ok(date.and_hms_opt(23, 59, 59).unwrap_or_default().and_utc())
+}
+
+/// The stored form of a rule. Tasks saved before rules were supported only
+/// have the weekday flags, which become a weekly rule on those days.
+#[derive(Deserialize)]
+#[serde(default)]
+struct RecurrenceRepr {
+	frequency: Option<Frequency>,
+	interval: u32,
+	by_day: Vec<Day>,
+	by_month_day: Vec<i8>,
+	until: Option<DateTime<Utc>>,
+	count: Option<u32>,
+	after_completion: bool,
+	monday: bool,
+	tuesday: bool,
+	wednesday: bool,
+	thursday: bool,
+	friday: bool,
+	saturday: bool,
+	sunday: bool,
+}
+
+impl Default for RecurrenceRepr {
+	fn default() -> Self {
+		Self {
+			frequency: None,
+			interval: 1,
+			by_day: vec![],
+			by_month_day: vec![],
+			until: None,
+			count: None,
+			after_completion: false,
+			monday: false,
+			tuesday: false,
+			wednesday: false,
+			thursday: false,
+			friday: false,
+			saturday: false,
+			sunday: false,
 		}
-		if self.saturday {
-			rec.push(Day::Saturday.to_string())
+	}
+}
+
+impl From<RecurrenceRepr> for Recurrence {
+	fn from(value: RecurrenceRepr) -> Self {
+		let weekdays = [
+			value.monday,
+			value.tuesday,
+			value.wednesday,
+			value.thursday,
+			value.friday,
+			value.saturday,
+			value.sunday,
+		];
+		if value.frequency.is_none() && weekdays.contains(&true) {
+			let days: Vec<Day> = Day::all()
+				.into_iter()
+				.zip(weekdays)
+				.filter_map(|(day, repeats)| repeats.then_some(day))
+				.collect();
+			return Recurrence::weekly(&days);
 		}
-		if self.sunday {
-			rec.push(Day::Sunday.to_string())
+		Recurrence {
+			frequency: value.frequency,
+			interval: value.interval.max(1),
+			by_day: value.by_day,
+			by_month_day: value.by_month_day,
+			until: value.until,
+			count: value.count,
+			after_completion: value.after_completion,
 		}
-		write!(f, "{}", rec.join(", "))
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A due date the way the app stores it, midnight UTC of the day.
+	fn date(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+		Utc.with_ymd_and_hms(year, month, day, 0, 0, 0).unwrap()
+	}
+
+	fn occurrences(rule: &str, start: DateTime<Utc>, take: usize) -> Vec<DateTime<Utc>> {
+		let rule: Recurrence = rule.parse().unwrap();
+		rule.occurrences(start).take(take).collect()
+	}
+
+	#[test]
+	fn interval() {
+		assert_eq!(
+			occurrences("FREQ=DAILY;INTERVAL=3", date(2024, 1, 1), 3),
+			[date(2024, 1, 1), date(2024, 1, 4), date(2024, 1, 7)]
+		);
+		assert_eq!(
+			occurrences("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE", date(2024, 1, 1), 4),
+			[
+				date(2024, 1, 1),
+				date(2024, 1, 3),
+				date(2024, 1, 15),
+				date(2024, 1, 17)
+			]
+		);
+	}
+
+	#[test]
+	fn last_day_of_month() {
+		assert_eq!(
+			occurrences("FREQ=MONTHLY;BYMONTHDAY=-1", date(2024, 1, 31), 4),
+			[
+				date(2024, 1, 31),
+				date(2024, 2, 29),
+				date(2024, 3, 31),
+				date(2024, 4, 30)
+			]
+		);
+	}
+
+	#[test]
+	fn count() {
+		assert_eq!(
+			occurrences("FREQ=WEEKLY;COUNT=3", date(2024, 1, 1), 10),
+			[date(2024, 1, 1), date(2024, 1, 8), date(2024, 1, 15)]
+		);
+	}
+
+	#[test]
+	fn until() {
+		assert_eq!(
+			occurrences("FREQ=DAILY;UNTIL=20240103T000000Z", date(2024, 1, 1), 10),
+			[date(2024, 1, 1), date(2024, 1, 2), date(2024, 1, 3)]
+		);
+		// A date includes the whole of that day in UTC, whatever the local time zone.
+		assert_eq!(
+			occurrences("FREQ=DAILY;UNTIL=20240103", date(2024, 1, 1), 10),
+			[date(2024, 1, 1), date(2024, 1, 2), date(2024, 1, 3)]
+		);
+	}
+
+	#[test]
+	fn until_follows_the_start() {
+		let rule: Recurrence = "FREQ=WEEKLY;UNTIL=20240131;X-AFTER-COMPLETION=TRUE"
+			.parse()
+			.unwrap();
+		assert!(rule.after_completion);
+		assert_eq!(rule.to_rrule(true), "FREQ=WEEKLY;UNTIL=20240131");
+		assert_eq!(rule.to_string(), "FREQ=WEEKLY;UNTIL=20240131T235959Z");
+		assert_eq!(
+			rule.to_rrule(true).parse::<Recurrence>().unwrap().until,
+			rule.until
+		);
+	}
+
+	#[test]
+	fn next_keeps_all_day_dates() {
+		let rule: Recurrence = "FREQ=MONTHLY".parse().unwrap();
+		let due = date(2099, 1, 15);
+		assert_eq!(rule.next(due, due), Some((date(2099, 2, 15), 1)));
+	}
+
+	#[test]
+	fn legacy_weekdays() {
+		let rule: Recurrence = ron::from_str("(monday: true, wednesday: true)").unwrap();
+		assert_eq!(rule, Recurrence::weekly(&[Day::Monday, Day::Wednesday]));
+		assert_eq!(
+			Recurrence::from_string("Mon, Wed".into()),
+			Recurrence::weekly(&[Day::Monday, Day::Wednesday])
+		);
+	}
+}
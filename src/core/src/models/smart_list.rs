@@ -0,0 +1,55 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use super::{status::Status, task::Task};
+
+/// A built-in view that gathers tasks from every list.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SmartList {
+    #[default]
+    Today,
+    Favorites,
+    Scheduled,
+    All,
+    Completed,
+}
+
+impl SmartList {
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self::Today,
+            Self::Favorites,
+            Self::Scheduled,
+            Self::All,
+            Self::Completed,
+        ]
+    }
+
+    pub fn icon(&self) -> String {
+        let shortcode = match self {
+            Self::Today => "sunny",
+            Self::Favorites => "star",
+            Self::Scheduled => "date",
+            Self::All => "clipboard",
+            Self::Completed => "white_check_mark",
+        };
+        emojis::get_by_shortcode(shortcode).unwrap().to_string()
+    }
+
+    /// Whether `task` belongs in this view. Only the completed view shows completed tasks,
+    /// and "today" includes tasks that are due today or overdue.
+    pub fn matches(&self, task: &Task) -> bool {
+        let completed = task.status == Status::Completed;
+        match self {
+            Self::Today => {
+                let today = Local::now().date_naive();
+                let due_today = task.due_date.is_some_and(|due| due.date_naive() <= today);
+                !completed && (task.today || due_today)
+            }
+            Self::Favorites => !completed && task.favorite,
+            Self::Scheduled => !completed && task.due_date.is_some(),
+            Self::All => !completed,
+            Self::Completed => completed,
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::error::Error;
+
 #[derive(
 	Clone,
 	Copy,
@@ -19,12 +21,14 @@ pub enum Status {
 	Completed = 1,
 }
 
-impl From<i32> for Status {
-	fn from(value: i32) -> Self {
+impl TryFrom<i32> for Status {
+	type Error = Error;
+
+	fn try_from(value: i32) -> Result<Self, Self::Error> {
 		match value {
-			0 => Self::NotStarted,
-			1 => Status::Completed,
-			_ => panic!("Invalid value for Status"),
+			0 => Ok(Self::NotStarted),
+			1 => Ok(Status::Completed),
+			_ => Err(Error::Corrupt(format!("Invalid value for Status: {value}"))),
 		}
 	}
 }
@@ -51,4 +51,155 @@ impl Task {
             last_modified_date_time: now,
         }
     }
+
+    /// Marks the task as modified now.
+    pub fn touch(&mut self) {
+        self.last_modified_date_time = Utc::now();
+    }
+
+    /// Changes the status, setting or clearing the completion date to match.
+    /// Completing a task completes its sub-tasks as well.
+    pub fn set_status(&mut self, status: Status) {
+        self.set_status_at(status, Utc::now());
+    }
+
+    fn set_status_at(&mut self, status: Status, now: DateTime<Utc>) {
+        if self.status == status {
+            return;
+        }
+        self.status = status;
+        self.completion_date = (status == Status::Completed).then_some(now);
+        if status == Status::Completed {
+            for sub_task in &mut self.sub_tasks {
+                sub_task.set_status_at(status, now);
+            }
+        }
+    }
+
+    /// Keeps the bookkeeping of a task right before it is written over
+    /// `stored`, whatever the caller changed: the completion date follows the
+    /// status, completing the task completes its sub-tasks, and a change the
+    /// caller did not stamp is stamped now. Sub-tasks are checked against
+    /// their stored copies the same way.
+    pub(crate) fn prepare_write(&mut self, stored: Option<&Task>) {
+        self.prepare_write_at(stored, Utc::now());
+    }
+
+    fn prepare_write_at(&mut self, stored: Option<&Task>, now: DateTime<Utc>) {
+        let completed = stored.is_some_and(|stored| stored.status != Status::Completed)
+            && self.status == Status::Completed;
+        if completed {
+            for sub_task in &mut self.sub_tasks {
+                sub_task.set_status_at(Status::Completed, now);
+            }
+        }
+        match self.status {
+            Status::Completed => {
+                self.completion_date.get_or_insert(now);
+            }
+            Status::NotStarted => self.completion_date = None,
+        }
+        for sub_task in &mut self.sub_tasks {
+            let stored = stored.and_then(|stored| {
+                stored
+                    .sub_tasks
+                    .iter()
+                    .find(|stored| stored.id == sub_task.id)
+            });
+            sub_task.prepare_write_at(stored, now);
+        }
+        if let Some(stored) = stored {
+            // A copy that is not newer than the stored one was either not
+            // stamped by the caller or is unchanged and merely out of date.
+            if self.last_modified_date_time <= stored.last_modified_date_time {
+                self.last_modified_date_time = if self.differs_from(stored) {
+                    now
+                } else {
+                    stored.last_modified_date_time
+                };
+            }
+        }
+    }
+
+    /// Whether the tasks hold different data, leaving out when they were modified.
+    fn differs_from(&self, other: &Task) -> bool {
+        let mut this = self.clone();
+        let mut other = other.clone();
+        this.clear_modified();
+        other.clear_modified();
+        this != other
+    }
+
+    fn clear_modified(&mut self) {
+        self.last_modified_date_time = DateTime::default();
+        for sub_task in &mut self.sub_tasks {
+            sub_task.clear_modified();
+        }
+    }
+
+    /// The task to create when this recurring task is completed: a copy that
+    /// is due on the next occurrence of its recurrence rule, with its
+    /// reminder moved along and its sub-tasks reset. Returns `None` when the
+    /// task does not repeat or its rule has run out.
+    pub fn next_occurrence(&self) -> Option<Task> {
+        let now = Utc::now();
+        let due = self.due_date.unwrap_or(now);
+        let (next, skipped) = self
+            .recurrence
+            .next(due, self.completion_date.unwrap_or(now))?;
+        let shift = next - due;
+
+        let mut task = self.clone();
+        task.id = Uuid::new_v4().to_string();
+        task.status = Status::NotStarted;
+        task.completion_date = None;
+        task.deletion_date = None;
+        task.due_date = Some(next);
+        task.reminder_date = self.reminder_date.map(|reminder| reminder + shift);
+        // The remaining count belongs to the new task, so the series still ends
+        // after the same number of occurrences.
+        task.recurrence.count = self
+            .recurrence
+            .count
+            .map(|count| count.saturating_sub(skipped));
+        task.sub_tasks = self
+            .sub_tasks
+            .iter()
+            .map(|sub_task| sub_task.reset(&task.id))
+            .collect();
+        task.created_date_time = now;
+        task.last_modified_date_time = now;
+        Some(task)
+    }
+
+    /// Whether `other` is a later, still open occurrence of this recurring
+    /// task, such as the one created when it was completed and then reopened.
+    pub fn is_followed_by(&self, other: &Task) -> bool {
+        let rule = |task: &Task| Recurrence {
+            count: None,
+            ..task.recurrence.clone()
+        };
+        !self.recurrence.is_empty()
+            && other.id != self.id
+            && other.parent == self.parent
+            && other.title == self.title
+            && other.status != Status::Completed
+            && other.due_date > self.due_date
+            && rule(other) == rule(self)
+    }
+
+    /// A not-started copy of this sub-task with a new id, under `parent`.
+    fn reset(&self, parent: &str) -> Task {
+        let mut task = self.clone();
+        task.id = Uuid::new_v4().to_string();
+        task.parent = parent.to_string();
+        task.status = Status::NotStarted;
+        task.completion_date = None;
+        task.sub_tasks = self
+            .sub_tasks
+            .iter()
+            .map(|sub_task| sub_task.reset(&task.id))
+            .collect();
+        task
+    }
 }
@@ -0,0 +1,429 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone, Utc};
+use futures::channel::mpsc::{self, UnboundedReceiver};
+use futures::StreamExt;
+use zbus::fdo::{DBusProxy, RequestNameReply};
+use zbus::zvariant::Value;
+use zbus::Connection;
+
+use crate::{
+    error::Result,
+    models::{query::TaskQuery, status::Status, task::Task},
+    service::TaskService,
+    services::files::write_atomic,
+};
+
+/// How long the Snooze action postpones a reminder.
+pub const SNOOZE: Duration = Duration::minutes(10);
+
+/// Reminders that were missed while nothing was running are still shown if
+/// they are at most this old.
+const MISSED: Duration = Duration::days(1);
+
+/// The hour of the due date at which a task without a reminder is notified.
+const DUE_ALERT_HOUR: u32 = 9;
+
+/// How often the tasks are read again to pick up reminders set elsewhere.
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// The file in the data directory of the app that holds when the reminders
+/// were last checked.
+const LAST_CHECK_FILE: &str = "reminders.ron";
+
+const ACTION_COMPLETE: &str = "complete";
+const ACTION_SNOOZE: &str = "snooze";
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, &Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+
+    fn close_notification(&self, id: u32) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn action_invoked(&self, id: u32, action_key: String) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn notification_closed(&self, id: u32, reason: u32) -> zbus::Result<()>;
+}
+
+/// Something the scheduler did, so a caller can refresh what it shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReminderEvent {
+    /// A notification was posted for the task.
+    Shown(Task),
+    /// The task was completed from its notification.
+    Completed(Task),
+    /// The task's reminder was postponed from its notification.
+    Snoozed(Task),
+}
+
+/// Posts a desktop notification when the reminder of a task is due, or on
+/// the morning of its due date when it has no reminder.
+///
+/// The scheduler reads the tasks of every list through the [`TaskService`],
+/// so it sees the same data as the app whichever provider is in use, and
+/// writes the Complete and Snooze actions of its notifications back the same
+/// way. The time of the last check is kept in the data directory of the app,
+/// so a reminder the app showed is not shown again by the daemon once it
+/// takes over, or after a restart. Notifications go to whoever owns
+/// `org.freedesktop.Notifications` on the given connection, which is the
+/// desktop's notification daemon on the session bus, or a mock server in
+/// tests.
+pub struct ReminderScheduler {
+    service: TaskService,
+    notifications: NotificationsProxy<'static>,
+    actions: ActionInvokedStream<'static>,
+    closed: NotificationClosedStream<'static>,
+    complete_label: String,
+    snooze_label: String,
+    shown: HashMap<u32, Task>,
+    last_check: Option<DateTime<Utc>>,
+    events: VecDeque<ReminderEvent>,
+    services: UnboundedReceiver<TaskService>,
+}
+
+impl ReminderScheduler {
+    pub async fn new(service: TaskService, connection: &Connection) -> Result<Self> {
+        let notifications = NotificationsProxy::new(connection).await?;
+        let (_, services) = mpsc::unbounded();
+        Ok(Self {
+            service,
+            actions: notifications.receive_action_invoked().await?,
+            closed: notifications.receive_notification_closed().await?,
+            notifications,
+            complete_label: "Complete".into(),
+            snooze_label: "Snooze".into(),
+            shown: HashMap::new(),
+            last_check: None,
+            events: VecDeque::new(),
+            services,
+        })
+    }
+
+    /// A scheduler that posts to the notification daemon of the session bus.
+    ///
+    /// Only one process posts reminders at a time, so the app and the
+    /// background daemon never show the same notification twice. This waits
+    /// until the reminder name of the app is free on the session bus, which
+    /// happens as soon as the process holding it exits.
+    pub async fn session(service: TaskService) -> Result<Self> {
+        let connection = Connection::session().await?;
+        let name = format!("{}.Reminders", service.app_id);
+        let mut acquired = DBusProxy::new(&connection)
+            .await?
+            .receive_name_acquired()
+            .await?;
+        let reply = connection
+            .request_name_with_flags(name.as_str(), Default::default())
+            .await?;
+        if reply == RequestNameReply::InQueue {
+            while let Some(signal) = acquired.next().await {
+                if signal.args()?.name.as_str() == name {
+                    break;
+                }
+            }
+        }
+        Self::new(service, &connection).await
+    }
+
+    /// Reads the tasks from the services sent on `services` from now on, such
+    /// as when the user picks another provider. A service is only switched to
+    /// while waiting for the next reminder, so switching never drops a
+    /// notification being posted or an action being handled.
+    pub fn with_services(mut self, services: UnboundedReceiver<TaskService>) -> Self {
+        self.services = services;
+        self
+    }
+
+    /// Sets the labels of the notification actions, which are in English by default.
+    pub fn with_actions(mut self, complete: impl Into<String>, snooze: impl Into<String>) -> Self {
+        self.complete_label = complete.into();
+        self.snooze_label = snooze.into();
+        self
+    }
+
+    /// Waits for the next thing the scheduler does: posting a notification,
+    /// or handling an action the user picked on one.
+    pub async fn next(&mut self) -> Result<ReminderEvent> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+
+            let upcoming = self.notify_due(Utc::now()).await?;
+            if !self.events.is_empty() {
+                continue;
+            }
+
+            let wait = upcoming
+                .and_then(|at| (at - Utc::now()).to_std().ok())
+                .map_or(POLL_INTERVAL, |wait| wait.min(POLL_INTERVAL));
+            tokio::select! {
+                Some(signal) = self.actions.next() => {
+                    let args = signal.args()?;
+                    if let Some(event) = self.handle_action(args.id, &args.action_key).await? {
+                        self.events.push_back(event);
+                    }
+                }
+                Some(signal) = self.closed.next() => {
+                    self.shown.remove(&signal.args()?.id);
+                }
+                Some(service) = self.services.next() => {
+                    self.service = service;
+                }
+                _ = tokio::time::sleep(wait) => {}
+            }
+        }
+    }
+
+    /// Posts a notification for every reminder that is due at `now` and was
+    /// not shown by an earlier check, and returns when the next one is due.
+    pub async fn notify_due(&mut self, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>> {
+        let Some(mut service) = self.service.get_service() else {
+            return Ok(None);
+        };
+        let tasks = service.query(&TaskQuery::default()).await?;
+        let last_check = self.last_check.max(self.read_last_check());
+
+        let mut upcoming: Option<DateTime<Utc>> = None;
+        for task in tasks {
+            let Some(reminder) = alert_date(&task) else {
+                continue;
+            };
+            if task.status == Status::Completed {
+                continue;
+            }
+            if reminder > now {
+                upcoming = Some(upcoming.map_or(reminder, |at| at.min(reminder)));
+                continue;
+            }
+            if last_check.is_some_and(|last_check| reminder <= last_check)
+                || now - reminder > MISSED
+            {
+                continue;
+            }
+            let id = self.notify(&task).await?;
+            self.shown.insert(id, task.clone());
+            self.events.push_back(ReminderEvent::Shown(task));
+        }
+
+        let last_check = last_check.map_or(now, |last_check| last_check.max(now));
+        self.last_check = Some(last_check);
+        if let Err(err) = self.write_last_check(last_check) {
+            tracing::warn!("failed to save when the reminders were checked: {err}");
+        }
+        Ok(upcoming)
+    }
+
+    /// Applies an action picked on one of the scheduler's notifications.
+    pub async fn handle_action(&mut self, id: u32, action: &str) -> Result<Option<ReminderEvent>> {
+        let Some(shown) = self.shown.remove(&id) else {
+            return Ok(None);
+        };
+        let Some(mut service) = self.service.get_service() else {
+            return Ok(None);
+        };
+        let mut task = service.get_task(shown.parent, shown.id).await?;
+        let event = match action {
+            ACTION_COMPLETE => {
+                task.set_status(Status::Completed);
+                service.update_task(task.clone()).await?;
+                service.create_next_occurrence(&task).await?;
+                ReminderEvent::Completed(task)
+            }
+            ACTION_SNOOZE => {
+                task.reminder_date = Some(Utc::now() + SNOOZE);
+                service.update_task(task.clone()).await?;
+                ReminderEvent::Snoozed(task)
+            }
+            _ => return Ok(None),
+        };
+        self.notifications.close_notification(id).await.ok();
+        Ok(Some(event))
+    }
+
+    fn last_check_path(&self) -> Option<PathBuf> {
+        Some(
+            dirs::data_local_dir()?
+                .join(&self.service.app_id)
+                .join(LAST_CHECK_FILE),
+        )
+    }
+
+    /// When the reminders were last checked by any process of the app.
+    fn read_last_check(&self) -> Option<DateTime<Utc>> {
+        let text = std::fs::read_to_string(self.last_check_path()?).ok()?;
+        ron::from_str(&text).ok()
+    }
+
+    fn write_last_check(&self, last_check: DateTime<Utc>) -> Result<()> {
+        let Some(path) = self.last_check_path() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        write_atomic(&path, ron::to_string(&last_check)?.as_bytes())?;
+        Ok(())
+    }
+
+    async fn notify(&self, task: &Task) -> Result<u32> {
+        let body = task
+            .due_date
+            .map(|due| due.format("%m-%d-%Y").to_string())
+            .unwrap_or_default();
+        let desktop_entry = Value::from(self.service.app_id.as_str());
+        let hints = HashMap::from([("desktop-entry", &desktop_entry)]);
+        let id = self
+            .notifications
+            .notify(
+                &self.service.app_id,
+                0,
+                &self.service.app_id,
+                &task.title,
+                &body,
+                &[
+                    ACTION_COMPLETE,
+                    &self.complete_label,
+                    ACTION_SNOOZE,
+                    &self.snooze_label,
+                ],
+                hints,
+                0,
+            )
+            .await?;
+        Ok(id)
+    }
+}
+
+/// When to notify about a task: its reminder, or the morning of its due date.
+fn alert_date(task: &Task) -> Option<DateTime<Utc>> {
+    task.reminder_date.or_else(|| {
+        let time = NaiveTime::from_hms_opt(DUE_ALERT_HOUR, 0, 0)?;
+        let due = task.due_date?.date_naive().and_time(time);
+        Local
+            .from_local_datetime(&due)
+            .earliest()
+            .map(|due| due.with_timezone(&Utc))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use zbus::zvariant::OwnedValue;
+
+    use super::*;
+    use crate::{models::list::List, testing};
+
+    const PATH: &str = "/org/freedesktop/Notifications";
+
+    /// A notification daemon that remembers what it was asked to do.
+    #[derive(Default)]
+    struct StubNotifications {
+        posted: Vec<String>,
+        closed: Vec<u32>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Notifications")]
+    impl StubNotifications {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &mut self,
+            _app_name: String,
+            _replaces_id: u32,
+            _app_icon: String,
+            summary: String,
+            _body: String,
+            _actions: Vec<String>,
+            _hints: HashMap<String, OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            self.posted.push(summary);
+            self.posted.len() as u32
+        }
+
+        fn close_notification(&mut self, id: u32) {
+            self.closed.push(id);
+        }
+    }
+
+    #[tokio::test]
+    async fn notifies_once_and_writes_actions_back() -> Result<()> {
+        let service = testing::service();
+        let (bus, connection) = testing::peers().await?;
+        bus.object_server()
+            .at(PATH, StubNotifications::default())
+            .await?;
+        let stub = bus
+            .object_server()
+            .interface::<_, StubNotifications>(PATH)
+            .await?;
+        let mut scheduler = ReminderScheduler::new(service.clone(), &connection).await?;
+
+        let mut provider = service.get_service().unwrap();
+        let list = provider.create_list(List::new("Chores")).await?;
+        let now = Utc::now();
+        for title in ["Water the plants", "Call the plumber"] {
+            let mut task = Task::new(title.into(), list.id.clone());
+            task.reminder_date = Some(now - Duration::minutes(1));
+            provider.create_task(task).await?;
+        }
+        let mut later = Task::new("Take out the trash".into(), list.id.clone());
+        later.reminder_date = Some(now + Duration::hours(1));
+        provider.create_task(later).await?;
+
+        let upcoming = scheduler.notify_due(now).await?;
+        assert_eq!(upcoming, Some(now + Duration::hours(1)));
+        scheduler.notify_due(now).await?;
+        let posted = stub.get().await.posted.clone();
+        assert_eq!(posted.len(), 2);
+
+        // Another process taking over, like the daemon after the app exits,
+        // does not show them again.
+        let mut other = ReminderScheduler::new(service.clone(), &connection).await?;
+        other.notify_due(now + Duration::minutes(1)).await?;
+        assert_eq!(stub.get().await.posted.len(), 2);
+
+        let notification = |title: &str| {
+            let position = posted.iter().position(|posted| posted == title).unwrap();
+            position as u32 + 1
+        };
+
+        let completed = notification("Water the plants");
+        let event = scheduler.handle_action(completed, ACTION_COMPLETE).await?;
+        let Some(ReminderEvent::Completed(task)) = event else {
+            panic!("expected the task to be completed, got {event:?}");
+        };
+        let stored = provider.get_task(list.id.clone(), task.id.clone()).await?;
+        assert_eq!(stored.status, Status::Completed);
+
+        let snoozed = notification("Call the plumber");
+        let event = scheduler.handle_action(snoozed, ACTION_SNOOZE).await?;
+        let Some(ReminderEvent::Snoozed(task)) = event else {
+            panic!("expected the task to be snoozed, got {event:?}");
+        };
+        let stored = provider.get_task(list.id.clone(), task.id.clone()).await?;
+        assert!(stored.reminder_date.is_some_and(|reminder| reminder > now));
+        assert_eq!(stored.status, Status::NotStarted);
+
+        assert_eq!(stub.get().await.closed, [completed, snoozed]);
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
+
+use crate::models::task::Task;
+
+/// Where in a task a search term was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SearchField {
+    Title,
+    Tag,
+    SubTask,
+    Notes,
+}
+
+/// A piece of a task that matched, with the byte ranges of the matching terms
+/// so they can be highlighted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub field: SearchField,
+    pub text: String,
+    pub ranges: Vec<Range<usize>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchResult {
+    pub task: Task,
+    pub matches: Vec<SearchMatch>,
+}
+
+/// An in-memory index over the titles, notes, sub-task titles and tags of
+/// every task that is not in the trash.
+///
+/// The index is built once from all tasks and then kept up to date with
+/// [`SearchIndex::insert`] and [`SearchIndex::remove`] as tasks change, so a
+/// search never has to read the storage. Every word of a query has to be the
+/// start of a word in the task, which keeps results stable while typing.
+#[derive(Clone, Debug, Default)]
+pub struct SearchIndex {
+    tasks: HashMap<String, Task>,
+    words: BTreeMap<String, HashSet<String>>,
+}
+
+impl SearchIndex {
+    pub fn new(tasks: impl IntoIterator<Item = Task>) -> Self {
+        let mut index = Self::default();
+        for task in tasks {
+            index.insert(task);
+        }
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Adds or replaces a task. Tasks in the trash are removed instead.
+    pub fn insert(&mut self, task: Task) {
+        self.remove(&task.id);
+        if task.deletion_date.is_some() {
+            return;
+        }
+        for word in task_words(&task) {
+            self.words.entry(word).or_default().insert(task.id.clone());
+        }
+        self.tasks.insert(task.id.clone(), task);
+    }
+
+    pub fn remove(&mut self, task_id: &str) {
+        let Some(task) = self.tasks.remove(task_id) else {
+            return;
+        };
+        for word in task_words(&task) {
+            if let Some(ids) = self.words.get_mut(&word) {
+                ids.remove(task_id);
+                if ids.is_empty() {
+                    self.words.remove(&word);
+                }
+            }
+        }
+    }
+
+    /// Removes every task of a list.
+    pub fn remove_list(&mut self, list_id: &str) {
+        let ids: Vec<String> = self
+            .tasks
+            .values()
+            .filter(|task| task.parent == list_id)
+            .map(|task| task.id.clone())
+            .collect();
+        for id in ids {
+            self.remove(&id);
+        }
+    }
+
+    /// Finds the tasks containing every word of `query`, best matches first.
+    pub fn search(&self, query: &str) -> Vec<SearchResult> {
+        let terms = words(query);
+        let Some((first, rest)) = terms.split_first() else {
+            return vec![];
+        };
+
+        let mut ids = self.prefixed(first);
+        for term in rest {
+            let matching = self.prefixed(term);
+            ids.retain(|id| matching.contains(id));
+        }
+
+        let mut results: Vec<SearchResult> = ids
+            .into_iter()
+            .filter_map(|id| self.tasks.get(id))
+            .map(|task| SearchResult {
+                task: task.clone(),
+                matches: matches(task, &terms),
+            })
+            .collect();
+        results.sort_by(|a, b| {
+            let field = |result: &SearchResult| result.matches.first().map(|m| m.field);
+            field(a).cmp(&field(b)).then_with(|| {
+                a.task
+                    .title
+                    .to_lowercase()
+                    .cmp(&b.task.title.to_lowercase())
+            })
+        });
+        results
+    }
+
+    fn prefixed(&self, prefix: &str) -> HashSet<&String> {
+        self.words
+            .range(prefix.to_string()..)
+            .take_while(|(word, _)| word.starts_with(prefix))
+            .flat_map(|(_, ids)| ids)
+            .collect()
+    }
+}
+
+/// Lowercase words of `text`, split on anything that is not a letter or digit.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn task_words(task: &Task) -> HashSet<String> {
+    fields(task)
+        .into_iter()
+        .flat_map(|(_, text)| words(&text))
+        .collect()
+}
+
+fn fields(task: &Task) -> Vec<(SearchField, String)> {
+    let mut fields = vec![(SearchField::Title, task.title.clone())];
+    fields.extend(task.tags.iter().map(|tag| (SearchField::Tag, tag.clone())));
+    let mut sub_tasks: Vec<&Task> = task.sub_tasks.iter().collect();
+    while let Some(sub_task) = sub_tasks.pop() {
+        fields.push((SearchField::SubTask, sub_task.title.clone()));
+        sub_tasks.extend(&sub_task.sub_tasks);
+    }
+    fields.extend(
+        task.notes
+            .lines()
+            .map(|line| (SearchField::Notes, line.to_string())),
+    );
+    fields
+}
+
+fn matches(task: &Task, terms: &[String]) -> Vec<SearchMatch> {
+    fields(task)
+        .into_iter()
+        .filter_map(|(field, text)| {
+            let ranges = ranges(&text, terms);
+            (!ranges.is_empty()).then_some(SearchMatch {
+                field,
+                text,
+                ranges,
+            })
+        })
+        .collect()
+}
+
+/// Byte ranges of the words in `text` that start with one of `terms`.
+fn ranges(text: &str, terms: &[String]) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut start = None;
+    for (index, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(index),
+            (false, Some(word_start)) => {
+                let word = text[word_start..index].to_lowercase();
+                if let Some(term) = terms.iter().find(|term| word.starts_with(term.as_str())) {
+                    // Highlight as much of the original word as the term covers.
+                    let end = text[word_start..index]
+                        .char_indices()
+                        .nth(term.chars().count())
+                        .map_or(index, |(offset, _)| word_start + offset);
+                    ranges.push(word_start..end);
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    ranges
+}
@@ -1,12 +1,17 @@
 use std::path::PathBuf;
 
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{sqlite::SqliteRow, Connection};
 
 use crate::{
+    error::Error,
+    migration::{self, MigrationSummary},
     models::{list::List, priority::Priority, recurrence::Recurrence, status::Status, task::Task},
-    services::computer::ComputerStorage,
+    services::{
+        caldav::CalDavStorage, computer::ComputerStorage, sqlite::SqliteStorage,
+        todo_txt::TodoTxtStorage,
+    },
     task_service::TasksProvider,
 };
 
@@ -16,10 +21,15 @@ pub struct TaskService {
     pub provider: Provider,
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
 pub enum Provider {
     #[default]
     Computer,
+    Sqlite,
+    CalDav,
+    TodoTxt,
 }
 
 impl TaskService {
@@ -31,25 +41,42 @@ impl TaskService {
     }
 
     pub fn services<'a>() -> &'a [Provider] {
-        &[Provider::Computer]
+        &[
+            Provider::Computer,
+            Provider::Sqlite,
+            Provider::CalDav,
+            Provider::TodoTxt,
+        ]
     }
 
     pub fn get_service(&self) -> Option<Box<dyn TasksProvider>> {
         match self.provider {
             Provider::Computer => ComputerStorage::new(&self.app_id)
                 .map(|storage| Box::new(storage) as Box<dyn TasksProvider>),
+            Provider::Sqlite => SqliteStorage::new(&self.app_id)
+                .map(|storage| Box::new(storage) as Box<dyn TasksProvider>),
+            Provider::CalDav => CalDavStorage::new(&self.app_id)
+                .map(|storage| Box::new(storage) as Box<dyn TasksProvider>),
+            Provider::TodoTxt => TodoTxtStorage::new(&self.app_id)
+                .map(|storage| Box::new(storage) as Box<dyn TasksProvider>),
         }
     }
 
     pub fn title(&self) -> &str {
         match self.provider {
             Provider::Computer => "Computer",
+            Provider::Sqlite => "SQLite",
+            Provider::CalDav => "CalDAV",
+            Provider::TodoTxt => "todo.txt",
         }
     }
 
     pub fn icon(&self) -> &str {
         match self.provider {
             Provider::Computer => "computer-symbolic",
+            Provider::Sqlite => "drive-harddisk-symbolic",
+            Provider::CalDav => "network-server-symbolic",
+            Provider::TodoTxt => "text-x-generic-symbolic",
         }
     }
 
@@ -76,52 +103,91 @@ impl TaskService {
         }
         Ok(())
     }
+
+    /// Copies the lists and tasks stored by the `Computer` provider into the
+    /// `Sqlite` database, unless the database already contains lists.
+    pub async fn migrate_to_sqlite(app_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let (Some(mut source), Some(mut target)) = (
+            TaskService::new(app_id, Provider::Computer).get_service(),
+            TaskService::new(app_id, Provider::Sqlite).get_service(),
+        ) else {
+            return Err(Error::Unsupported("Opening this provider").into());
+        };
+        if !target.get_lists().await?.is_empty() {
+            return Ok(());
+        }
+        migration::copy(source.as_mut(), target.as_mut(), false).await?;
+        Ok(())
+    }
+
+    /// Copies every list and task from one provider to another. With `dry_run`
+    /// nothing is written and the summary describes what would change.
+    pub async fn copy(
+        app_id: &str,
+        from: Provider,
+        to: Provider,
+        dry_run: bool,
+    ) -> Result<MigrationSummary, Box<dyn std::error::Error>> {
+        let (Some(mut source), Some(mut target)) = (
+            TaskService::new(app_id, from).get_service(),
+            TaskService::new(app_id, to).get_service(),
+        ) else {
+            return Err(Error::Unsupported("Opening this provider").into());
+        };
+        let summary = migration::copy(source.as_mut(), target.as_mut(), dry_run).await?;
+        Ok(summary)
+    }
 }
 
 use sqlx::Row;
 
 async fn get_tasks(database_path: &PathBuf) -> Result<Vec<Task>, Box<dyn std::error::Error>> {
     let mut conn = sqlx::SqliteConnection::connect(database_path.to_str().unwrap()).await?;
-    let tasks = sqlx::query("SELECT * FROM tasks")
-        .map(|row: SqliteRow| Task {
-            id: row.get(0),
-            parent: row.get(1),
-            title: row.get(2),
-            notes: row.get(3),
-            priority: Priority::from(row.get::<i32, _>(4)),
-            favorite: row.get(5),
-            status: Status::from(row.get::<i32, _>(6)),
-            completion_date: NaiveDateTime::parse_from_str(row.get(7), "%Y-%m-%d %H:%M:%S.%f")
-                .map(|ndt| ndt.and_utc())
-                .ok(),
-            due_date: NaiveDateTime::parse_from_str(row.get(8), "%Y-%m-%d %H:%M:%S.%f")
-                .map(|ndt| ndt.and_utc())
-                .ok(),
-            reminder_date: NaiveDateTime::parse_from_str(row.get(9), "%Y-%m-%d %H:%M:%S.%f")
-                .map(|ndt| ndt.and_utc())
-                .ok(),
-            created_date_time: NaiveDateTime::parse_from_str(row.get(10), "%Y-%m-%d %H:%M:%S.%f")
-                .unwrap()
-                .and_utc(),
-            last_modified_date_time: NaiveDateTime::parse_from_str(
-                row.get(11),
-                "%Y-%m-%d %H:%M:%S.%f",
-            )
-            .unwrap()
-            .and_utc(),
-            sub_tasks: serde_json::from_str(row.get(12)).unwrap(),
-            tags: serde_json::from_str(row.get(13)).unwrap(),
-            today: row.get(14),
-            deletion_date: NaiveDateTime::parse_from_str(row.get(15), "%Y-%m-%d %H:%M:%S.%f")
-                .map(|ndt| ndt.and_utc())
-                .ok(),
-            recurrence: Recurrence::from_string(row.get(16)),
-        })
+    let rows = sqlx::query("SELECT * FROM tasks")
         .fetch_all(&mut conn)
         .await?;
+    let mut tasks = vec![];
+    for row in rows {
+        match legacy_task(&row) {
+            Ok(task) => tasks.push(task),
+            Err(err) => tracing::warn!("skipping a task that could not be migrated: {err}"),
+        }
+    }
     Ok(tasks)
 }
 
+/// Reads a task from a row of the old database.
+fn legacy_task(row: &SqliteRow) -> crate::error::Result<Task> {
+    let date = |index: usize| -> crate::error::Result<Option<DateTime<Utc>>> {
+        let value: Option<String> = row.try_get(index)?;
+        Ok(value
+            .and_then(|value| NaiveDateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S.%f").ok())
+            .map(|ndt| ndt.and_utc()))
+    };
+    let required = |index: usize| -> crate::error::Result<DateTime<Utc>> {
+        date(index)?.ok_or_else(|| Error::Corrupt(format!("missing date in column {index}")))
+    };
+    Ok(Task {
+        id: row.try_get(0)?,
+        parent: row.try_get(1)?,
+        title: row.try_get(2)?,
+        notes: row.try_get(3)?,
+        priority: Priority::try_from(row.try_get::<i32, _>(4)?)?,
+        favorite: row.try_get(5)?,
+        status: Status::try_from(row.try_get::<i32, _>(6)?)?,
+        completion_date: date(7)?,
+        due_date: date(8)?,
+        reminder_date: date(9)?,
+        created_date_time: required(10)?,
+        last_modified_date_time: required(11)?,
+        sub_tasks: serde_json::from_str(row.try_get(12)?)?,
+        tags: serde_json::from_str(row.try_get(13)?)?,
+        today: row.try_get(14)?,
+        deletion_date: date(15)?,
+        recurrence: Recurrence::from_string(row.try_get(16)?),
+    })
+}
+
 async fn get_lists(database_path: &PathBuf) -> Result<Vec<List>, Box<dyn std::error::Error>> {
     let mut conn = sqlx::SqliteConnection::connect(database_path.to_str().unwrap()).await?;
     let tasks = sqlx::query("SELECT * FROM lists")
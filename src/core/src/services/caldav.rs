@@ -0,0 +1,1497 @@
+use std::{fs::File, io::Write, path::PathBuf};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conflict::Conflict,
+    error::{Error, Result},
+    ical::{self, Component},
+    models::{list::List, query::TaskQuery, task::Task},
+    services::files::{self, write_atomic},
+    task_service::TasksProvider,
+};
+
+use self::client::{Client, Outcome, Response};
+
+mod client;
+mod secret;
+
+const DIRECTORY: &str = "caldav";
+const ACCOUNT_FILE: &str = "account.ron";
+const CACHE_FILE: &str = "cache.ron";
+
+/// The server the CalDAV provider syncs with.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CalDavAccount {
+    /// Address of the server, or of the collection holding the calendars
+    /// for servers that can't find it on their own.
+    pub url: String,
+    pub username: String,
+    /// Kept in the keyring. Only accounts saved before that have it on disk.
+    #[serde(default, skip_serializing)]
+    pub password: String,
+}
+
+impl CalDavAccount {
+    /// The account saved for the app, if any.
+    pub fn load(application_id: &str) -> Option<Self> {
+        let path = directory(application_id)?.join(ACCOUNT_FILE);
+        let content = std::fs::read_to_string(path).ok()?;
+        match ron::from_str(&content) {
+            Ok(account) => Some(account),
+            Err(err) => {
+                tracing::warn!("failed to read the CalDAV account: {err}");
+                None
+            }
+        }
+    }
+
+    /// Saves the account for the app, with the password in the keyring. An
+    /// empty password keeps the one stored before. Switching to another
+    /// account drops what was cached from the previous one, along with
+    /// unsent changes.
+    pub async fn save(&self, application_id: &str) -> Result<()> {
+        let directory = directory(application_id)
+            .ok_or_else(|| Error::Unsupported("Storing data on this system"))?;
+        if !self.password.is_empty() {
+            let connection = zbus::Connection::session().await.map_err(Error::Keyring)?;
+            secret::store(&connection, application_id, self)
+                .await
+                .map_err(Error::Keyring)?;
+        }
+        std::fs::create_dir_all(&directory)?;
+        if Self::load(application_id)
+            .is_some_and(|account| account.url != self.url || account.username != self.username)
+        {
+            let cache = directory.join(CACHE_FILE);
+            if cache.exists() {
+                std::fs::remove_file(cache)?;
+            }
+        }
+        let mut options = File::options();
+        options.create(true).truncate(true).write(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(directory.join(ACCOUNT_FILE))?;
+        file.write_all(ron::to_string(self)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+fn directory(application_id: &str) -> Option<PathBuf> {
+    Some(dirs::data_local_dir()?.join(application_id).join(DIRECTORY))
+}
+
+/// Syncs lists and tasks with the VTODO calendars of a CalDAV server.
+///
+/// Everything is read from a local copy of the calendars, which is brought
+/// up to date when lists are read, fetching only the calendars whose ctag
+/// and the tasks whose ETag changed. Changes are written to the local copy
+/// and queued, then sent in order whenever the server can be reached, so
+/// the provider keeps working offline.
+///
+/// Every write to the server only applies over the version that was last
+/// read. When a task was changed on both sides in the meantime, the version
+/// modified last is kept. The trash is kept locally: a task moved to the
+/// trash is deleted from the server, and restoring it uploads it again.
+#[derive(Debug, Clone)]
+pub struct CalDavStorage {
+    path: PathBuf,
+    client: Client,
+}
+
+/// The local copy of the calendars and the changes not sent yet.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Cache {
+    /// Path of the collection holding the calendars, once found.
+    home: Option<String>,
+    calendars: Vec<Calendar>,
+    trash: Vec<Task>,
+    pending: Vec<Pending>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Calendar {
+    list: List,
+    /// Relative to the collection holding the calendars for a list created
+    /// before that collection was found.
+    href: String,
+    ctag: Option<String>,
+    resources: Vec<Resource>,
+}
+
+/// A task or sub-task, stored on the server as its own VTODO.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Resource {
+    uid: String,
+    href: String,
+    /// `None` until the resource was uploaded.
+    etag: Option<String>,
+    ics: String,
+}
+
+/// A change made locally that still has to be sent to the server.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum Pending {
+    CreateList(String),
+    UpdateList(String),
+    DeleteList { href: String },
+    PutTask { list: String, uid: String },
+    DeleteTask { href: String, etag: Option<String> },
+}
+
+impl Calendar {
+    fn new(list: List, href: String) -> Self {
+        Self {
+            list,
+            href,
+            ctag: None,
+            resources: vec![],
+        }
+    }
+
+    /// The tasks of the calendar, with their sub-tasks nested.
+    fn tasks(&self) -> Vec<Task> {
+        let tasks = self
+            .resources
+            .iter()
+            .filter_map(|resource| match resource.task(&self.list.id) {
+                Ok(task) => Some(task),
+                Err(err) => {
+                    tracing::warn!("skipping task {}: {err}", resource.href);
+                    None
+                }
+            })
+            .collect();
+        ical::nest(tasks)
+    }
+
+    fn task(&self, id: &str) -> Result<Task> {
+        find(&self.tasks(), id)
+            .cloned()
+            .ok_or_else(|| Error::TaskNotFound(id.to_string()))
+    }
+
+    /// Moves a calendar created before the collection holding the calendars
+    /// was found into `home`, along with its tasks.
+    fn locate(&mut self, home: &str) {
+        if self.href.starts_with('/') {
+            return;
+        }
+        self.href = format!("{home}{}", self.href);
+        for resource in &mut self.resources {
+            resource.href = format!("{home}{}", resource.href);
+        }
+    }
+
+    fn resource(&self, uid: &str) -> Option<&Resource> {
+        self.resources.iter().find(|resource| resource.uid == uid)
+    }
+
+    /// Stores a task and its sub-tasks, queueing an upload of the ones that
+    /// changed and the removal of sub-tasks that are gone since `stored`.
+    fn put(&mut self, task: &Task, stored: Option<&Task>, pending: &mut Vec<Pending>) {
+        let tasks = ical::flatten(task);
+        if let Some(stored) = stored {
+            for (removed, _) in ical::flatten(stored) {
+                if !tasks.iter().any(|(task, _)| task.id == removed.id) {
+                    self.remove(&removed.id, pending);
+                }
+            }
+        }
+        for (task, parent) in tasks {
+            let existing = self.resource(&task.id);
+            let component = existing.and_then(|resource| resource.component().ok());
+            let todo = ical::component_from_task(&task, parent.as_deref(), component.as_ref());
+            let unchanged = component.is_some_and(|component| {
+                ical::task_from_component(&component, &self.list.id).ok()
+                    == ical::task_from_component(&todo, &self.list.id).ok()
+            });
+            if unchanged {
+                continue;
+            }
+            let resource = Resource {
+                uid: task.id.clone(),
+                href: existing.map_or_else(
+                    || format!("{}{}.ics", self.href, task.id),
+                    |resource| resource.href.clone(),
+                ),
+                etag: existing.and_then(|resource| resource.etag.clone()),
+                ics: Component::calendar(vec![todo]).to_ics(),
+            };
+            self.resources.retain(|existing| existing.uid != task.id);
+            self.resources.push(resource);
+            enqueue(
+                pending,
+                Pending::PutTask {
+                    list: self.list.id.clone(),
+                    uid: task.id.clone(),
+                },
+            );
+        }
+    }
+
+    /// Removes a task and its sub-tasks, queueing their deletion on the server.
+    fn remove_tree(&mut self, task: &Task, pending: &mut Vec<Pending>) {
+        for (task, _) in ical::flatten(task) {
+            self.remove(&task.id, pending);
+        }
+    }
+
+    fn remove(&mut self, uid: &str, pending: &mut Vec<Pending>) {
+        let Some(index) = self
+            .resources
+            .iter()
+            .position(|resource| resource.uid == uid)
+        else {
+            return;
+        };
+        let resource = self.resources.remove(index);
+        let put = Pending::PutTask {
+            list: self.list.id.clone(),
+            uid: uid.to_string(),
+        };
+        pending.retain(|pending| *pending != put);
+        // A task that was never uploaded has nothing to delete.
+        if resource.etag.is_some() {
+            enqueue(
+                pending,
+                Pending::DeleteTask {
+                    href: resource.href,
+                    etag: resource.etag,
+                },
+            );
+        }
+    }
+}
+
+impl Resource {
+    fn component(&self) -> Result<Component> {
+        ical::parse(&self.ics)?
+            .iter()
+            .flat_map(Component::todos)
+            .next()
+            .cloned()
+            .ok_or_else(|| Error::Corrupt(format!("No VTODO in {}", self.href)))
+    }
+
+    fn task(&self, list_id: &str) -> Result<(Task, Option<String>)> {
+        ical::task_from_component(&self.component()?, list_id)
+    }
+}
+
+impl Cache {
+    fn calendar(&self, list_id: &str) -> Result<&Calendar> {
+        self.calendars
+            .iter()
+            .find(|calendar| calendar.list.id == list_id)
+            .ok_or_else(|| Error::ListNotFound(list_id.to_string()))
+    }
+
+    /// The calendar of a list, along with the queue, to change both.
+    fn calendar_mut(&mut self, list_id: &str) -> Result<(&mut Calendar, &mut Vec<Pending>)> {
+        let calendar = self
+            .calendars
+            .iter_mut()
+            .find(|calendar| calendar.list.id == list_id)
+            .ok_or_else(|| Error::ListNotFound(list_id.to_string()))?;
+        Ok((calendar, &mut self.pending))
+    }
+}
+
+fn enqueue(pending: &mut Vec<Pending>, change: Pending) {
+    if !pending.contains(&change) {
+        pending.push(change);
+    }
+}
+
+fn find<'a>(tasks: &'a [Task], id: &str) -> Option<&'a Task> {
+    tasks.iter().find_map(|task| {
+        if task.id == id {
+            Some(task)
+        } else {
+            find(&task.sub_tasks, id)
+        }
+    })
+}
+
+/// The id of the list of a calendar, the last segment of its href.
+fn list_id(href: &str) -> String {
+    href.trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or(href)
+        .to_string()
+}
+
+/// Whether the server may accept the change later, as opposed to having
+/// refused it for good.
+fn is_temporary(err: &Error) -> bool {
+    match err {
+        Error::Network(_) | Error::Keyring(_) => true,
+        Error::Server { status, .. } => *status >= 500 || *status == 401 || *status == 429,
+        _ => false,
+    }
+}
+
+impl CalDavStorage {
+    pub(crate) fn new(application_id: &str) -> Option<Self> {
+        let path = directory(application_id)?;
+        if !path.exists() {
+            std::fs::create_dir_all(&path).ok()?;
+        }
+        let account = CalDavAccount::load(application_id)?;
+        match Client::new(application_id, &account) {
+            Ok(client) => Some(Self { path, client }),
+            Err(err) => {
+                tracing::warn!("failed to set up the CalDAV account: {err}");
+                None
+            }
+        }
+    }
+
+    fn load(&self) -> Result<Cache> {
+        let path = self.path.join(CACHE_FILE);
+        if !path.exists() {
+            return Ok(Cache::default());
+        }
+        Ok(ron::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Writes the local copy if it differs from `before`, through a
+    /// temporary file so it is never left half written.
+    fn save(&self, before: &Cache, cache: &Cache) -> Result<()> {
+        if before == cache {
+            return Ok(());
+        }
+        write_atomic(
+            &self.path.join(CACHE_FILE),
+            ron::to_string(cache)?.as_bytes(),
+        )
+    }
+
+    /// Sends the queued changes, then brings the local copy up to date. The
+    /// local copy is all there is while the server can't be reached.
+    async fn sync(&self, cache: &mut Cache) {
+        let result = match self.flush(cache).await {
+            Ok(()) => self.refresh(cache).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            tracing::info!("working offline: {err}");
+        }
+    }
+
+    /// Sends queued changes in order, stopping at the first one that the
+    /// server can't take right now.
+    async fn flush(&self, cache: &mut Cache) -> Result<()> {
+        while let Some(change) = cache.pending.first().cloned() {
+            if let Err(err) = self.send(cache, &change).await {
+                if is_temporary(&err) {
+                    return Err(err);
+                }
+                tracing::warn!("dropping a change the server refused: {err}");
+            }
+            cache.pending.retain(|pending| *pending != change);
+        }
+        Ok(())
+    }
+
+    /// Sends changes, leaving them queued if the server can't be reached.
+    async fn push(&self, cache: &mut Cache) {
+        if let Err(err) = self.flush(cache).await {
+            tracing::info!("keeping changes to send later: {err}");
+        }
+    }
+
+    async fn send(&self, cache: &mut Cache, change: &Pending) -> Result<()> {
+        match change {
+            Pending::CreateList(id) => {
+                let home = self.home(cache).await?;
+                let (calendar, _) = cache.calendar_mut(id)?;
+                calendar.locate(&home);
+                match self
+                    .client
+                    .make_calendar(&calendar.href, &calendar.list)
+                    .await
+                {
+                    Ok(Outcome::Missing) => Err(Error::Server {
+                        status: 404,
+                        url: calendar.href.clone(),
+                    }),
+                    Ok(_) => Ok(()),
+                    // Already there, created by an earlier attempt.
+                    Err(Error::Server { status: 405, .. }) => {
+                        self.client
+                            .update_calendar(&calendar.href, &calendar.list)
+                            .await
+                    }
+                    Err(err) => Err(err),
+                }
+            }
+            Pending::UpdateList(id) => {
+                let calendar = cache.calendar(id)?;
+                self.client
+                    .update_calendar(&calendar.href, &calendar.list)
+                    .await
+            }
+            Pending::DeleteList { href } => {
+                self.client.delete(href, None).await?;
+                Ok(())
+            }
+            Pending::PutTask { list, uid } => self.upload(cache, list, uid).await,
+            Pending::DeleteTask { href, etag } => {
+                if self.client.delete(href, etag.as_deref()).await? == Outcome::Changed {
+                    // Changed somewhere else after it was read here, which
+                    // wins over the deletion, so it shows up again.
+                    tracing::warn!("{href} was changed on the server, keeping it");
+                    if let Some(calendar) = cache
+                        .calendars
+                        .iter_mut()
+                        .find(|calendar| href.starts_with(&calendar.href))
+                    {
+                        calendar.ctag = None;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    async fn upload(&self, cache: &mut Cache, list: &str, uid: &str) -> Result<()> {
+        let Ok((calendar, _)) = cache.calendar_mut(list) else {
+            return Ok(());
+        };
+        let Some(resource) = calendar
+            .resources
+            .iter_mut()
+            .find(|resource| resource.uid == uid)
+        else {
+            return Ok(());
+        };
+        let mut etag = resource.etag.clone();
+        loop {
+            match self
+                .client
+                .put(&resource.href, resource.ics.clone(), etag.as_deref())
+                .await?
+            {
+                Outcome::Done(new) => {
+                    resource.etag = match new {
+                        Some(new) => Some(new),
+                        None => self.client.etag(&resource.href).await?,
+                    };
+                    return Ok(());
+                }
+                Outcome::Missing => {
+                    return Err(Error::Server {
+                        status: 404,
+                        url: resource.href.clone(),
+                    })
+                }
+                Outcome::Changed => {}
+            }
+            // Changed on the server since it was read: keep whichever
+            // version was modified last.
+            let theirs = self
+                .client
+                .multiget(&calendar.href, std::slice::from_ref(&resource.href))
+                .await?
+                .into_iter()
+                .next();
+            let Some(Response {
+                etag: their_etag,
+                calendar_data: Some(ics),
+                ..
+            }) = theirs
+            else {
+                // Deleted on the server, upload it again.
+                etag = None;
+                continue;
+            };
+            let ours = resource.task(list)?.0;
+            let newer = ical::parse(&ics)?
+                .iter()
+                .flat_map(Component::todos)
+                .next()
+                .map(|todo| ical::task_from_component(todo, list))
+                .transpose()?
+                .is_some_and(|(theirs, _)| {
+                    theirs.last_modified_date_time > ours.last_modified_date_time
+                });
+            if newer || their_etag.is_none() {
+                tracing::warn!("{} was changed on the server, keeping it", resource.href);
+                resource.etag = their_etag;
+                resource.ics = ics;
+                return Ok(());
+            }
+            tracing::warn!("{} was changed on the server, replacing it", resource.href);
+            etag = their_etag;
+        }
+    }
+
+    /// Finds the collection holding the calendars once, and remembers it.
+    async fn home(&self, cache: &mut Cache) -> Result<String> {
+        if let Some(home) = &cache.home {
+            return Ok(home.clone());
+        }
+        let home = self.client.discover().await?;
+        let home = if home.ends_with('/') {
+            home
+        } else {
+            format!("{home}/")
+        };
+        cache.home = Some(home.clone());
+        Ok(home)
+    }
+
+    /// Reads the calendars from the server, and the tasks of the ones whose
+    /// ctag changed. Nothing changes locally if any request fails.
+    async fn refresh(&self, cache: &mut Cache) -> Result<()> {
+        let home = self.home(cache).await?;
+        let mut calendars = vec![];
+        for response in self.client.calendars(&home).await? {
+            if !response.holds_tasks() {
+                continue;
+            }
+            let mut calendar = match cache
+                .calendars
+                .iter()
+                .find(|calendar| calendar.href == response.href)
+            {
+                Some(calendar) => calendar.clone(),
+                None => {
+                    let mut list = List::new("");
+                    list.id = list_id(&response.href);
+                    list.icon = None;
+                    Calendar::new(list, response.href.clone())
+                }
+            };
+            let list_id = calendar.list.id.clone();
+            if !cache
+                .pending
+                .contains(&Pending::UpdateList(list_id.clone()))
+            {
+                calendar.list.name = response
+                    .display_name
+                    .clone()
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or_else(|| list_id.clone());
+                calendar.list.description = response.description.clone().unwrap_or_default();
+                calendar.list.icon = response.icon.clone().filter(|icon| !icon.is_empty());
+            }
+            if response.ctag.is_none() || response.ctag != calendar.ctag {
+                self.refresh_tasks(&mut calendar, &cache.pending).await?;
+                calendar.ctag = response.ctag;
+            }
+            calendars.push(calendar);
+        }
+        // Lists created here that were not sent yet.
+        calendars.extend(
+            cache
+                .calendars
+                .iter()
+                .filter(|calendar| {
+                    cache
+                        .pending
+                        .contains(&Pending::CreateList(calendar.list.id.clone()))
+                })
+                .cloned(),
+        );
+        cache.calendars = calendars;
+        Ok(())
+    }
+
+    /// Fetches the tasks of a calendar whose ETag changed and drops the ones
+    /// that are gone, leaving alone the ones with changes not sent yet.
+    async fn refresh_tasks(&self, calendar: &mut Calendar, pending: &[Pending]) -> Result<()> {
+        let etags = self.client.etags(&calendar.href).await?;
+        let list = calendar.list.id.clone();
+        let unsent = |uid: &str| {
+            pending.contains(&Pending::PutTask {
+                list: list.clone(),
+                uid: uid.to_string(),
+            })
+        };
+        let deleted = |href: &str| {
+            pending.iter().any(|change| {
+                matches!(change, Pending::DeleteTask { href: queued, .. } if *queued == href)
+            })
+        };
+        calendar.resources.retain(|resource| {
+            unsent(&resource.uid) || etags.iter().any(|(href, _)| *href == resource.href)
+        });
+        let changed: Vec<String> = etags
+            .into_iter()
+            .filter(|(href, etag)| {
+                !deleted(href)
+                    && match calendar
+                        .resources
+                        .iter()
+                        .find(|resource| resource.href == *href)
+                    {
+                        Some(resource) => resource.etag != *etag && !unsent(&resource.uid),
+                        None => true,
+                    }
+            })
+            .map(|(href, _)| href)
+            .collect();
+        if changed.is_empty() {
+            return Ok(());
+        }
+        for response in self.client.multiget(&calendar.href, &changed).await? {
+            let Some(ics) = response.calendar_data else {
+                continue;
+            };
+            let resource = Resource {
+                uid: String::new(),
+                href: response.href,
+                etag: response.etag,
+                ics,
+            };
+            let uid = match resource.task(&list) {
+                Ok((task, _)) => task.id,
+                Err(err) => {
+                    tracing::warn!("skipping task {}: {err}", resource.href);
+                    continue;
+                }
+            };
+            calendar
+                .resources
+                .retain(|existing| existing.href != resource.href && existing.uid != uid);
+            calendar.resources.push(Resource { uid, ..resource });
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TasksProvider for CalDavStorage {
+    async fn get_task(&mut self, list_id: String, task_id: String) -> Result<Task> {
+        let _lock = files::lock(&self.path)?;
+        self.load()?.calendar(&list_id)?.task(&task_id)
+    }
+
+    async fn get_tasks_from_list(&mut self, parent_list: String) -> Result<Vec<Task>> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut cache = before.clone();
+        self.sync(&mut cache).await;
+        self.save(&before, &cache)?;
+        Ok(cache.calendar(&parent_list)?.tasks())
+    }
+
+    async fn get_deleted_tasks(&mut self) -> Result<Vec<Task>> {
+        let _lock = files::lock(&self.path)?;
+        Ok(self.load()?.trash)
+    }
+
+    async fn query(&mut self, query: &TaskQuery) -> Result<Vec<Task>> {
+        let _lock = files::lock(&self.path)?;
+        let cache = self.load()?;
+        let mut tasks = vec![];
+        for calendar in &cache.calendars {
+            if query
+                .list
+                .as_ref()
+                .is_none_or(|list| *list == calendar.list.id)
+            {
+                tasks.extend(
+                    calendar
+                        .tasks()
+                        .into_iter()
+                        .filter(|task| query.matches(task)),
+                );
+            }
+        }
+        if query.include_deleted {
+            tasks.extend(cache.trash.into_iter().filter(|task| query.matches(task)));
+        }
+        Ok(query.finish(tasks))
+    }
+
+    async fn create_task(&mut self, mut task: Task) -> Result<Task> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut cache = before.clone();
+        let trashed = cache.trash.iter().any(|trashed| trashed.id == task.id);
+        let (calendar, pending) = cache.calendar_mut(&task.parent)?;
+        if trashed || calendar.resource(&task.id).is_some() {
+            return Err(Error::AlreadyExists(task.id));
+        }
+        task.prepare_write(None);
+        // Tasks copied from the trash of another provider stay in the trash.
+        if task.deletion_date.is_some() {
+            cache.trash.push(task.clone());
+        } else {
+            calendar.put(&task, None, pending);
+        }
+        self.save(&before, &cache)?;
+        self.push(&mut cache).await;
+        self.save(&before, &cache)?;
+        Ok(task)
+    }
+
+    async fn update_task(&mut self, mut task: Task) -> Result<()> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut cache = before.clone();
+        let (calendar, pending) = cache.calendar_mut(&task.parent)?;
+        let stored = calendar.task(&task.id)?;
+        task.prepare_write(Some(&stored));
+        calendar.put(&task, Some(&stored), pending);
+        self.save(&before, &cache)?;
+        self.push(&mut cache).await;
+        self.save(&before, &cache)?;
+        Ok(())
+    }
+
+    async fn update_task_if_unchanged(
+        &mut self,
+        mut task: Task,
+        expected: DateTime<Utc>,
+    ) -> Result<()> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut cache = before.clone();
+        // Catch changes made on other devices too.
+        self.sync(&mut cache).await;
+        let (calendar, pending) = cache.calendar_mut(&task.parent)?;
+        let stored = calendar.task(&task.id)?;
+        // LAST-MODIFIED has no fractions of a second.
+        if stored.last_modified_date_time.timestamp() != expected.timestamp() {
+            self.save(&before, &cache)?;
+            return Err(Error::Conflict(Box::new(Conflict {
+                ours: task,
+                theirs: stored,
+            })));
+        }
+        task.prepare_write(Some(&stored));
+        calendar.put(&task, Some(&stored), pending);
+        self.save(&before, &cache)?;
+        self.push(&mut cache).await;
+        self.save(&before, &cache)?;
+        Ok(())
+    }
+
+    async fn delete_task(&mut self, list_id: String, task_id: String) -> Result<()> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut cache = before.clone();
+        let (calendar, pending) = cache.calendar_mut(&list_id)?;
+        let mut task = calendar.task(&task_id)?;
+        calendar.remove_tree(&task, pending);
+        task.deletion_date = Some(Utc::now());
+        cache.trash.push(task);
+        self.save(&before, &cache)?;
+        self.push(&mut cache).await;
+        self.save(&before, &cache)?;
+        Ok(())
+    }
+
+    async fn restore_task(&mut self, list_id: String, task_id: String) -> Result<()> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut cache = before.clone();
+        let index = cache
+            .trash
+            .iter()
+            .position(|task| task.id == task_id)
+            .ok_or_else(|| Error::TaskNotFound(task_id.clone()))?;
+        let mut task = cache.trash.remove(index);
+        task.deletion_date = None;
+        let (calendar, pending) = cache.calendar_mut(&list_id)?;
+        calendar.put(&task, None, pending);
+        self.save(&before, &cache)?;
+        self.push(&mut cache).await;
+        self.save(&before, &cache)?;
+        Ok(())
+    }
+
+    async fn purge_task(&mut self, list_id: String, task_id: String) -> Result<()> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut cache = before.clone();
+        if let Some(index) = cache.trash.iter().position(|task| task.id == task_id) {
+            cache.trash.remove(index);
+        } else {
+            let (calendar, pending) = cache.calendar_mut(&list_id)?;
+            let task = calendar.task(&task_id)?;
+            calendar.remove_tree(&task, pending);
+        }
+        self.save(&before, &cache)?;
+        self.push(&mut cache).await;
+        self.save(&before, &cache)?;
+        Ok(())
+    }
+
+    async fn get_lists(&mut self) -> Result<Vec<List>> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut cache = before.clone();
+        self.sync(&mut cache).await;
+        self.save(&before, &cache)?;
+        Ok(cache
+            .calendars
+            .into_iter()
+            .map(|calendar| calendar.list)
+            .collect())
+    }
+
+    async fn get_list(&mut self, id: String) -> Result<List> {
+        let _lock = files::lock(&self.path)?;
+        Ok(self.load()?.calendar(&id)?.list.clone())
+    }
+
+    async fn create_list(&mut self, list: List) -> Result<List> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut cache = before.clone();
+        if cache.calendar(&list.id).is_ok() {
+            return Err(Error::AlreadyExists(list.id));
+        }
+        // The new calendar goes next to the others, which may only be found
+        // once the server can be reached.
+        let home = match self.home(&mut cache).await {
+            Ok(home) => home,
+            Err(err) if is_temporary(&err) => String::new(),
+            Err(err) => return Err(err),
+        };
+        let href = format!("{home}{}/", list.id);
+        cache.calendars.push(Calendar::new(list.clone(), href));
+        enqueue(&mut cache.pending, Pending::CreateList(list.id.clone()));
+        self.save(&before, &cache)?;
+        self.push(&mut cache).await;
+        self.save(&before, &cache)?;
+        Ok(list)
+    }
+
+    async fn update_list(&mut self, list: List) -> Result<()> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut cache = before.clone();
+        let (calendar, pending) = cache.calendar_mut(&list.id)?;
+        calendar.list = list.clone();
+        if !pending.contains(&Pending::CreateList(list.id.clone())) {
+            enqueue(pending, Pending::UpdateList(list.id));
+        }
+        self.save(&before, &cache)?;
+        self.push(&mut cache).await;
+        self.save(&before, &cache)?;
+        Ok(())
+    }
+
+    async fn delete_list(&mut self, id: String) -> Result<()> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut cache = before.clone();
+        let calendar = cache.calendar(&id)?.clone();
+        cache.calendars.retain(|calendar| calendar.list.id != id);
+        cache.trash.retain(|task| task.parent != id);
+        let created = cache.pending.contains(&Pending::CreateList(id.clone()));
+        cache.pending.retain(|pending| match pending {
+            Pending::CreateList(list) | Pending::UpdateList(list) => *list != id,
+            Pending::PutTask { list, .. } => *list != id,
+            Pending::DeleteTask { href, .. } => !href.starts_with(&calendar.href),
+            Pending::DeleteList { .. } => true,
+        });
+        // A list that never reached the server has nothing to delete there.
+        if !created {
+            enqueue(
+                &mut cache.pending,
+                Pending::DeleteList {
+                    href: calendar.href,
+                },
+            );
+        }
+        self.save(&before, &cache)?;
+        self.push(&mut cache).await;
+        self.save(&before, &cache)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use quick_xml::escape::escape;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::{TcpListener, TcpStream},
+    };
+    use uuid::Uuid;
+
+    use super::*;
+
+    const HOME: &str = "/dav/";
+
+    /// A CalDAV server keeping its calendars in memory, which answers the
+    /// requests of the client the way a real one would, and logs them.
+    #[derive(Default)]
+    struct StubServer {
+        calendars: Vec<StubCalendar>,
+        requests: Vec<String>,
+        /// The hrefs of the tasks sent with their content.
+        fetched: Vec<String>,
+        offline: bool,
+        /// Whether tasks are refused for good, as without write access.
+        refusing: bool,
+        version: u32,
+    }
+
+    struct StubCalendar {
+        href: String,
+        name: String,
+        ctag: u32,
+        /// The href, ETag and content of each task.
+        resources: Vec<(String, String, String)>,
+    }
+
+    struct Reply {
+        status: u16,
+        etag: Option<String>,
+        body: String,
+    }
+
+    impl Reply {
+        fn status(status: u16) -> Self {
+            Self {
+                status,
+                etag: None,
+                body: String::new(),
+            }
+        }
+
+        fn multistatus(responses: String) -> Self {
+            Self {
+                status: 207,
+                etag: None,
+                body: format!(
+                    r#"<?xml version="1.0" encoding="utf-8"?><D:multistatus xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav" xmlns:CS="http://calendarserver.org/ns/">{responses}</D:multistatus>"#
+                ),
+            }
+        }
+    }
+
+    impl StubServer {
+        fn calendar(&mut self, href: &str) -> Option<&mut StubCalendar> {
+            self.calendars
+                .iter_mut()
+                .find(|calendar| href.starts_with(&calendar.href))
+        }
+
+        /// Stores a task as another device would.
+        fn put(&mut self, list: &str, task: &Task) -> String {
+            self.version += 1;
+            let etag = format!("\"{}\"", self.version);
+            let ics = Component::calendar(vec![ical::component_from_task(task, None, None)]);
+            let href = format!("{HOME}{list}/{}.ics", task.id);
+            let calendar = self.calendar(&href).expect("no such calendar");
+            calendar.ctag += 1;
+            calendar
+                .resources
+                .retain(|(existing, _, _)| *existing != href);
+            calendar
+                .resources
+                .push((href.clone(), etag.clone(), ics.to_ics()));
+            href
+        }
+
+        fn handle(
+            &mut self,
+            method: &str,
+            href: &str,
+            headers: &[(String, String)],
+            body: &str,
+        ) -> Reply {
+            self.requests.push(format!("{method} {href}"));
+            if self.offline {
+                return Reply::status(503);
+            }
+            if self.refusing && method == "PUT" {
+                return Reply::status(403);
+            }
+            let header = |name: &str| {
+                headers
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                    .map(|(_, value)| value.as_str())
+            };
+            match method {
+                "PROPFIND" if href == HOME && header("Depth") == Some("1") => {
+                    let calendars: String = self
+                        .calendars
+                        .iter()
+                        .map(|calendar| {
+                            format!(
+                                "<D:response><D:href>{}</D:href><D:propstat><D:prop>\
+                                 <D:resourcetype><D:collection/><C:calendar/></D:resourcetype>\
+                                 <D:displayname>{}</D:displayname><CS:getctag>{}</CS:getctag>\
+                                 </D:prop></D:propstat></D:response>",
+                                calendar.href,
+                                escape(&calendar.name),
+                                calendar.ctag
+                            )
+                        })
+                        .collect();
+                    Reply::multistatus(format!(
+                        "<D:response><D:href>{HOME}</D:href></D:response>{calendars}"
+                    ))
+                }
+                "PROPFIND" => {
+                    let etag = self.calendar(href).and_then(|calendar| {
+                        calendar
+                            .resources
+                            .iter()
+                            .find(|(existing, _, _)| existing == href)
+                            .map(|(_, etag, _)| etag.clone())
+                    });
+                    Reply::multistatus(etag.map_or_else(String::new, |etag| {
+                        format!(
+                            "<D:response><D:href>{href}</D:href><D:propstat><D:prop>\
+                             <D:getetag>{etag}</D:getetag></D:prop></D:propstat></D:response>"
+                        )
+                    }))
+                }
+                "REPORT" => {
+                    let Some(calendar) =
+                        self.calendars.iter().find(|calendar| calendar.href == href)
+                    else {
+                        return Reply::status(404);
+                    };
+                    let multiget = body.contains("calendar-multiget");
+                    let wanted: Vec<&str> = body
+                        .split("<D:href>")
+                        .skip(1)
+                        .filter_map(|rest| rest.split("</D:href>").next())
+                        .collect();
+                    let responses = calendar
+                        .resources
+                        .iter()
+                        .filter(|(href, _, _)| !multiget || wanted.contains(&href.as_str()))
+                        .inspect(|(href, _, _)| {
+                            if multiget {
+                                self.fetched.push(href.clone());
+                            }
+                        })
+                        .map(|(href, etag, ics)| {
+                            let data = if multiget {
+                                format!("<C:calendar-data>{}</C:calendar-data>", escape(ics))
+                            } else {
+                                String::new()
+                            };
+                            format!(
+                                "<D:response><D:href>{href}</D:href><D:propstat><D:prop>\
+                                 <D:getetag>{etag}</D:getetag>{data}</D:prop></D:propstat></D:response>"
+                            )
+                        })
+                        .collect();
+                    Reply::multistatus(responses)
+                }
+                "PUT" => {
+                    self.version += 1;
+                    let etag = format!("\"{}\"", self.version);
+                    let Some(calendar) = self.calendar(href) else {
+                        return Reply::status(409);
+                    };
+                    let current = calendar
+                        .resources
+                        .iter()
+                        .find(|(existing, _, _)| existing == href)
+                        .map(|(_, etag, _)| etag.as_str());
+                    let applies = match (header("If-Match"), header("If-None-Match")) {
+                        (Some(expected), _) => current == Some(expected),
+                        (None, Some("*")) => current.is_none(),
+                        _ => true,
+                    };
+                    if !applies {
+                        return Reply::status(412);
+                    }
+                    calendar.ctag += 1;
+                    calendar
+                        .resources
+                        .retain(|(existing, _, _)| existing != href);
+                    calendar
+                        .resources
+                        .push((href.to_string(), etag.clone(), body.to_string()));
+                    Reply {
+                        status: 201,
+                        etag: Some(etag),
+                        body: String::new(),
+                    }
+                }
+                "DELETE" => {
+                    if let Some(index) = self
+                        .calendars
+                        .iter()
+                        .position(|calendar| calendar.href == href)
+                    {
+                        self.calendars.remove(index);
+                        return Reply::status(204);
+                    }
+                    let Some(calendar) = self.calendar(href) else {
+                        return Reply::status(404);
+                    };
+                    let Some(index) = calendar
+                        .resources
+                        .iter()
+                        .position(|(existing, _, _)| existing == href)
+                    else {
+                        return Reply::status(404);
+                    };
+                    if header("If-Match").is_some_and(|etag| etag != calendar.resources[index].1) {
+                        return Reply::status(412);
+                    }
+                    calendar.resources.remove(index);
+                    calendar.ctag += 1;
+                    Reply::status(204)
+                }
+                "MKCALENDAR" => {
+                    if self.calendars.iter().any(|calendar| calendar.href == href) {
+                        return Reply::status(405);
+                    }
+                    let name = body
+                        .split("<D:displayname>")
+                        .nth(1)
+                        .and_then(|rest| rest.split("</D:displayname>").next())
+                        .unwrap_or_default();
+                    self.calendars.push(StubCalendar {
+                        href: href.to_string(),
+                        name: name.to_string(),
+                        ctag: 0,
+                        resources: vec![],
+                    });
+                    Reply::status(201)
+                }
+                "PROPPATCH" => Reply::multistatus(String::new()),
+                _ => Reply::status(405),
+            }
+        }
+    }
+
+    /// Serves the stub over HTTP on a free local port, returning its address.
+    async fn serve(stub: Arc<Mutex<StubServer>>) -> std::io::Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}{HOME}", listener.local_addr()?);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(connection(stream, stub.clone()));
+            }
+        });
+        Ok(url)
+    }
+
+    async fn connection(stream: TcpStream, stub: Arc<Mutex<StubServer>>) -> std::io::Result<()> {
+        let mut stream = BufReader::new(stream);
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await? == 0 {
+                return Ok(());
+            }
+            let mut request = line.split_whitespace();
+            let method = request.next().unwrap_or_default().to_string();
+            let href = request.next().unwrap_or_default().to_string();
+            let mut headers = vec![];
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).await?;
+                let Some((name, value)) = line.trim_end().split_once(':') else {
+                    break;
+                };
+                headers.push((name.to_string(), value.trim().to_string()));
+            }
+            let length = headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("Content-Length"))
+                .and_then(|(_, length)| length.parse().ok())
+                .unwrap_or(0);
+            let mut body = vec![0; length];
+            stream.read_exact(&mut body).await?;
+            let reply = stub.lock().unwrap().handle(
+                &method,
+                &href,
+                &headers,
+                &String::from_utf8_lossy(&body),
+            );
+            let etag = reply
+                .etag
+                .map(|etag| format!("ETag: {etag}\r\n"))
+                .unwrap_or_default();
+            let response = format!(
+                "HTTP/1.1 {} Stub\r\nContent-Length: {}\r\n{etag}\r\n{}",
+                reply.status,
+                reply.body.len(),
+                reply.body
+            );
+            stream.get_mut().write_all(response.as_bytes()).await?;
+        }
+    }
+
+    /// A provider syncing with the stub, with a local copy of its own.
+    async fn storage(stub: &Arc<Mutex<StubServer>>) -> Result<CalDavStorage> {
+        let path = std::env::temp_dir().join(format!("tasks-core-caldav-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&path)?;
+        let account = CalDavAccount {
+            url: serve(stub.clone()).await?,
+            username: "user".to_string(),
+            password: "secret".to_string(),
+        };
+        Ok(CalDavStorage {
+            path,
+            client: Client::new("dev.edfloreshz.Tasks.Test", &account)?,
+        })
+    }
+
+    fn stub_with_list(list: &str) -> Arc<Mutex<StubServer>> {
+        let stub = StubServer {
+            calendars: vec![StubCalendar {
+                href: format!("{HOME}{list}/"),
+                name: "Work".to_string(),
+                ctag: 0,
+                resources: vec![],
+            }],
+            ..Default::default()
+        };
+        Arc::new(Mutex::new(stub))
+    }
+
+    fn requests(stub: &Arc<Mutex<StubServer>>) -> Vec<String> {
+        std::mem::take(&mut stub.lock().unwrap().requests)
+    }
+
+    #[test]
+    fn tells_temporary_failures_apart() {
+        let status = |status| Error::Server {
+            status,
+            url: String::new(),
+        };
+        assert!(is_temporary(&status(503)));
+        assert!(is_temporary(&status(401)));
+        assert!(is_temporary(&status(429)));
+        assert!(!is_temporary(&status(403)));
+        assert!(!is_temporary(&Error::Corrupt(String::new())));
+    }
+
+    #[tokio::test]
+    async fn fetches_only_tasks_whose_etag_changed() -> Result<()> {
+        let stub = stub_with_list("work");
+        let mut storage = storage(&stub).await?;
+        let kept = Task::new("Kept".to_string(), "work".to_string());
+        let mut changed = Task::new("Changed".to_string(), "work".to_string());
+        let removed = Task::new("Removed".to_string(), "work".to_string());
+        let (changed_href, removed_href) = {
+            let mut stub = stub.lock().unwrap();
+            stub.put("work", &kept);
+            (stub.put("work", &changed), stub.put("work", &removed))
+        };
+
+        let lists = storage.get_lists().await?;
+        assert_eq!(lists.len(), 1);
+        assert_eq!(lists[0].name, "Work");
+        assert_eq!(
+            storage.get_tasks_from_list("work".to_string()).await?.len(),
+            3
+        );
+
+        // Nothing is fetched again while the ctag stays the same.
+        requests(&stub);
+        storage.get_tasks_from_list("work".to_string()).await?;
+        assert!(!requests(&stub)
+            .iter()
+            .any(|request| request.starts_with("REPORT")));
+
+        changed.title = "Changed elsewhere".to_string();
+        changed.touch();
+        {
+            let mut stub = stub.lock().unwrap();
+            stub.fetched.clear();
+            stub.put("work", &changed);
+            let calendar = stub.calendar("/dav/work/").unwrap();
+            calendar
+                .resources
+                .retain(|(href, _, _)| *href != removed_href);
+            calendar.ctag += 1;
+        }
+        let tasks = storage.get_tasks_from_list("work".to_string()).await?;
+        let mut titles: Vec<_> = tasks.iter().map(|task| task.title.as_str()).collect();
+        titles.sort();
+        assert_eq!(titles, ["Changed elsewhere", "Kept"]);
+        assert_eq!(stub.lock().unwrap().fetched, [changed_href]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sends_changes_queued_while_offline() -> Result<()> {
+        let stub = Arc::new(Mutex::new(StubServer {
+            offline: true,
+            ..Default::default()
+        }));
+        let mut storage = storage(&stub).await?;
+        let list = storage.create_list(List::new("Errands")).await?;
+        let task = Task::new("Buy milk".to_string(), list.id.clone());
+        let task = storage.create_task(task).await?;
+        let tasks = storage.get_tasks_from_list(list.id.clone()).await?;
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title, "Buy milk");
+        assert_eq!(
+            storage.load()?.pending,
+            [
+                Pending::CreateList(list.id.clone()),
+                Pending::PutTask {
+                    list: list.id.clone(),
+                    uid: task.id.clone(),
+                },
+            ]
+        );
+
+        stub.lock().unwrap().offline = false;
+        let lists = storage.get_lists().await?;
+        assert_eq!(lists.len(), 1);
+        assert_eq!(lists[0].name, "Errands");
+        assert!(storage.load()?.pending.is_empty());
+        let stub = stub.lock().unwrap();
+        let calendar = &stub.calendars[0];
+        assert_eq!(calendar.href, format!("{HOME}{}/", list.id));
+        assert_eq!(calendar.name, "Errands");
+        assert_eq!(
+            calendar.resources[0].0,
+            format!("{HOME}{}/{}.ics", list.id, task.id)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn drops_changes_the_server_refuses() -> Result<()> {
+        let stub = stub_with_list("work");
+        let mut storage = storage(&stub).await?;
+        storage.get_lists().await?;
+        stub.lock().unwrap().refusing = true;
+        let refused = Task::new("Refused".to_string(), "work".to_string());
+        storage.create_task(refused).await?;
+        assert!(storage.load()?.pending.is_empty());
+
+        stub.lock().unwrap().offline = true;
+        let kept = Task::new("Kept".to_string(), "work".to_string());
+        let kept = storage.create_task(kept).await?;
+        assert_eq!(
+            storage.load()?.pending,
+            [Pending::PutTask {
+                list: "work".to_string(),
+                uid: kept.id,
+            }]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keeps_the_version_modified_last_on_conflict() -> Result<()> {
+        let stub = stub_with_list("work");
+        let mut storage = storage(&stub).await?;
+        storage.get_lists().await?;
+        let task = Task::new("Draft".to_string(), "work".to_string());
+        let task = storage.create_task(task).await?;
+        let title = |stub: &Arc<Mutex<StubServer>>| {
+            let (href, etag, ics) = stub.lock().unwrap().calendars[0].resources[0].clone();
+            let resource = Resource {
+                uid: String::new(),
+                href,
+                etag: Some(etag),
+                ics,
+            };
+            resource.task("work").map(|(task, _)| task.title)
+        };
+
+        // Changed on another device before it was changed here.
+        let mut theirs = task.clone();
+        theirs.title = "Older".to_string();
+        theirs.last_modified_date_time -= chrono::Duration::days(1);
+        stub.lock().unwrap().put("work", &theirs);
+        let mut ours = storage
+            .get_task("work".to_string(), task.id.clone())
+            .await?;
+        ours.title = "Ours".to_string();
+        ours.touch();
+        requests(&stub);
+        storage.update_task(ours).await?;
+        assert_eq!(title(&stub)?, "Ours");
+        let sent = requests(&stub);
+        assert_eq!(
+            sent.iter().filter(|sent| sent.starts_with("PUT")).count(),
+            2
+        );
+
+        // Changed on another device after it was changed here.
+        let mut theirs = task.clone();
+        theirs.title = "Newer".to_string();
+        theirs.last_modified_date_time = Utc::now() + chrono::Duration::days(1);
+        stub.lock().unwrap().put("work", &theirs);
+        let mut ours = storage
+            .get_task("work".to_string(), task.id.clone())
+            .await?;
+        ours.title = "Mine".to_string();
+        ours.touch();
+        storage.update_task(ours).await?;
+        assert_eq!(title(&stub)?, "Newer");
+        let stored = storage.get_task("work".to_string(), task.id).await?;
+        assert_eq!(stored.title, "Newer");
+        Ok(())
+    }
+
+    /// Runs against a real server, such as Radicale started with
+    /// `radicale --storage-filesystem-folder=/tmp/radicale --auth-type=none`,
+    /// given in `TASKS_CALDAV_URL`, `TASKS_CALDAV_USERNAME` and
+    /// `TASKS_CALDAV_PASSWORD`.
+    #[tokio::test]
+    #[ignore = "needs a CalDAV server"]
+    async fn syncs_with_a_real_server() -> Result<()> {
+        let variable = |name| std::env::var(name).unwrap_or_default();
+        let account = CalDavAccount {
+            url: variable("TASKS_CALDAV_URL"),
+            username: variable("TASKS_CALDAV_USERNAME"),
+            password: std::env::var("TASKS_CALDAV_PASSWORD").unwrap_or_else(|_| "none".to_string()),
+        };
+        let path = std::env::temp_dir().join(format!("tasks-core-caldav-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&path)?;
+        let mut storage = CalDavStorage {
+            path,
+            client: Client::new("dev.edfloreshz.Tasks.Test", &account)?,
+        };
+        let list = storage.create_list(List::new("Tasks test")).await?;
+        let task = Task::new("Buy milk".to_string(), list.id.clone());
+        let mut task = storage.create_task(task).await?;
+        assert!(storage.load()?.pending.is_empty());
+
+        // A second copy reads what the first one wrote.
+        let path = std::env::temp_dir().join(format!("tasks-core-caldav-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&path)?;
+        let mut other = CalDavStorage {
+            path,
+            client: storage.client.clone(),
+        };
+        assert!(other
+            .get_lists()
+            .await?
+            .iter()
+            .any(|other| other.id == list.id));
+        let tasks = other.get_tasks_from_list(list.id.clone()).await?;
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title, "Buy milk");
+
+        task.title = "Buy oat milk".to_string();
+        storage.update_task(task.clone()).await?;
+        let tasks = other.get_tasks_from_list(list.id.clone()).await?;
+        assert_eq!(tasks[0].title, "Buy oat milk");
+
+        storage.delete_list(list.id.clone()).await?;
+        assert!(storage.load()?.pending.is_empty());
+        assert!(!other
+            .get_lists()
+            .await?
+            .iter()
+            .any(|other| other.id == list.id));
+        Ok(())
+    }
+}
@@ -0,0 +1,496 @@
+use std::sync::{Arc, OnceLock};
+
+use quick_xml::{escape::escape, events::Event, Reader};
+use reqwest::{header, Method, RequestBuilder, StatusCode, Url};
+
+use crate::{
+    error::{Error, Result},
+    models::list::List,
+};
+
+use super::{secret, CalDavAccount};
+
+/// The namespaces used in request bodies. `T` holds the properties only this
+/// app reads, such as the icon of a list.
+const NAMESPACES: &str = concat!(
+    r#"xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav" "#,
+    r#"xmlns:CS="http://calendarserver.org/ns/" xmlns:T="https://github.com/edfloreshz/tasks""#
+);
+
+/// What became of a write that only applies if the resource is unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The write went through, and the server may have sent the new ETag.
+    Done(Option<String>),
+    /// The resource was changed on the server since it was last read.
+    Changed,
+    /// The resource does not exist on the server.
+    Missing,
+}
+
+/// One `response` element of a WebDAV multistatus, with the properties the
+/// provider asks for.
+#[derive(Debug, Clone, Default)]
+pub struct Response {
+    pub href: String,
+    pub status: Option<u16>,
+    pub etag: Option<String>,
+    pub ctag: Option<String>,
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    pub calendar: bool,
+    /// The kinds of component the collection holds, empty when it does not say.
+    pub components: Vec<String>,
+    pub calendar_data: Option<String>,
+    pub principal: Option<String>,
+    pub home: Option<String>,
+}
+
+impl Response {
+    /// Whether this is a calendar that can hold tasks.
+    pub fn holds_tasks(&self) -> bool {
+        self.calendar
+            && (self.components.is_empty()
+                || self
+                    .components
+                    .iter()
+                    .any(|component| component.eq_ignore_ascii_case("VTODO")))
+    }
+}
+
+/// A minimal CalDAV client, speaking only the requests the provider needs.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    url: Url,
+    application_id: String,
+    account: CalDavAccount,
+    /// Read from the keyring on the first request, unless the account
+    /// came with it.
+    password: Arc<OnceLock<String>>,
+}
+
+impl Client {
+    pub fn new(application_id: &str, account: &CalDavAccount) -> Result<Self> {
+        let url = Url::parse(&account.url)
+            .map_err(|err| Error::Corrupt(format!("Invalid address {}: {err}", account.url)))?;
+        let password = OnceLock::new();
+        if !account.password.is_empty() {
+            let _ = password.set(account.password.clone());
+        }
+        Ok(Self {
+            http: reqwest::Client::new(),
+            url,
+            application_id: application_id.to_string(),
+            account: account.clone(),
+            password: Arc::new(password),
+        })
+    }
+
+    async fn password(&self) -> Result<&str> {
+        if let Some(password) = self.password.get() {
+            return Ok(password);
+        }
+        let connection = zbus::Connection::session().await.map_err(Error::Keyring)?;
+        let password = secret::lookup(&connection, &self.application_id, &self.account)
+            .await
+            .map_err(Error::Keyring)?
+            .unwrap_or_default();
+        Ok(self.password.get_or_init(|| password))
+    }
+
+    /// The path of `href`, resolved against the server address, so the
+    /// same resource always has the same href.
+    pub fn path(&self, href: &str) -> String {
+        self.url
+            .join(href)
+            .map(|url| url.path().to_string())
+            .unwrap_or_else(|_| href.to_string())
+    }
+
+    async fn request(&self, method: &str, href: &str) -> Result<RequestBuilder> {
+        let url = self
+            .url
+            .join(href)
+            .map_err(|err| Error::Corrupt(format!("Invalid address {href}: {err}")))?;
+        let method = Method::from_bytes(method.as_bytes()).unwrap_or(Method::GET);
+        Ok(self
+            .http
+            .request(method, url)
+            .basic_auth(&self.account.username, Some(self.password().await?)))
+    }
+
+    async fn multistatus(&self, request: RequestBuilder, body: String) -> Result<Vec<Response>> {
+        let response = request
+            .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+            .body(body)
+            .send()
+            .await?;
+        let url = response.url().to_string();
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Server {
+                status: status.as_u16(),
+                url,
+            });
+        }
+        let text = response.text().await?;
+        let responses = parse_multistatus(&text)?;
+        Ok(responses
+            .into_iter()
+            .map(|mut response| {
+                response.href = self.path(&response.href);
+                response
+            })
+            .collect())
+    }
+
+    async fn propfind(&self, href: &str, depth: &str, props: &str) -> Result<Vec<Response>> {
+        let request = self.request("PROPFIND", href).await?.header("Depth", depth);
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?><D:propfind {NAMESPACES}><D:prop>{props}</D:prop></D:propfind>"#
+        );
+        self.multistatus(request, body).await
+    }
+
+    /// Finds the collection holding the user's calendars, starting from the
+    /// address of the account. Servers that don't support discovery are
+    /// expected to be given that collection directly.
+    pub async fn discover(&self) -> Result<String> {
+        let start = self.url.path().to_string();
+        let principal = self
+            .propfind(&start, "0", "<D:current-user-principal/>")
+            .await?
+            .into_iter()
+            .find_map(|response| response.principal)
+            .map(|principal| self.path(&principal))
+            .unwrap_or_else(|| start.clone());
+        let home = self
+            .propfind(&principal, "0", "<C:calendar-home-set/>")
+            .await?
+            .into_iter()
+            .find_map(|response| response.home)
+            .map(|home| self.path(&home))
+            .unwrap_or(start);
+        Ok(home)
+    }
+
+    /// The collections directly inside `home`, with their names and ctags.
+    pub async fn calendars(&self, home: &str) -> Result<Vec<Response>> {
+        let responses = self
+            .propfind(
+                home,
+                "1",
+                "<D:resourcetype/><D:displayname/><C:calendar-description/>\
+                 <C:supported-calendar-component-set/><CS:getctag/><T:icon/>",
+            )
+            .await?;
+        Ok(responses
+            .into_iter()
+            .filter(|response| response.href != home)
+            .collect())
+    }
+
+    /// The href and ETag of every task in a calendar.
+    pub async fn etags(&self, calendar: &str) -> Result<Vec<(String, Option<String>)>> {
+        let request = self.request("REPORT", calendar).await?.header("Depth", "1");
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?><C:calendar-query {NAMESPACES}><D:prop><D:getetag/></D:prop><C:filter><C:comp-filter name="VCALENDAR"><C:comp-filter name="VTODO"/></C:comp-filter></C:filter></C:calendar-query>"#
+        );
+        Ok(self
+            .multistatus(request, body)
+            .await?
+            .into_iter()
+            .filter(|response| response.status.is_none_or(|status| status < 400))
+            .map(|response| (response.href, response.etag))
+            .collect())
+    }
+
+    /// The content and ETag of each of `hrefs` in a calendar.
+    pub async fn multiget(&self, calendar: &str, hrefs: &[String]) -> Result<Vec<Response>> {
+        let request = self.request("REPORT", calendar).await?.header("Depth", "1");
+        let hrefs: String = hrefs
+            .iter()
+            .map(|href| format!("<D:href>{}</D:href>", escape(href)))
+            .collect();
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?><C:calendar-multiget {NAMESPACES}><D:prop><D:getetag/><C:calendar-data/></D:prop>{hrefs}</C:calendar-multiget>"#
+        );
+        Ok(self
+            .multistatus(request, body)
+            .await?
+            .into_iter()
+            .filter(|response| response.calendar_data.is_some())
+            .collect())
+    }
+
+    /// The current ETag of a single resource.
+    pub async fn etag(&self, href: &str) -> Result<Option<String>> {
+        Ok(self
+            .propfind(href, "0", "<D:getetag/>")
+            .await?
+            .into_iter()
+            .find_map(|response| response.etag))
+    }
+
+    /// Writes a resource, only over the version with `etag` or, without one,
+    /// only if it does not exist yet.
+    pub async fn put(&self, href: &str, ics: String, etag: Option<&str>) -> Result<Outcome> {
+        let request = self
+            .request("PUT", href)
+            .await?
+            .header(header::CONTENT_TYPE, "text/calendar; charset=utf-8");
+        let request = match etag {
+            Some(etag) => request.header(header::IF_MATCH, etag),
+            None => request.header(header::IF_NONE_MATCH, "*"),
+        };
+        let response = request.body(ics).send().await?;
+        let etag = response
+            .headers()
+            .get(header::ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(str::to_string);
+        outcome(response, etag)
+    }
+
+    /// Deletes a resource or collection, only the version with `etag` if given.
+    pub async fn delete(&self, href: &str, etag: Option<&str>) -> Result<Outcome> {
+        let mut request = self.request("DELETE", href).await?;
+        if let Some(etag) = etag {
+            request = request.header(header::IF_MATCH, etag);
+        }
+        outcome(request.send().await?, None)
+    }
+
+    /// Creates a calendar for `list` that holds tasks.
+    pub async fn make_calendar(&self, href: &str, list: &List) -> Result<Outcome> {
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?><C:mkcalendar {NAMESPACES}><D:set><D:prop>{}<C:supported-calendar-component-set><C:comp name="VTODO"/></C:supported-calendar-component-set></D:prop></D:set></C:mkcalendar>"#,
+            list_props(list)
+        );
+        let response = self
+            .request("MKCALENDAR", href)
+            .await?
+            .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+            .body(body)
+            .send()
+            .await?;
+        outcome(response, None)
+    }
+
+    /// Changes the name, description and icon of the calendar of `list`.
+    pub async fn update_calendar(&self, href: &str, list: &List) -> Result<()> {
+        let request = self.request("PROPPATCH", href).await?;
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?><D:propertyupdate {NAMESPACES}><D:set><D:prop>{}</D:prop></D:set></D:propertyupdate>"#,
+            list_props(list)
+        );
+        self.multistatus(request, body).await?;
+        Ok(())
+    }
+}
+
+fn list_props(list: &List) -> String {
+    format!(
+        "<D:displayname>{}</D:displayname><C:calendar-description>{}</C:calendar-description><T:icon>{}</T:icon>",
+        escape(&list.name),
+        escape(&list.description),
+        escape(list.icon.as_deref().unwrap_or_default()),
+    )
+}
+
+fn outcome(response: reqwest::Response, etag: Option<String>) -> Result<Outcome> {
+    match response.status() {
+        status if status.is_success() => Ok(Outcome::Done(etag)),
+        StatusCode::PRECONDITION_FAILED => Ok(Outcome::Changed),
+        StatusCode::NOT_FOUND | StatusCode::GONE => Ok(Outcome::Missing),
+        status => Err(Error::Server {
+            status: status.as_u16(),
+            url: response.url().to_string(),
+        }),
+    }
+}
+
+/// Reads the responses of a WebDAV multistatus body.
+fn parse_multistatus(xml: &str) -> Result<Vec<Response>> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+    let mut responses = vec![];
+    let mut response = Response::default();
+    // Local names of the open elements, namespaces aside.
+    let mut path: Vec<String> = vec![];
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).to_string();
+                start(&mut response, &path, &name, &element)?;
+                if name == "response" {
+                    response = Response::default();
+                }
+                path.push(name);
+            }
+            Event::Empty(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).to_string();
+                start(&mut response, &path, &name, &element)?;
+            }
+            Event::End(_) => {
+                let name = path.pop();
+                if name.as_deref() == Some("response") {
+                    responses.push(std::mem::take(&mut response));
+                }
+            }
+            Event::Text(text) => text_in(&mut response, &path, text.unescape()?.into_owned()),
+            Event::CData(data) => {
+                let text = String::from_utf8_lossy(&data.into_inner()).to_string();
+                text_in(&mut response, &path, text);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(responses)
+}
+
+fn start(
+    response: &mut Response,
+    path: &[String],
+    name: &str,
+    element: &quick_xml::events::BytesStart,
+) -> Result<()> {
+    let parent = path.last().map(String::as_str);
+    match (parent, name) {
+        (Some("resourcetype"), "calendar") => response.calendar = true,
+        (Some("supported-calendar-component-set"), "comp") => {
+            let component = element
+                .attributes()
+                .flatten()
+                .find(|attribute| attribute.key.local_name().as_ref() == b"name");
+            if let Some(component) = component {
+                response
+                    .components
+                    .push(component.unescape_value()?.into_owned());
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn text_in(response: &mut Response, path: &[String], text: String) {
+    let mut names = path.iter().rev().map(String::as_str);
+    let (Some(name), parent) = (names.next(), names.next()) else {
+        return;
+    };
+    match (parent, name) {
+        (Some("response"), "href") => response.href = text,
+        (Some("response"), "status") => response.status = status_code(&text),
+        (Some("current-user-principal"), "href") => response.principal = Some(text),
+        (Some("calendar-home-set"), "href") => response.home = Some(text),
+        (_, "getetag") => response.etag = Some(text),
+        (_, "getctag") => response.ctag = Some(text),
+        (_, "displayname") => response.display_name = Some(text),
+        (_, "calendar-description") => response.description = Some(text),
+        (_, "icon") => response.icon = Some(text),
+        (_, "calendar-data") => response.calendar_data = Some(text),
+        _ => {}
+    }
+}
+
+/// The code of a status line such as `HTTP/1.1 404 Not Found`.
+fn status_code(line: &str) -> Option<u16> {
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_multistatus() -> Result<()> {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+            <multistatus xmlns="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav"
+                xmlns:CS="http://calendarserver.org/ns/">
+              <response>
+                <href>/dav/user/</href>
+                <propstat>
+                  <prop>
+                    <current-user-principal><href>/dav/principals/user/</href></current-user-principal>
+                    <C:calendar-home-set><href>/dav/calendars/user/</href></C:calendar-home-set>
+                  </prop>
+                  <status>HTTP/1.1 200 OK</status>
+                </propstat>
+              </response>
+              <response>
+                <href>/dav/calendars/user/work/</href>
+                <propstat>
+                  <prop>
+                    <resourcetype><collection/><C:calendar/></resourcetype>
+                    <displayname>Work &amp; chores</displayname>
+                    <C:supported-calendar-component-set>
+                      <C:comp name="VEVENT"/><C:comp name="VTODO"/>
+                    </C:supported-calendar-component-set>
+                    <CS:getctag>"ctag-1"</CS:getctag>
+                  </prop>
+                </propstat>
+              </response>
+              <response>
+                <href>/dav/calendars/user/work/task.ics</href>
+                <propstat>
+                  <prop>
+                    <getetag>"etag-1"</getetag>
+                    <C:calendar-data><![CDATA[BEGIN:VCALENDAR
+END:VCALENDAR]]></C:calendar-data>
+                  </prop>
+                </propstat>
+              </response>
+              <response>
+                <href>/dav/calendars/user/work/gone.ics</href>
+                <status>HTTP/1.1 404 Not Found</status>
+              </response>
+            </multistatus>"#;
+        let responses = parse_multistatus(xml)?;
+        assert_eq!(responses.len(), 4);
+
+        let principal = &responses[0];
+        assert_eq!(principal.href, "/dav/user/");
+        assert_eq!(principal.status, None);
+        assert_eq!(
+            principal.principal.as_deref(),
+            Some("/dav/principals/user/")
+        );
+        assert_eq!(principal.home.as_deref(), Some("/dav/calendars/user/"));
+        assert!(!principal.holds_tasks());
+
+        let calendar = &responses[1];
+        assert!(calendar.holds_tasks());
+        assert_eq!(calendar.display_name.as_deref(), Some("Work & chores"));
+        assert_eq!(calendar.components, ["VEVENT", "VTODO"]);
+        assert_eq!(calendar.ctag.as_deref(), Some("\"ctag-1\""));
+
+        let task = &responses[2];
+        assert_eq!(task.etag.as_deref(), Some("\"etag-1\""));
+        assert_eq!(
+            task.calendar_data.as_deref(),
+            Some("BEGIN:VCALENDAR\nEND:VCALENDAR")
+        );
+
+        assert_eq!(responses[3].status, Some(404));
+        Ok(())
+    }
+
+    #[test]
+    fn calendars_without_vtodo_hold_no_tasks() {
+        let events = Response {
+            calendar: true,
+            components: vec!["VEVENT".to_string()],
+            ..Default::default()
+        };
+        assert!(!events.holds_tasks());
+        let any = Response {
+            calendar: true,
+            ..Default::default()
+        };
+        assert!(any.holds_tasks());
+    }
+}
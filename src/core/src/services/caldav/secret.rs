@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use zbus::{
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Type, Value},
+    Connection,
+};
+
+use super::CalDavAccount;
+
+/// The default collection, usually the login keyring.
+const DEFAULT_COLLECTION: &str = "/org/freedesktop/secrets/aliases/default";
+
+/// Secrets are sent as they are, which only the session bus gets to see.
+const PLAIN: &str = "plain";
+
+/// A secret as the Secret Service sends it.
+#[derive(Debug, Serialize, Deserialize, Type)]
+struct Secret {
+    session: OwnedObjectPath,
+    parameters: Vec<u8>,
+    value: Vec<u8>,
+    content_type: String,
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Secret.Service",
+    default_service = "org.freedesktop.secrets",
+    default_path = "/org/freedesktop/secrets"
+)]
+trait Service {
+    fn open_session(
+        &self,
+        algorithm: &str,
+        input: &Value<'_>,
+    ) -> zbus::Result<(OwnedValue, OwnedObjectPath)>;
+
+    fn search_items(
+        &self,
+        attributes: HashMap<&str, &str>,
+    ) -> zbus::Result<(Vec<OwnedObjectPath>, Vec<OwnedObjectPath>)>;
+
+    fn unlock(
+        &self,
+        objects: &[ObjectPath<'_>],
+    ) -> zbus::Result<(Vec<OwnedObjectPath>, OwnedObjectPath)>;
+
+    fn get_secrets(
+        &self,
+        items: &[ObjectPath<'_>],
+        session: &ObjectPath<'_>,
+    ) -> zbus::Result<HashMap<OwnedObjectPath, Secret>>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Secret.Collection",
+    default_service = "org.freedesktop.secrets"
+)]
+trait Collection {
+    fn create_item(
+        &self,
+        properties: HashMap<&str, Value<'_>>,
+        secret: &Secret,
+        replace: bool,
+    ) -> zbus::Result<(OwnedObjectPath, OwnedObjectPath)>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Secret.Prompt",
+    default_service = "org.freedesktop.secrets"
+)]
+trait Prompt {
+    fn prompt(&self, window_id: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn completed(&self, dismissed: bool, result: OwnedValue) -> zbus::Result<()>;
+}
+
+/// What the password of an account is stored under.
+fn attributes<'a>(
+    application_id: &'a str,
+    account: &'a CalDavAccount,
+) -> HashMap<&'a str, &'a str> {
+    HashMap::from([
+        ("application", application_id),
+        ("url", account.url.as_str()),
+        ("username", account.username.as_str()),
+    ])
+}
+
+/// Stores the password of an account in the default keyring, over the one
+/// stored before.
+pub async fn store(
+    connection: &Connection,
+    application_id: &str,
+    account: &CalDavAccount,
+) -> zbus::Result<()> {
+    let service = ServiceProxy::new(connection).await?;
+    let (_, session) = service.open_session(PLAIN, &Value::from("")).await?;
+    let collection = CollectionProxy::builder(connection)
+        .path(DEFAULT_COLLECTION)?
+        .build()
+        .await?;
+    let properties = HashMap::from([
+        (
+            "org.freedesktop.Secret.Item.Label",
+            Value::from(format!("CalDAV password for {}", account.username)),
+        ),
+        (
+            "org.freedesktop.Secret.Item.Attributes",
+            Value::from(attributes(application_id, account)),
+        ),
+    ]);
+    let secret = Secret {
+        session,
+        parameters: vec![],
+        value: account.password.as_bytes().to_vec(),
+        content_type: "text/plain".to_string(),
+    };
+    let (item, prompt) = collection.create_item(properties, &secret, true).await?;
+    // A locked keyring has to be unlocked by the user first.
+    if item.as_str() == "/" && !confirm(connection, prompt).await? {
+        return Err(zbus::Error::Failure(
+            "the keyring stayed locked".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// The password stored for an account, if any.
+pub async fn lookup(
+    connection: &Connection,
+    application_id: &str,
+    account: &CalDavAccount,
+) -> zbus::Result<Option<String>> {
+    let service = ServiceProxy::new(connection).await?;
+    let attributes = attributes(application_id, account);
+    let (mut items, locked) = service.search_items(attributes.clone()).await?;
+    if items.is_empty() && !locked.is_empty() {
+        let locked: Vec<_> = locked.iter().map(|item| item.as_ref()).collect();
+        let (_, prompt) = service.unlock(&locked).await?;
+        if confirm(connection, prompt).await? {
+            items = service.search_items(attributes).await?.0;
+        }
+    }
+    let Some(item) = items.into_iter().next() else {
+        return Ok(None);
+    };
+    let (_, session) = service.open_session(PLAIN, &Value::from("")).await?;
+    let mut secrets = service
+        .get_secrets(&[item.as_ref()], &session.as_ref())
+        .await?;
+    let Some(secret) = secrets.remove(&item) else {
+        return Ok(None);
+    };
+    String::from_utf8(secret.value)
+        .map(Some)
+        .map_err(|err| zbus::Error::Failure(err.to_string()))
+}
+
+/// Shows a prompt of the Secret Service, such as the one unlocking the
+/// keyring, and waits for the user. Whether it was not dismissed.
+async fn confirm(connection: &Connection, prompt: OwnedObjectPath) -> zbus::Result<bool> {
+    if prompt.as_str() == "/" {
+        return Ok(true);
+    }
+    let prompt = PromptProxy::builder(connection)
+        .path(prompt)?
+        .build()
+        .await?;
+    let mut completed = prompt.receive_completed().await?;
+    prompt.prompt("").await?;
+    let Some(signal) = completed.next().await else {
+        return Ok(false);
+    };
+    Ok(!signal.args()?.dismissed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::testing;
+
+    const SERVICE: &str = "/org/freedesktop/secrets";
+
+    /// The stored items, each with its attributes and secret.
+    type Items = Arc<Mutex<Vec<(HashMap<String, String>, Vec<u8>)>>>;
+
+    fn item(index: usize) -> OwnedObjectPath {
+        ObjectPath::try_from(format!("{SERVICE}/collection/login/{index}"))
+            .unwrap()
+            .into()
+    }
+
+    /// A keyring that is always unlocked.
+    struct StubService {
+        items: Items,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Secret.Service")]
+    impl StubService {
+        fn open_session(
+            &self,
+            _algorithm: String,
+            _input: OwnedValue,
+        ) -> zbus::fdo::Result<(OwnedValue, OwnedObjectPath)> {
+            let output = Value::from("").try_to_owned().map_err(zbus::Error::from)?;
+            let session =
+                ObjectPath::try_from(format!("{SERVICE}/session/1")).map_err(zbus::Error::from)?;
+            Ok((output, session.into()))
+        }
+
+        fn search_items(
+            &self,
+            attributes: HashMap<String, String>,
+        ) -> (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) {
+            let items = self.items.lock().unwrap();
+            let found = (0..items.len())
+                .filter(|index| items[*index].0 == attributes)
+                .map(item)
+                .collect();
+            (found, vec![])
+        }
+
+        fn get_secrets(
+            &self,
+            paths: Vec<OwnedObjectPath>,
+            session: OwnedObjectPath,
+        ) -> HashMap<OwnedObjectPath, Secret> {
+            let items = self.items.lock().unwrap();
+            (0..items.len())
+                .map(item)
+                .zip(items.iter())
+                .filter(|(path, _)| paths.contains(path))
+                .map(|(path, (_, value))| {
+                    let secret = Secret {
+                        session: session.clone(),
+                        parameters: vec![],
+                        value: value.clone(),
+                        content_type: "text/plain".to_string(),
+                    };
+                    (path, secret)
+                })
+                .collect()
+        }
+    }
+
+    struct StubCollection {
+        items: Items,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Secret.Collection")]
+    impl StubCollection {
+        fn create_item(
+            &self,
+            properties: HashMap<String, OwnedValue>,
+            secret: Secret,
+            replace: bool,
+        ) -> zbus::fdo::Result<(OwnedObjectPath, OwnedObjectPath)> {
+            let attributes = properties
+                .get("org.freedesktop.Secret.Item.Attributes")
+                .ok_or_else(|| zbus::fdo::Error::InvalidArgs("no attributes".to_string()))?;
+            let attributes = HashMap::<String, String>::try_from(
+                attributes.try_clone().map_err(zbus::Error::from)?,
+            )
+            .map_err(zbus::Error::from)?;
+            let mut items = self.items.lock().unwrap();
+            if replace {
+                items.retain(|(existing, _)| *existing != attributes);
+            }
+            items.push((attributes, secret.value));
+            let prompt = ObjectPath::try_from("/").map_err(zbus::Error::from)?;
+            Ok((item(items.len() - 1), prompt.into()))
+        }
+    }
+
+    #[tokio::test]
+    async fn stores_passwords_by_account() -> zbus::Result<()> {
+        let (keyring, client) = testing::peers().await?;
+        let items = Items::default();
+        let server = keyring.object_server();
+        server
+            .at(
+                SERVICE,
+                StubService {
+                    items: items.clone(),
+                },
+            )
+            .await?;
+        server
+            .at(
+                DEFAULT_COLLECTION,
+                StubCollection {
+                    items: items.clone(),
+                },
+            )
+            .await?;
+
+        let mut account = CalDavAccount {
+            url: "https://example.com/dav/".to_string(),
+            username: "ana".to_string(),
+            password: "first".to_string(),
+        };
+        assert_eq!(lookup(&client, "app", &account).await?, None);
+        store(&client, "app", &account).await?;
+        account.password = "second".to_string();
+        store(&client, "app", &account).await?;
+        assert_eq!(items.lock().unwrap().len(), 1);
+        assert_eq!(
+            lookup(&client, "app", &account).await?.as_deref(),
+            Some("second")
+        );
+
+        let other = CalDavAccount {
+            username: "bo".to_string(),
+            ..account
+        };
+        assert_eq!(lookup(&client, "app", &other).await?, None);
+        assert_eq!(lookup(&client, "other.app", &other).await?, None);
+        Ok(())
+    }
+}
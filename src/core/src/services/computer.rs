@@ -1,8 +1,9 @@
-use anyhow::Result;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 
 use crate::{
-    models::{list::List, task::Task},
+    error::Result,
+    models::{diagnostic::Diagnostic, list::List, query::TaskQuery, task::Task},
     task_service::TasksProvider,
 };
 
@@ -39,8 +40,24 @@ impl TasksProvider for ComputerStorage {
         self.engine.update_task(task)
     }
 
-    async fn delete_task(&mut self, list_id: String, task_id: String) -> Result<()> {
-        self.engine.delete_task(&list_id, &task_id)
+    async fn update_task_if_unchanged(
+        &mut self,
+        task: Task,
+        expected: DateTime<Utc>,
+    ) -> Result<()> {
+        self.engine.update_task_if_unchanged(task, expected)
+    }
+
+    async fn get_deleted_tasks(&mut self) -> Result<Vec<Task>> {
+        self.engine.deleted_tasks()
+    }
+
+    async fn query(&mut self, query: &TaskQuery) -> Result<Vec<Task>> {
+        self.engine.query(query)
+    }
+
+    async fn purge_task(&mut self, list_id: String, task_id: String) -> Result<()> {
+        self.engine.purge_task(&list_id, &task_id)
     }
 
     async fn get_lists(&mut self) -> Result<Vec<List>> {
@@ -62,4 +79,12 @@ impl TasksProvider for ComputerStorage {
     async fn delete_list(&mut self, id: String) -> Result<()> {
         self.engine.delete_list(&id)
     }
+
+    async fn diagnostics(&mut self) -> Result<Vec<Diagnostic>> {
+        self.engine.diagnostics()
+    }
+
+    async fn acknowledge_diagnostics(&mut self, diagnostics: &[Diagnostic]) -> Result<()> {
+        self.engine.acknowledge_diagnostics(diagnostics)
+    }
 }
@@ -1,6 +1,22 @@
-use std::path::PathBuf;
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
 
-use crate::models::{list::List, task::Task};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    conflict::Conflict,
+    error::{Error, Result},
+    models::{diagnostic::Diagnostic, list::List, query::TaskQuery, task::Task},
+    services::files::{self, write_atomic, TEMP_EXTENSION},
+};
+
+/// The data directories this process already cleaned up. An engine is created
+/// for every operation, so only the first one in each directory does it.
+static RECOVERED: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
 
 #[derive(Debug, Clone)]
 pub struct ComputerStorageEngine {
@@ -20,42 +36,199 @@ impl ComputerStorageEngine {
         if !engine.tasks_path().exists() {
             std::fs::create_dir_all(&engine.tasks_path()).ok()?;
         }
+        let first = RECOVERED
+            .lock()
+            .is_ok_and(|mut recovered| recovered.insert(engine.path.clone()));
+        if first {
+            if let Err(err) = engine.recover() {
+                tracing::warn!("failed to clean up temporary files: {err}");
+            }
+        }
         Some(engine)
     }
 
-    pub fn tasks(&self, list_id: &str) -> anyhow::Result<Vec<Task>> {
-        let mut tasks = vec![];
-        let path = self.tasks_path().join(list_id);
-        if !path.exists() {
-            return Ok(tasks);
+    /// Removes temporary files left behind by writes that were interrupted
+    /// before they could be renamed over the live file. Writes still under way
+    /// in other processes hold the lock, so their files are left alone.
+    pub fn recover(&self) -> Result<()> {
+        let _lock = files::lock(&self.path)?;
+        let mut dirs = vec![self.lists_path()];
+        for entry in self.tasks_path().read_dir()? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            }
         }
-        for entry in path.read_dir()? {
-            let entry = entry?;
-            let path = entry.path();
-            let content = std::fs::read_to_string(&path)?;
-            let task = ron::from_str(&content)?;
-            tasks.push(task);
+        for dir in dirs {
+            for entry in dir.read_dir()? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|ext| ext == TEMP_EXTENSION) {
+                    tracing::info!("removing orphaned temporary file {}", path.display());
+                    std::fs::remove_file(&path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn tasks(&self, list_id: &str) -> Result<Vec<Task>> {
+        let tasks: Vec<Task> = self.records(&self.tasks_path().join(list_id))?;
+        Ok(tasks
+            .into_iter()
+            .filter(|task| task.deletion_date.is_none())
+            .collect())
+    }
+
+    pub fn deleted_tasks(&self) -> Result<Vec<Task>> {
+        let mut tasks = vec![];
+        for entry in self.tasks_path().read_dir()? {
+            let path = entry?.path();
+            if path.is_dir() {
+                let records: Vec<Task> = self.records(&path)?;
+                tasks.extend(
+                    records
+                        .into_iter()
+                        .filter(|task| task.deletion_date.is_some()),
+                );
+            }
         }
         Ok(tasks)
     }
 
-    pub fn lists(&self) -> anyhow::Result<Vec<List>> {
+    /// Reads the tasks matching `query` in a single pass over the task directories.
+    pub fn query(&self, query: &TaskQuery) -> Result<Vec<Task>> {
+        let dirs = match &query.list {
+            Some(list) => vec![self.tasks_path().join(list)],
+            None => self
+                .tasks_path()
+                .read_dir()?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<Vec<_>>>()?
+                .into_iter()
+                .filter(|path| path.is_dir())
+                .collect(),
+        };
         let mut tasks = vec![];
-        let path = self.lists_path();
-        if !path.exists() {
-            return Ok(tasks);
+        for dir in dirs {
+            let records: Vec<Task> = self.records(&dir)?;
+            for task in records {
+                if query.matches(&task) {
+                    tasks.push(task);
+                    if query.is_full(tasks.len()) {
+                        return Ok(query.finish(tasks));
+                    }
+                }
+            }
+        }
+        Ok(query.finish(tasks))
+    }
+
+    pub fn lists(&self) -> Result<Vec<List>> {
+        self.records(&self.lists_path())
+    }
+
+    /// Lists the files that were moved to quarantine because they could not be
+    /// read, and that nobody acknowledged yet.
+    pub fn diagnostics(&self) -> Result<Vec<Diagnostic>> {
+        let mut diagnostics = vec![];
+        let mut dirs = vec![self.quarantine_path()];
+        while let Some(dir) = dirs.pop() {
+            if !dir.exists() {
+                continue;
+            }
+            for entry in dir.read_dir()? {
+                let path = entry?.path();
+                if path == self.acknowledged_path() {
+                    continue;
+                }
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                let message = match std::fs::read_to_string(&path) {
+                    Ok(content) => match ron::from_str::<ron::Value>(&content) {
+                        Ok(_) => "File does not match the expected format".to_string(),
+                        Err(err) => err.to_string(),
+                    },
+                    Err(err) => err.to_string(),
+                };
+                diagnostics.push(Diagnostic { path, message });
+            }
+        }
+        Ok(diagnostics)
+    }
+
+    /// Moves quarantined files into the acknowledged directory, keeping them
+    /// around for recovery without reporting them again.
+    pub fn acknowledge_diagnostics(&self, diagnostics: &[Diagnostic]) -> Result<()> {
+        for diagnostic in diagnostics {
+            let Ok(relative) = diagnostic.path.strip_prefix(self.quarantine_path()) else {
+                continue;
+            };
+            if !diagnostic.path.exists() {
+                continue;
+            }
+            let destination = self.acknowledged_path().join(relative);
+            if let Some(parent) = destination.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::rename(&diagnostic.path, destination)?;
         }
-        for entry in self.lists_path().read_dir()? {
+        Ok(())
+    }
+
+    /// Reads every record in `dir`, moving the ones that cannot be parsed to
+    /// quarantine. Files that cannot be read at all are left in place, as
+    /// that may only last until their permissions are fixed.
+    fn records<T: DeserializeOwned>(&self, dir: &Path) -> Result<Vec<T>> {
+        let mut records = vec![];
+        if !dir.exists() {
+            return Ok(records);
+        }
+        for entry in dir.read_dir()? {
             let entry = entry?;
             let path = entry.path();
-            let content = std::fs::read_to_string(&path)?;
-            let task = ron::from_str(&content)?;
-            tasks.push(task);
+            if !is_record(&path) {
+                continue;
+            }
+            let content = match std::fs::read_to_string(&path) {
+                Ok(content) => content,
+                // Deleted by another process since the directory was read.
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            };
+            match ron::from_str(&content) {
+                Ok(record) => records.push(record),
+                Err(err) => {
+                    tracing::warn!("failed to read {}: {err}", path.display());
+                    if let Err(err) = self.quarantine(&path) {
+                        tracing::error!("failed to quarantine {}: {err}", path.display());
+                    }
+                }
+            }
         }
-        Ok(tasks)
+        Ok(records)
+    }
+
+    /// Moves a file into the quarantine directory, keeping its location
+    /// relative to the data directory.
+    fn quarantine(&self, path: &Path) -> Result<PathBuf> {
+        let relative = path
+            .strip_prefix(&self.path)
+            .map_err(std::io::Error::other)?;
+        let mut destination = self.quarantine_path().join(relative);
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if destination.exists() {
+            let timestamp = Utc::now().format("%Y%m%d%H%M%S%f");
+            destination = destination.with_extension(format!("{timestamp}.ron"));
+        }
+        std::fs::rename(path, &destination)?;
+        Ok(destination)
     }
 
-    pub fn get_task(&self, list_id: &str, task_id: &str) -> anyhow::Result<Task> {
+    pub fn get_task(&self, list_id: &str, task_id: &str) -> Result<Task> {
         let path = self
             .tasks_path()
             .join(list_id)
@@ -66,97 +239,128 @@ impl ComputerStorageEngine {
             let task = ron::from_str(&content)?;
             Ok(task)
         } else {
-            Err(anyhow::anyhow!("Task does not exist"))
+            Err(Error::TaskNotFound(task_id.to_string()))
         }
     }
 
-    pub fn create_task(&self, task: Task) -> anyhow::Result<Task> {
+    pub fn create_task(&self, mut task: Task) -> Result<Task> {
         let path = self
             .tasks_path()
             .join(&task.parent)
             .join(&task.id)
             .with_extension("ron");
+        let _lock = files::lock(&self.path)?;
         if !path.exists() {
             std::fs::create_dir_all(&self.tasks_path().join(&task.parent))?;
+            task.prepare_write(None);
             let content = ron::to_string(&task)?;
-            std::fs::write(path, content)?;
+            write_atomic(&path, content.as_bytes())?;
             Ok(task)
         } else {
-            Err(anyhow::anyhow!("Task already exists"))
+            Err(Error::AlreadyExists(task.id))
         }
     }
 
-    pub fn update_task(&self, task: Task) -> anyhow::Result<()> {
+    pub fn update_task(&self, mut task: Task) -> Result<()> {
         let path = self
             .tasks_path()
             .join(&task.parent)
             .join(&task.id)
             .with_extension("ron");
+        let _lock = files::lock(&self.path)?;
         if path.exists() {
+            let stored = self.get_task(&task.parent, &task.id)?;
+            task.prepare_write(Some(&stored));
             let content = ron::to_string(&task)?;
-            std::fs::write(path, content)?;
+            write_atomic(&path, content.as_bytes())?;
             Ok(())
         } else {
-            Err(anyhow::anyhow!("Task does not exist"))
+            Err(Error::TaskNotFound(task.id))
         }
     }
 
-    pub fn delete_task(&self, list_id: &str, task_id: &str) -> anyhow::Result<()> {
+    /// Writes `task` unless the stored copy was modified at another time than
+    /// `expected`. The data directory stays locked from the read to the write,
+    /// so another process cannot slip a change in between.
+    pub fn update_task_if_unchanged(&self, mut task: Task, expected: DateTime<Utc>) -> Result<()> {
+        let path = self
+            .tasks_path()
+            .join(&task.parent)
+            .join(&task.id)
+            .with_extension("ron");
+        let _lock = files::lock(&self.path)?;
+        let stored = self.get_task(&task.parent, &task.id)?;
+        if stored.last_modified_date_time != expected {
+            return Err(Error::Conflict(Box::new(Conflict {
+                ours: task,
+                theirs: stored,
+            })));
+        }
+        task.prepare_write(Some(&stored));
+        let content = ron::to_string(&task)?;
+        write_atomic(&path, content.as_bytes())
+    }
+
+    pub fn purge_task(&self, list_id: &str, task_id: &str) -> Result<()> {
         let path = self
             .tasks_path()
             .join(list_id)
             .join(task_id)
             .with_extension("ron");
+        let _lock = files::lock(&self.path)?;
         if path.exists() {
             std::fs::remove_file(path)?;
             Ok(())
         } else {
-            Err(anyhow::anyhow!("Task does not exist"))
+            Err(Error::TaskNotFound(task_id.to_string()))
         }
     }
 
-    pub fn get_list(&self, list_id: &str) -> anyhow::Result<List> {
+    pub fn get_list(&self, list_id: &str) -> Result<List> {
         let path = self.lists_path().join(list_id).with_extension("ron");
         if path.exists() {
             let content = std::fs::read_to_string(path)?;
             let list = ron::from_str(&content)?;
             Ok(list)
         } else {
-            Err(anyhow::anyhow!("List does not exist"))
+            Err(Error::ListNotFound(list_id.to_string()))
         }
     }
 
-    pub fn create_list(&self, list: List) -> anyhow::Result<List> {
+    pub fn create_list(&self, list: List) -> Result<List> {
         let path = self.lists_path().join(&list.id).with_extension("ron");
+        let _lock = files::lock(&self.path)?;
         if !path.exists() {
             let content = ron::to_string(&list)?;
-            std::fs::write(path, content).unwrap();
+            write_atomic(&path, content.as_bytes())?;
             Ok(list)
         } else {
-            Err(anyhow::anyhow!("List already exists"))
+            Err(Error::AlreadyExists(list.id))
         }
     }
 
-    pub fn update_list(&self, list: List) -> anyhow::Result<()> {
+    pub fn update_list(&self, list: List) -> Result<()> {
         let path = self.lists_path().join(&list.id).with_extension("ron");
+        let _lock = files::lock(&self.path)?;
         if path.exists() {
             let content = ron::to_string(&list)?;
-            std::fs::write(path, content)?;
+            write_atomic(&path, content.as_bytes())?;
             Ok(())
         } else {
-            Err(anyhow::anyhow!("List does not exist"))
+            Err(Error::ListNotFound(list.id))
         }
     }
 
-    pub fn delete_list(&self, list_id: &str) -> anyhow::Result<()> {
+    pub fn delete_list(&self, list_id: &str) -> Result<()> {
         let path = self.lists_path().join(list_id).with_extension("ron");
         let tasks = self.tasks_path().join(list_id);
+        let _lock = files::lock(&self.path)?;
         if path.exists() {
             std::fs::remove_file(path)?;
             std::fs::remove_dir_all(tasks)?;
             Ok(())
         } else {
-            Err(anyhow::anyhow!("List does not exist"))
+            Err(Error::ListNotFound(list_id.to_string()))
         }
     }
 
@@ -167,4 +371,17 @@ impl ComputerStorageEngine {
     pub fn tasks_path(&self) -> PathBuf {
         self.path.join("tasks")
     }
+
+    pub fn quarantine_path(&self) -> PathBuf {
+        self.path.join("quarantine")
+    }
+
+    /// Where quarantined files go once the user has been told about them.
+    pub fn acknowledged_path(&self) -> PathBuf {
+        self.quarantine_path().join("acknowledged")
+    }
+}
+
+fn is_record(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|ext| ext == "ron")
 }
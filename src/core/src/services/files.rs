@@ -0,0 +1,108 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use crate::error::Result;
+
+/// The extension of files written by [`write_atomic`] before they are renamed
+/// over the live file.
+pub(crate) const TEMP_EXTENSION: &str = "tmp";
+
+const LOCK_FILE: &str = ".lock";
+
+/// The files [`write_atomic`] wrote in this process, each with the
+/// modification time it left them with.
+static WRITTEN: Mutex<BTreeMap<PathBuf, SystemTime>> = Mutex::new(BTreeMap::new());
+
+/// Takes an exclusive lock on `dir`, shared with every other process using it,
+/// until the returned file is dropped.
+pub(crate) fn lock(dir: &Path) -> Result<File> {
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(dir.join(LOCK_FILE))?;
+    file.lock()?;
+    Ok(file)
+}
+
+/// Writes `content` next to `path` in a temporary file, flushes it to disk and
+/// renames it over `path`, so readers only ever see the old or the new file.
+pub(crate) fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let parent = path.parent().ok_or_else(|| invalid_path(path))?;
+    let file_name = path.file_name().ok_or_else(|| invalid_path(path))?;
+    let temp_path = parent
+        .join(format!(".{}", file_name.to_string_lossy()))
+        .with_extension(TEMP_EXTENSION);
+
+    let result = (|| {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        // Noted before the rename, which a watcher may see right away.
+        let modified = file.metadata()?.modified()?;
+        if let Ok(mut written) = WRITTEN.lock() {
+            written.insert(path.to_path_buf(), modified);
+        }
+        std::fs::rename(&temp_path, path)?;
+        File::open(parent)?.sync_all()
+    })();
+
+    if result.is_err() && temp_path.exists() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    Ok(result?)
+}
+
+/// Whether the file at `path` is still as [`write_atomic`] left it in this
+/// process, so a change to it was this process' own.
+pub(crate) fn is_own_write(path: &Path) -> bool {
+    let Ok(modified) = std::fs::metadata(path).and_then(|metadata| metadata.modified()) else {
+        return false;
+    };
+    WRITTEN
+        .lock()
+        .is_ok_and(|written| written.get(path) == Some(&modified))
+}
+
+fn invalid_path(path: &Path) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("Invalid path: {}", path.display()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn tells_own_writes_apart() -> Result<()> {
+        let directory = std::env::temp_dir().join(format!(
+            "tasks-core-files-{}",
+            uuid::Uuid::new_v4().simple()
+        ));
+        std::fs::create_dir_all(&directory)?;
+        let path = directory.join("list.ron");
+        assert!(!is_own_write(&path));
+
+        write_atomic(&path, b"()")?;
+        assert!(is_own_write(&path));
+        assert!(!directory.join(".list.tmp").exists());
+
+        // Another process writing the file afterwards.
+        let file = File::options().write(true).open(&path)?;
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1))?;
+        assert!(!is_own_write(&path));
+
+        std::fs::remove_dir_all(directory)?;
+        Ok(())
+    }
+}
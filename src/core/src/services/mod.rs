@@ -1 +1,5 @@
+pub mod caldav;
 pub mod computer;
+pub(crate) mod files;
+pub mod sqlite;
+pub mod todo_txt;
@@ -0,0 +1,527 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions, SqliteRow},
+    Row, SqlitePool,
+};
+
+use crate::{
+    conflict::Conflict,
+    error::{Error, Result},
+    models::{list::List, priority::Priority, status::Status, task::Task},
+    task_service::TasksProvider,
+};
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS lists (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    description TEXT NOT NULL,
+    icon TEXT,
+    position INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS tasks (
+    id TEXT PRIMARY KEY NOT NULL,
+    parent TEXT NOT NULL,
+    title TEXT NOT NULL,
+    favorite INTEGER NOT NULL,
+    today INTEGER NOT NULL,
+    status INTEGER NOT NULL,
+    priority INTEGER NOT NULL,
+    notes TEXT NOT NULL,
+    completion_date TEXT,
+    due_date TEXT,
+    reminder_date TEXT,
+    recurrence TEXT NOT NULL,
+    deletion_date TEXT,
+    created_date_time TEXT NOT NULL,
+    last_modified_date_time TEXT NOT NULL,
+    position INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS subtasks (
+    task_id TEXT NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    subtask_id TEXT NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (task_id, subtask_id)
+);
+
+CREATE TABLE IF NOT EXISTS tags (
+    task_id TEXT NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (task_id, tag)
+);
+
+CREATE INDEX IF NOT EXISTS tasks_parent ON tasks (parent);
+CREATE INDEX IF NOT EXISTS tasks_due_date ON tasks (due_date);
+CREATE INDEX IF NOT EXISTS subtasks_subtask_id ON subtasks (subtask_id);
+CREATE INDEX IF NOT EXISTS tags_tag ON tags (tag);
+"#;
+
+/// Selects the ids of a set of top level tasks and all of their sub-tasks.
+/// The statement using it must provide the `roots(id)` table.
+const TREE: &str = r#"
+tree(id) AS (
+    SELECT id FROM roots
+    UNION ALL
+    SELECT subtasks.subtask_id FROM subtasks JOIN tree ON subtasks.task_id = tree.id
+)
+"#;
+
+#[derive(Debug)]
+pub struct SqliteStorage {
+    path: PathBuf,
+    pool: Option<SqlitePool>,
+}
+
+impl SqliteStorage {
+    pub(crate) fn new(application_id: &str) -> Option<Self> {
+        let directory = dirs::data_local_dir()?.join(application_id);
+        if !directory.exists() {
+            std::fs::create_dir_all(&directory).ok()?;
+        }
+        Some(Self {
+            path: directory.join("tasks.db"),
+            pool: None,
+        })
+    }
+
+    /// Opens the database on first use and makes sure the schema exists.
+    async fn pool(&mut self) -> Result<SqlitePool> {
+        if let Some(pool) = &self.pool {
+            return Ok(pool.clone());
+        }
+        let options = SqliteConnectOptions::new()
+            .filename(&self.path)
+            .create_if_missing(true)
+            .foreign_keys(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+        sqlx::raw_sql(SCHEMA).execute(&pool).await?;
+        self.pool = Some(pool.clone());
+        Ok(pool)
+    }
+
+    /// Loads the tasks selected by `roots`, a query returning top level task ids
+    /// that takes `parameters`, together with their tags and nested sub-tasks.
+    async fn load_tasks(&mut self, roots: &str, parameters: &[&str]) -> Result<Vec<Task>> {
+        let pool = self.pool().await?;
+
+        let sql = format!(
+            "WITH RECURSIVE roots(id) AS ({roots}), {TREE} \
+             SELECT tasks.* FROM tasks JOIN tree ON tasks.id = tree.id"
+        );
+        let mut query = sqlx::query(&sql);
+        for parameter in parameters {
+            query = query.bind(*parameter);
+        }
+        let mut tasks: HashMap<String, (Task, i64)> = query
+            .try_map(|row: SqliteRow| {
+                let position = row.try_get("position")?;
+                Ok((task_from_row(&row)?, position))
+            })
+            .fetch_all(&pool)
+            .await?
+            .into_iter()
+            .map(|(task, position)| (task.id.clone(), (task, position)))
+            .collect();
+
+        let sql = format!(
+            "WITH RECURSIVE roots(id) AS ({roots}), {TREE} \
+             SELECT tags.task_id, tags.tag FROM tags JOIN tree ON tags.task_id = tree.id \
+             ORDER BY tags.position"
+        );
+        let mut query = sqlx::query_as(&sql);
+        for parameter in parameters {
+            query = query.bind(*parameter);
+        }
+        let tags: Vec<(String, String)> = query.fetch_all(&pool).await?;
+        for (task_id, tag) in tags {
+            if let Some((task, _)) = tasks.get_mut(&task_id) {
+                task.tags.push(tag);
+            }
+        }
+
+        let sql = format!(
+            "WITH RECURSIVE roots(id) AS ({roots}), {TREE} \
+             SELECT subtasks.task_id, subtasks.subtask_id FROM subtasks \
+             JOIN tree ON subtasks.task_id = tree.id ORDER BY subtasks.position"
+        );
+        let mut query = sqlx::query_as(&sql);
+        for parameter in parameters {
+            query = query.bind(*parameter);
+        }
+        let edges: Vec<(String, String)> = query.fetch_all(&pool).await?;
+        let mut children: HashMap<String, Vec<String>> = HashMap::new();
+        for (task_id, subtask_id) in &edges {
+            children
+                .entry(task_id.clone())
+                .or_default()
+                .push(subtask_id.clone());
+        }
+
+        let nested: HashSet<&String> = edges.iter().map(|(_, subtask_id)| subtask_id).collect();
+        let mut roots: Vec<(Task, i64)> = tasks
+            .iter()
+            .filter(|(id, _)| !nested.contains(id))
+            .map(|(_, task)| task.clone())
+            .collect();
+        roots.sort_by_key(|(_, position)| *position);
+
+        let tasks: HashMap<String, Task> = tasks
+            .into_iter()
+            .map(|(id, (task, _))| (id, task))
+            .collect();
+        Ok(roots
+            .into_iter()
+            .map(|(task, _)| assemble(task, &tasks, &children))
+            .collect())
+    }
+
+    /// Writes a task row, its tags and its sub-tasks, replacing whatever was
+    /// stored for it before. New top level tasks are appended to their list.
+    ///
+    /// With `expected`, nothing is written unless the stored task was last
+    /// modified at that time.
+    async fn save_task(&mut self, task: &Task, expected: Option<DateTime<Utc>>) -> Result<()> {
+        let pool = self.pool().await?;
+        let mut transaction = pool.begin().await?;
+
+        if let Some(expected) = expected {
+            let stored: Option<String> =
+                sqlx::query_scalar("SELECT last_modified_date_time FROM tasks WHERE id = ?")
+                    .bind(&task.id)
+                    .fetch_optional(&mut *transaction)
+                    .await?;
+            let stored = stored
+                .map(|stored| DateTime::parse_from_rfc3339(&stored))
+                .transpose()
+                .map_err(|err| Error::Corrupt(err.to_string()))?;
+            if stored.is_some_and(|stored| stored != expected) {
+                transaction.rollback().await?;
+                let theirs = self.get_task(task.parent.clone(), task.id.clone()).await?;
+                return Err(Error::Conflict(Box::new(Conflict {
+                    ours: task.clone(),
+                    theirs,
+                })));
+            }
+        }
+
+        sqlx::query(&format!(
+            "WITH RECURSIVE roots(id) AS (SELECT ?), {TREE} \
+             DELETE FROM tasks WHERE id IN (SELECT id FROM tree) AND id != ?"
+        ))
+        .bind(&task.id)
+        .bind(&task.id)
+        .execute(&mut *transaction)
+        .await?;
+
+        let mut pending = vec![(task, None::<&str>, None::<i64>)];
+        while let Some((task, parent_task, position)) = pending.pop() {
+            sqlx::query(
+                "INSERT INTO tasks (id, parent, title, favorite, today, status, priority, notes, \
+                 completion_date, due_date, reminder_date, recurrence, deletion_date, \
+                 created_date_time, last_modified_date_time, position) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, \
+                 COALESCE(?, (SELECT COALESCE(MAX(position), -1) + 1 FROM tasks WHERE parent = ?))) \
+                 ON CONFLICT (id) DO UPDATE SET parent = excluded.parent, title = excluded.title, \
+                 favorite = excluded.favorite, today = excluded.today, status = excluded.status, \
+                 priority = excluded.priority, notes = excluded.notes, \
+                 completion_date = excluded.completion_date, due_date = excluded.due_date, \
+                 reminder_date = excluded.reminder_date, recurrence = excluded.recurrence, \
+                 deletion_date = excluded.deletion_date, \
+                 created_date_time = excluded.created_date_time, \
+                 last_modified_date_time = excluded.last_modified_date_time",
+            )
+            .bind(&task.id)
+            .bind(&task.parent)
+            .bind(&task.title)
+            .bind(task.favorite)
+            .bind(task.today)
+            .bind(i32::from(task.status))
+            .bind(i32::from(task.priority))
+            .bind(&task.notes)
+            .bind(task.completion_date.map(|date| date.to_rfc3339()))
+            .bind(task.due_date.map(|date| date.to_rfc3339()))
+            .bind(task.reminder_date.map(|date| date.to_rfc3339()))
+            .bind(ron::to_string(&task.recurrence)?)
+            .bind(task.deletion_date.map(|date| date.to_rfc3339()))
+            .bind(task.created_date_time.to_rfc3339())
+            .bind(task.last_modified_date_time.to_rfc3339())
+            .bind(position)
+            .bind(&task.parent)
+            .execute(&mut *transaction)
+            .await?;
+
+            sqlx::query("DELETE FROM tags WHERE task_id = ?")
+                .bind(&task.id)
+                .execute(&mut *transaction)
+                .await?;
+            for (position, tag) in task.tags.iter().enumerate() {
+                sqlx::query("INSERT OR IGNORE INTO tags (task_id, tag, position) VALUES (?, ?, ?)")
+                    .bind(&task.id)
+                    .bind(tag)
+                    .bind(position as i64)
+                    .execute(&mut *transaction)
+                    .await?;
+            }
+
+            if let Some(parent_task) = parent_task {
+                sqlx::query(
+                    "INSERT INTO subtasks (task_id, subtask_id, position) VALUES (?, ?, ?)",
+                )
+                .bind(parent_task)
+                .bind(&task.id)
+                .bind(position)
+                .execute(&mut *transaction)
+                .await?;
+            }
+
+            for (position, sub_task) in task.sub_tasks.iter().enumerate() {
+                pending.push((sub_task, Some(task.id.as_str()), Some(position as i64)));
+            }
+        }
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Reads a task by its id alone, whatever list it is in.
+    async fn stored_task(&mut self, task_id: &str) -> Result<Task> {
+        self.load_tasks("SELECT id FROM tasks WHERE id = ?", &[task_id])
+            .await?
+            .pop()
+            .ok_or_else(|| Error::TaskNotFound(task_id.to_string()))
+    }
+
+    async fn task_exists(&mut self, task_id: &str) -> Result<bool> {
+        let pool = self.pool().await?;
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tasks WHERE id = ?")
+            .bind(task_id)
+            .fetch_one(&pool)
+            .await?;
+        Ok(count > 0)
+    }
+
+    async fn list_exists(&mut self, list_id: &str) -> Result<bool> {
+        let pool = self.pool().await?;
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM lists WHERE id = ?")
+            .bind(list_id)
+            .fetch_one(&pool)
+            .await?;
+        Ok(count > 0)
+    }
+}
+
+#[async_trait]
+impl TasksProvider for SqliteStorage {
+    async fn get_task(&mut self, list_id: String, task_id: String) -> Result<Task> {
+        let mut tasks = self
+            .load_tasks("SELECT id FROM tasks WHERE id = ?", &[&task_id])
+            .await?;
+        match tasks.pop() {
+            Some(task) if task.parent == list_id => Ok(task),
+            _ => Err(Error::TaskNotFound(task_id)),
+        }
+    }
+
+    async fn get_tasks_from_list(&mut self, parent_list: String) -> Result<Vec<Task>> {
+        self.load_tasks(
+            "SELECT id FROM tasks WHERE parent = ? AND deletion_date IS NULL \
+             AND id NOT IN (SELECT subtask_id FROM subtasks)",
+            &[&parent_list],
+        )
+        .await
+    }
+
+    async fn get_deleted_tasks(&mut self) -> Result<Vec<Task>> {
+        self.load_tasks(
+            "SELECT id FROM tasks WHERE deletion_date IS NOT NULL \
+             AND id NOT IN (SELECT subtask_id FROM subtasks)",
+            &[],
+        )
+        .await
+    }
+
+    async fn create_task(&mut self, mut task: Task) -> Result<Task> {
+        if self.task_exists(&task.id).await? {
+            return Err(Error::AlreadyExists(task.id));
+        }
+        task.prepare_write(None);
+        self.save_task(&task, None).await?;
+        Ok(task)
+    }
+
+    async fn update_task(&mut self, mut task: Task) -> Result<()> {
+        let stored = self.stored_task(&task.id).await?;
+        task.prepare_write(Some(&stored));
+        self.save_task(&task, None).await
+    }
+
+    async fn update_task_if_unchanged(
+        &mut self,
+        mut task: Task,
+        expected: DateTime<Utc>,
+    ) -> Result<()> {
+        let stored = self.stored_task(&task.id).await?;
+        task.prepare_write(Some(&stored));
+        self.save_task(&task, Some(expected)).await
+    }
+
+    async fn purge_task(&mut self, list_id: String, task_id: String) -> Result<()> {
+        let pool = self.pool().await?;
+        let result = sqlx::query(&format!(
+            "WITH RECURSIVE roots(id) AS (SELECT id FROM tasks WHERE id = ? AND parent = ?), \
+             {TREE} DELETE FROM tasks WHERE id IN (SELECT id FROM tree)"
+        ))
+        .bind(&task_id)
+        .bind(&list_id)
+        .execute(&pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(Error::TaskNotFound(task_id));
+        }
+        Ok(())
+    }
+
+    async fn get_lists(&mut self) -> Result<Vec<List>> {
+        let pool = self.pool().await?;
+        let lists = sqlx::query("SELECT * FROM lists ORDER BY position")
+            .try_map(|row: SqliteRow| list_from_row(&row))
+            .fetch_all(&pool)
+            .await?;
+        Ok(lists)
+    }
+
+    async fn get_list(&mut self, id: String) -> Result<List> {
+        let pool = self.pool().await?;
+        let list = sqlx::query("SELECT * FROM lists WHERE id = ?")
+            .bind(&id)
+            .try_map(|row: SqliteRow| list_from_row(&row))
+            .fetch_optional(&pool)
+            .await?;
+        list.ok_or_else(|| Error::ListNotFound(id))
+    }
+
+    async fn create_list(&mut self, list: List) -> Result<List> {
+        if self.list_exists(&list.id).await? {
+            return Err(Error::AlreadyExists(list.id));
+        }
+        let pool = self.pool().await?;
+        sqlx::query(
+            "INSERT INTO lists (id, name, description, icon, position) \
+             VALUES (?, ?, ?, ?, (SELECT COALESCE(MAX(position), -1) + 1 FROM lists))",
+        )
+        .bind(&list.id)
+        .bind(&list.name)
+        .bind(&list.description)
+        .bind(&list.icon)
+        .execute(&pool)
+        .await?;
+        Ok(list)
+    }
+
+    async fn update_list(&mut self, list: List) -> Result<()> {
+        let pool = self.pool().await?;
+        let result =
+            sqlx::query("UPDATE lists SET name = ?, description = ?, icon = ? WHERE id = ?")
+                .bind(&list.name)
+                .bind(&list.description)
+                .bind(&list.icon)
+                .bind(&list.id)
+                .execute(&pool)
+                .await?;
+        if result.rows_affected() == 0 {
+            return Err(Error::ListNotFound(list.id));
+        }
+        Ok(())
+    }
+
+    async fn delete_list(&mut self, id: String) -> Result<()> {
+        let pool = self.pool().await?;
+        let mut transaction = pool.begin().await?;
+        let result = sqlx::query("DELETE FROM lists WHERE id = ?")
+            .bind(&id)
+            .execute(&mut *transaction)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(Error::ListNotFound(id));
+        }
+        sqlx::query(&format!(
+            "WITH RECURSIVE roots(id) AS (SELECT id FROM tasks WHERE parent = ?), {TREE} \
+             DELETE FROM tasks WHERE id IN (SELECT id FROM tree)"
+        ))
+        .bind(&id)
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+}
+
+fn assemble(
+    mut task: Task,
+    tasks: &HashMap<String, Task>,
+    children: &HashMap<String, Vec<String>>,
+) -> Task {
+    task.sub_tasks = children
+        .get(&task.id)
+        .into_iter()
+        .flatten()
+        .filter_map(|id| tasks.get(id))
+        .map(|sub_task| assemble(sub_task.clone(), tasks, children))
+        .collect();
+    task
+}
+
+fn list_from_row(row: &SqliteRow) -> sqlx::Result<List> {
+    Ok(List {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        description: row.try_get("description")?,
+        icon: row.try_get("icon")?,
+    })
+}
+
+fn task_from_row(row: &SqliteRow) -> sqlx::Result<Task> {
+    Ok(Task {
+        id: row.try_get("id")?,
+        parent: row.try_get("parent")?,
+        title: row.try_get("title")?,
+        favorite: row.try_get("favorite")?,
+        today: row.try_get("today")?,
+        status: Status::try_from(row.try_get::<i32, _>("status")?)
+            .map_err(|err| sqlx::Error::Decode(Box::new(err)))?,
+        priority: Priority::try_from(row.try_get::<i32, _>("priority")?)
+            .map_err(|err| sqlx::Error::Decode(Box::new(err)))?,
+        sub_tasks: vec![],
+        tags: vec![],
+        notes: row.try_get("notes")?,
+        completion_date: date_column(row, "completion_date")?,
+        due_date: date_column(row, "due_date")?,
+        reminder_date: date_column(row, "reminder_date")?,
+        recurrence: ron::from_str(row.try_get("recurrence")?)
+            .map_err(|err| sqlx::Error::Decode(Box::new(err)))?,
+        deletion_date: date_column(row, "deletion_date")?,
+        created_date_time: date_column(row, "created_date_time")?.unwrap_or_default(),
+        last_modified_date_time: date_column(row, "last_modified_date_time")?.unwrap_or_default(),
+    })
+}
+
+fn date_column(row: &SqliteRow, column: &str) -> sqlx::Result<Option<DateTime<Utc>>> {
+    let Some(value) = row.try_get::<Option<String>, _>(column)? else {
+        return Ok(None);
+    };
+    DateTime::parse_from_rfc3339(&value)
+        .map(|date| Some(date.with_timezone(&Utc)))
+        .map_err(|err| sqlx::Error::Decode(Box::new(err)))
+}
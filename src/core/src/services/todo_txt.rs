@@ -0,0 +1,654 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conflict::Conflict,
+    error::{Error, Result},
+    ical,
+    models::{list::List, query::TaskQuery, status::Status, task::Task},
+    services::files::{self, write_atomic},
+    task_service::TasksProvider,
+    todo_txt::{self, Line},
+};
+
+const DIRECTORY: &str = "todo-txt";
+const FILES_FILE: &str = "files.ron";
+const STATE_FILE: &str = "state.ron";
+
+/// The id of the list holding the tasks without a `+project`.
+const INBOX: &str = "inbox";
+
+const TODO: usize = 0;
+const DONE: usize = 1;
+
+/// The files the todo.txt provider keeps its tasks in.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TodoTxtFiles {
+    pub todo: PathBuf,
+    /// Where todo.txt tools archive completed tasks.
+    pub done: PathBuf,
+}
+
+impl TodoTxtFiles {
+    /// `todo` and the done.txt next to it.
+    pub fn new(todo: PathBuf) -> Self {
+        let done = todo.with_file_name("done.txt");
+        Self { todo, done }
+    }
+
+    /// The files chosen for the app, if any.
+    pub fn load(application_id: &str) -> Option<Self> {
+        let path = directory(application_id)?.join(FILES_FILE);
+        let content = std::fs::read_to_string(path).ok()?;
+        match ron::from_str(&content) {
+            Ok(files) => Some(files),
+            Err(err) => {
+                tracing::warn!("failed to read the todo.txt files: {err}");
+                None
+            }
+        }
+    }
+
+    /// Saves the files for the app. Switching to another todo.txt drops what
+    /// was kept next to the previous one.
+    pub fn save(&self, application_id: &str) -> Result<()> {
+        let directory = directory(application_id)
+            .ok_or_else(|| Error::Unsupported("Storing data on this system"))?;
+        std::fs::create_dir_all(&directory)?;
+        if Self::load(application_id).is_some_and(|files| files.todo != self.todo) {
+            let state = directory.join(STATE_FILE);
+            if state.exists() {
+                std::fs::remove_file(state)?;
+            }
+        }
+        write_atomic(
+            &directory.join(FILES_FILE),
+            ron::to_string(self)?.as_bytes(),
+        )
+    }
+}
+
+fn directory(application_id: &str) -> Option<PathBuf> {
+    Some(dirs::data_local_dir()?.join(application_id).join(DIRECTORY))
+}
+
+/// Keeps lists and tasks in a todo.txt file chosen by the user, so other
+/// todo.txt apps and scripts work on the same tasks.
+///
+/// Each `+project` is a list, and the tasks without one are in the inbox.
+/// Tasks archived in done.txt show up in their lists as well. Lines of tasks
+/// the app doesn't change are left as they are.
+///
+/// What todo.txt has no room for, such as notes, reminders, list icons and
+/// the trash, is kept in the app's data directory. So is the last version of
+/// each line written, which tells apart the lines another app changed since.
+#[derive(Debug, Clone)]
+pub struct TodoTxtStorage {
+    path: PathBuf,
+    files: TodoTxtFiles,
+}
+
+/// What is kept next to the files.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct State {
+    /// Lists created or changed in the app.
+    lists: Vec<List>,
+    /// The last version of each task written to the files, by id.
+    records: BTreeMap<String, Record>,
+    trash: Vec<Task>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Record {
+    line: String,
+    /// The task, without its sub-tasks.
+    task: Task,
+}
+
+/// The lines of both files as read, and what is kept next to them.
+#[derive(Clone, Debug, PartialEq)]
+struct Store {
+    /// The lines of todo.txt, then the ones of done.txt.
+    files: [Vec<String>; 2],
+    state: State,
+    /// When the files were last written, the modification time of the tasks
+    /// another app changed.
+    modified: DateTime<Utc>,
+}
+
+/// A task line.
+struct Entry {
+    file: usize,
+    index: usize,
+    task: Task,
+    project: Option<String>,
+    parent: Option<String>,
+}
+
+impl Store {
+    fn entries(&self) -> Vec<Entry> {
+        let mut entries = vec![];
+        for (file, lines) in self.files.iter().enumerate() {
+            let parsed = todo_txt::parse(&lines.join("\n"));
+            for (index, line) in parsed.into_iter().enumerate() {
+                let Some(line) = line else {
+                    continue;
+                };
+                entries.push(Entry {
+                    file,
+                    index,
+                    task: self.task(&line, lines[index].trim()),
+                    project: line.project,
+                    parent: line.parent,
+                });
+            }
+        }
+        entries
+    }
+
+    /// The task of a line, along with what todo.txt has no room for from the
+    /// last version written here.
+    fn task(&self, line: &Line, text: &str) -> Task {
+        let mut task = line.task.clone();
+        let Some(record) = self.state.records.get(&task.id) else {
+            task.last_modified_date_time = self.modified;
+            return task;
+        };
+        if record.line == text {
+            return record.task.clone();
+        }
+        let written = &record.task;
+        task.notes.clone_from(&written.notes);
+        task.favorite = written.favorite;
+        task.today = written.today;
+        task.reminder_date = written.reminder_date;
+        if task.recurrence.is_empty() && todo_txt::recurrence_value(&written.recurrence).is_none() {
+            task.recurrence = written.recurrence.clone();
+        }
+        task.created_date_time = written.created_date_time;
+        task.last_modified_date_time = self.modified;
+        task
+    }
+
+    /// The file and index of the line of a task.
+    fn locate(&self, id: &str) -> Option<(usize, usize)> {
+        self.files.iter().enumerate().find_map(|(file, lines)| {
+            todo_txt::parse(&lines.join("\n"))
+                .iter()
+                .position(|line| line.as_ref().is_some_and(|line| line.task.id == id))
+                .map(|index| (file, index))
+        })
+    }
+
+    fn lists(&self, entries: &[Entry]) -> Vec<List> {
+        let inbox = self
+            .state
+            .lists
+            .iter()
+            .find(|list| list.id == INBOX)
+            .cloned()
+            .unwrap_or_else(|| List {
+                id: INBOX.to_string(),
+                name: "Inbox".to_string(),
+                description: String::new(),
+                icon: None,
+            });
+        let mut lists = vec![inbox];
+        lists.extend(
+            self.state
+                .lists
+                .iter()
+                .filter(|list| list.id != INBOX)
+                .cloned(),
+        );
+        for entry in entries {
+            let list_id = self.list_id(entry.project.as_deref());
+            if !lists.iter().any(|list| list.id == list_id) {
+                lists.push(List {
+                    id: list_id.clone(),
+                    name: list_id,
+                    description: String::new(),
+                    icon: None,
+                });
+            }
+        }
+        lists
+    }
+
+    fn list(&self, entries: &[Entry], id: &str) -> Result<List> {
+        self.lists(entries)
+            .into_iter()
+            .find(|list| list.id == id)
+            .ok_or_else(|| Error::ListNotFound(id.to_string()))
+    }
+
+    /// The list of a `+project`. Lists nobody named in the app go by their
+    /// project.
+    fn list_id(&self, project: Option<&str>) -> String {
+        let Some(project) = project else {
+            return INBOX.to_string();
+        };
+        self.state
+            .lists
+            .iter()
+            .find(|list| list.id != INBOX && todo_txt::token(&list.name) == project)
+            .map_or_else(|| project.to_string(), |list| list.id.clone())
+    }
+
+    /// The `+project` of a list.
+    fn project(&self, list_id: &str) -> Option<String> {
+        if list_id == INBOX {
+            return None;
+        }
+        let project = self
+            .state
+            .lists
+            .iter()
+            .find(|list| list.id == list_id)
+            .map_or_else(|| list_id.to_string(), |list| todo_txt::token(&list.name));
+        Some(project)
+    }
+
+    /// Every task with its sub-tasks, in the list of its `+project`.
+    fn tasks(&self, entries: &[Entry]) -> Vec<Task> {
+        let projects: HashMap<&str, Option<&str>> = entries
+            .iter()
+            .map(|entry| (entry.task.id.as_str(), entry.project.as_deref()))
+            .collect();
+        let mut tasks = ical::nest(
+            entries
+                .iter()
+                .map(|entry| (entry.task.clone(), entry.parent.clone()))
+                .collect(),
+        );
+        for task in &mut tasks {
+            task.parent = self.list_id(projects.get(task.id.as_str()).copied().flatten());
+        }
+        tasks
+    }
+
+    fn list_tasks(&self, list_id: &str) -> Vec<Task> {
+        let entries = self.entries();
+        self.tasks(&entries)
+            .into_iter()
+            .filter(|task| task.parent == list_id)
+            .collect()
+    }
+
+    fn task_in_list(&self, list_id: &str, id: &str) -> Result<Task> {
+        find(&self.list_tasks(list_id), id)
+            .cloned()
+            .ok_or_else(|| Error::TaskNotFound(id.to_string()))
+    }
+
+    /// Writes a task and its sub-tasks over their lines, or at the end of
+    /// todo.txt for new ones, and removes the lines of the sub-tasks that
+    /// are gone since `stored`. Tasks that are no longer completed move out
+    /// of done.txt.
+    fn put(&mut self, task: &Task, stored: Option<&Task>) {
+        let tasks = ical::flatten(task);
+        if let Some(stored) = stored {
+            for (removed, _) in ical::flatten(stored) {
+                if !tasks.iter().any(|(task, _)| task.id == removed.id) {
+                    self.remove(&removed.id);
+                }
+            }
+        }
+        let project = self.project(&task.parent);
+        let parents: HashSet<String> = tasks
+            .iter()
+            .filter_map(|(_, parent)| parent.clone())
+            .collect();
+        for (task, parent) in tasks {
+            let location = self.locate(&task.id);
+            let mut line = todo_txt::write_line(&task, project.as_deref(), parent.as_deref());
+            let duplicated = self.files.iter().enumerate().any(|(file, lines)| {
+                lines
+                    .iter()
+                    .enumerate()
+                    .any(|(index, other)| other.trim() == line && location != Some((file, index)))
+            });
+            // Without `id:` the id is made from the line, which only works
+            // for a line that is unique, and that no sub-task or data kept
+            // here points at.
+            if duplicated
+                || parents.contains(&task.id)
+                || keeps_data(&task)
+                || task.id != todo_txt::line_id(&line, 0)
+            {
+                line.push_str(&format!(" id:{}", task.id));
+            }
+            match location {
+                Some((DONE, index)) if task.status != Status::Completed => {
+                    self.files[DONE].remove(index);
+                    self.files[TODO].push(line.clone());
+                }
+                Some((file, index)) => self.files[file][index].clone_from(&line),
+                None => self.files[TODO].push(line.clone()),
+            }
+            self.state
+                .records
+                .insert(task.id.clone(), Record { line, task });
+        }
+    }
+
+    /// Removes a task and its sub-tasks from the files.
+    fn remove_tree(&mut self, task: &Task) {
+        for (task, _) in ical::flatten(task) {
+            self.remove(&task.id);
+        }
+    }
+
+    fn remove(&mut self, id: &str) {
+        if let Some((file, index)) = self.locate(id) {
+            self.files[file].remove(index);
+        }
+        self.state.records.remove(id);
+    }
+
+    /// Remembers the current version of every line, so the lines another
+    /// app changes from now on can be told apart, and forgets the tasks that
+    /// are gone.
+    fn record(&mut self) {
+        let entries = self.entries();
+        let mut records = BTreeMap::new();
+        for entry in entries {
+            let line = self.files[entry.file][entry.index].trim().to_string();
+            let id = entry.task.id.clone();
+            records.insert(
+                id,
+                Record {
+                    line,
+                    task: entry.task,
+                },
+            );
+        }
+        self.state.records = records;
+    }
+}
+
+/// Whether the task has data that todo.txt has no room for.
+fn keeps_data(task: &Task) -> bool {
+    !task.notes.is_empty()
+        || task.favorite
+        || task.today
+        || task.reminder_date.is_some()
+        || (!task.recurrence.is_empty() && todo_txt::recurrence_value(&task.recurrence).is_none())
+}
+
+fn find<'a>(tasks: &'a [Task], id: &str) -> Option<&'a Task> {
+    tasks.iter().find_map(|task| {
+        if task.id == id {
+            Some(task)
+        } else {
+            find(&task.sub_tasks, id)
+        }
+    })
+}
+
+impl TodoTxtStorage {
+    pub(crate) fn new(application_id: &str) -> Option<Self> {
+        let path = directory(application_id)?;
+        if !path.exists() {
+            std::fs::create_dir_all(&path).ok()?;
+        }
+        let files = TodoTxtFiles::load(application_id)?;
+        Some(Self { path, files })
+    }
+
+    fn paths(&self) -> [&Path; 2] {
+        [&self.files.todo, &self.files.done]
+    }
+
+    fn load(&self) -> Result<Store> {
+        let mut files: [Vec<String>; 2] = Default::default();
+        let mut modified = DateTime::<Utc>::default();
+        for (lines, path) in files.iter_mut().zip(self.paths()) {
+            if !path.exists() {
+                continue;
+            }
+            *lines = std::fs::read_to_string(path)?
+                .lines()
+                .map(str::to_string)
+                .collect();
+            modified = modified.max(std::fs::metadata(path)?.modified()?.into());
+        }
+        let state_path = self.path.join(STATE_FILE);
+        let state = if state_path.exists() {
+            ron::from_str(&std::fs::read_to_string(state_path)?)?
+        } else {
+            State::default()
+        };
+        Ok(Store {
+            files,
+            state,
+            modified,
+        })
+    }
+
+    /// Writes the files and the state that differ from `before`, each
+    /// through a temporary file so it is never left half written.
+    fn save(&self, before: &Store, store: &mut Store) -> Result<()> {
+        store.record();
+        for ((lines, previous), path) in store.files.iter().zip(&before.files).zip(self.paths()) {
+            if lines == previous {
+                continue;
+            }
+            let mut content = lines.join("\n");
+            if !content.is_empty() {
+                content.push('\n');
+            }
+            // Replace what a symbolic link points at rather than the link.
+            let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+            write_atomic(&path, content.as_bytes())?;
+        }
+        if store.state != before.state {
+            write_atomic(
+                &self.path.join(STATE_FILE),
+                ron::to_string(&store.state)?.as_bytes(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TasksProvider for TodoTxtStorage {
+    async fn get_task(&mut self, list_id: String, task_id: String) -> Result<Task> {
+        let _lock = files::lock(&self.path)?;
+        self.load()?.task_in_list(&list_id, &task_id)
+    }
+
+    async fn get_tasks_from_list(&mut self, parent_list: String) -> Result<Vec<Task>> {
+        let _lock = files::lock(&self.path)?;
+        let store = self.load()?;
+        let entries = store.entries();
+        store.list(&entries, &parent_list)?;
+        Ok(store
+            .tasks(&entries)
+            .into_iter()
+            .filter(|task| task.parent == parent_list)
+            .collect())
+    }
+
+    async fn get_deleted_tasks(&mut self) -> Result<Vec<Task>> {
+        let _lock = files::lock(&self.path)?;
+        Ok(self.load()?.state.trash)
+    }
+
+    async fn query(&mut self, query: &TaskQuery) -> Result<Vec<Task>> {
+        let _lock = files::lock(&self.path)?;
+        let store = self.load()?;
+        let entries = store.entries();
+        let mut tasks: Vec<Task> = store
+            .tasks(&entries)
+            .into_iter()
+            .filter(|task| query.list.as_ref().is_none_or(|list| *list == task.parent))
+            .filter(|task| query.matches(task))
+            .collect();
+        if query.include_deleted {
+            tasks.extend(
+                store
+                    .state
+                    .trash
+                    .into_iter()
+                    .filter(|task| query.matches(task)),
+            );
+        }
+        Ok(query.finish(tasks))
+    }
+
+    async fn create_task(&mut self, mut task: Task) -> Result<Task> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut store = before.clone();
+        let trashed = store
+            .state
+            .trash
+            .iter()
+            .any(|trashed| trashed.id == task.id);
+        if trashed || store.locate(&task.id).is_some() {
+            return Err(Error::AlreadyExists(task.id));
+        }
+        task.prepare_write(None);
+        // Tasks copied from the trash of another provider stay in the trash.
+        if task.deletion_date.is_some() {
+            store.state.trash.push(task.clone());
+        } else {
+            store.put(&task, None);
+        }
+        self.save(&before, &mut store)?;
+        Ok(task)
+    }
+
+    async fn update_task(&mut self, mut task: Task) -> Result<()> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut store = before.clone();
+        let stored = store.task_in_list(&task.parent, &task.id)?;
+        task.prepare_write(Some(&stored));
+        store.put(&task, Some(&stored));
+        self.save(&before, &mut store)
+    }
+
+    async fn update_task_if_unchanged(
+        &mut self,
+        mut task: Task,
+        expected: DateTime<Utc>,
+    ) -> Result<()> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut store = before.clone();
+        let stored = store.task_in_list(&task.parent, &task.id)?;
+        if stored.last_modified_date_time != expected {
+            return Err(Error::Conflict(Box::new(Conflict {
+                ours: task,
+                theirs: stored,
+            })));
+        }
+        task.prepare_write(Some(&stored));
+        store.put(&task, Some(&stored));
+        self.save(&before, &mut store)
+    }
+
+    async fn delete_task(&mut self, list_id: String, task_id: String) -> Result<()> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut store = before.clone();
+        let mut task = store.task_in_list(&list_id, &task_id)?;
+        store.remove_tree(&task);
+        task.deletion_date = Some(Utc::now());
+        store.state.trash.push(task);
+        self.save(&before, &mut store)
+    }
+
+    async fn restore_task(&mut self, _list_id: String, task_id: String) -> Result<()> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut store = before.clone();
+        let index = store
+            .state
+            .trash
+            .iter()
+            .position(|task| task.id == task_id)
+            .ok_or_else(|| Error::TaskNotFound(task_id.clone()))?;
+        let mut task = store.state.trash.remove(index);
+        task.deletion_date = None;
+        store.put(&task, None);
+        self.save(&before, &mut store)
+    }
+
+    async fn purge_task(&mut self, list_id: String, task_id: String) -> Result<()> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut store = before.clone();
+        if let Some(index) = store.state.trash.iter().position(|task| task.id == task_id) {
+            store.state.trash.remove(index);
+        } else {
+            let task = store.task_in_list(&list_id, &task_id)?;
+            store.remove_tree(&task);
+        }
+        self.save(&before, &mut store)
+    }
+
+    async fn get_lists(&mut self) -> Result<Vec<List>> {
+        let _lock = files::lock(&self.path)?;
+        let store = self.load()?;
+        Ok(store.lists(&store.entries()))
+    }
+
+    async fn get_list(&mut self, id: String) -> Result<List> {
+        let _lock = files::lock(&self.path)?;
+        let store = self.load()?;
+        store.list(&store.entries(), &id)
+    }
+
+    async fn create_list(&mut self, list: List) -> Result<List> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut store = before.clone();
+        if store.list(&store.entries(), &list.id).is_ok() {
+            return Err(Error::AlreadyExists(list.id));
+        }
+        store.state.lists.push(list.clone());
+        self.save(&before, &mut store)?;
+        Ok(list)
+    }
+
+    async fn update_list(&mut self, list: List) -> Result<()> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut store = before.clone();
+        store.list(&store.entries(), &list.id)?;
+        let tasks = store.list_tasks(&list.id);
+        let project = store.project(&list.id);
+        store.state.lists.retain(|known| known.id != list.id);
+        store.state.lists.push(list.clone());
+        // A new name is a new `+project` on every line of the list.
+        if store.project(&list.id) != project {
+            for task in tasks {
+                store.put(&task, Some(&task));
+            }
+        }
+        self.save(&before, &mut store)
+    }
+
+    async fn delete_list(&mut self, id: String) -> Result<()> {
+        let _lock = files::lock(&self.path)?;
+        let before = self.load()?;
+        let mut store = before.clone();
+        store.list(&store.entries(), &id)?;
+        for task in store.list_tasks(&id) {
+            store.remove_tree(&task);
+        }
+        store.state.lists.retain(|list| list.id != id);
+        store.state.trash.retain(|task| task.parent != id);
+        self.save(&before, &mut store)
+    }
+}
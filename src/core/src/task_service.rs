@@ -1,24 +1,113 @@
-use anyhow::Result;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 
-use crate::models::{list::List, task::Task};
+use crate::{
+    conflict::Conflict,
+    error::{Error, Result},
+    models::{diagnostic::Diagnostic, list::List, query::TaskQuery, task::Task},
+};
 
 #[async_trait]
 pub trait TasksProvider: Sync + Send {
     /// Reads a single task by its id.
     async fn get_task(&mut self, task_list_id: String, task_id: String) -> Result<Task>;
 
-    /// Read all the tasks from a list.
+    /// Read all the tasks from a list, leaving out the ones in the trash.
     async fn get_tasks_from_list(&mut self, parent_list: String) -> Result<Vec<Task>>;
 
+    /// Read the tasks in the trash from every list.
+    async fn get_deleted_tasks(&mut self) -> Result<Vec<Task>>;
+
+    /// Finds the tasks matching `query` across every list.
+    async fn query(&mut self, query: &TaskQuery) -> Result<Vec<Task>> {
+        let lists = match &query.list {
+            Some(list) => vec![list.clone()],
+            None => self
+                .get_lists()
+                .await?
+                .into_iter()
+                .map(|list| list.id)
+                .collect(),
+        };
+        let mut tasks = vec![];
+        for list in lists {
+            let list_tasks = self.get_tasks_from_list(list).await?;
+            tasks.extend(list_tasks.into_iter().filter(|task| query.matches(task)));
+        }
+        if query.include_deleted {
+            let deleted = self.get_deleted_tasks().await?;
+            tasks.extend(deleted.into_iter().filter(|task| query.matches(task)));
+        }
+        Ok(query.finish(tasks))
+    }
+
     /// Creates a single task.
     async fn create_task(&mut self, task: Task) -> Result<Task>;
 
+    /// Creates the next occurrence of a completed recurring task, unless the
+    /// list already holds one from an earlier completion of the same task.
+    /// Returns the task that was created, if any.
+    async fn create_next_occurrence(&mut self, task: &Task) -> Result<Option<Task>> {
+        let Some(next) = task.next_occurrence() else {
+            return Ok(None);
+        };
+        let tasks = self.get_tasks_from_list(task.parent.clone()).await?;
+        if tasks.iter().any(|other| task.is_followed_by(other)) {
+            return Ok(None);
+        }
+        self.create_task(next).await.map(Some)
+    }
+
     /// Updates a single task.
     async fn update_task(&mut self, task: Task) -> Result<()>;
 
-    /// Deltes a single task.
-    async fn delete_task(&mut self, list_id: String, task_id: String) -> Result<()>;
+    /// Updates a single task, as long as the stored copy was last modified at
+    /// `expected`, the modification time of the copy the changes were made to.
+    /// Otherwise nothing is written and [`Error::Conflict`] is returned.
+    async fn update_task_if_unchanged(
+        &mut self,
+        task: Task,
+        expected: DateTime<Utc>,
+    ) -> Result<()> {
+        let stored = self.get_task(task.parent.clone(), task.id.clone()).await?;
+        if stored.last_modified_date_time != expected {
+            return Err(Error::Conflict(Box::new(Conflict {
+                ours: task,
+                theirs: stored,
+            })));
+        }
+        self.update_task(task).await
+    }
+
+    /// Moves a single task to the trash.
+    async fn delete_task(&mut self, list_id: String, task_id: String) -> Result<()> {
+        let mut task = self.get_task(list_id, task_id).await?;
+        task.deletion_date = Some(Utc::now());
+        self.update_task(task).await
+    }
+
+    /// Moves a single task out of the trash and back into its list.
+    async fn restore_task(&mut self, list_id: String, task_id: String) -> Result<()> {
+        let mut task = self.get_task(list_id, task_id).await?;
+        task.deletion_date = None;
+        self.update_task(task).await
+    }
+
+    /// Permanently deletes a single task.
+    async fn purge_task(&mut self, list_id: String, task_id: String) -> Result<()>;
+
+    /// Permanently deletes the tasks that were moved to the trash before `date`
+    /// and returns how many were removed.
+    async fn purge_deleted_tasks(&mut self, date: DateTime<Utc>) -> Result<usize> {
+        let mut purged = 0;
+        for task in self.get_deleted_tasks().await? {
+            if task.deletion_date.is_some_and(|deleted| deleted < date) {
+                self.purge_task(task.parent, task.id).await?;
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
 
     /// Read all the lists from a service.
     async fn get_lists(&mut self) -> Result<Vec<List>>;
@@ -34,4 +123,14 @@ pub trait TasksProvider: Sync + Send {
 
     /// Deletes a single task list.
     async fn delete_list(&mut self, id: String) -> Result<()>;
+
+    /// Reports problems found while reading the service's data.
+    async fn diagnostics(&mut self) -> Result<Vec<Diagnostic>> {
+        Ok(vec![])
+    }
+
+    /// Marks problems as seen, so they are no longer reported.
+    async fn acknowledge_diagnostics(&mut self, _diagnostics: &[Diagnostic]) -> Result<()> {
+        Ok(())
+    }
 }
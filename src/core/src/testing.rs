@@ -0,0 +1,30 @@
+//! Helpers shared by the tests of the crate.
+
+use tokio::net::UnixStream;
+use uuid::Uuid;
+use zbus::{connection::Builder, Connection, Guid};
+
+use crate::service::{Provider, TaskService};
+
+/// A service on local files that no other test touches.
+///
+/// The files live in a data directory of the test run, and every service
+/// gets an app id of its own, so the tests can run side by side.
+pub fn service() -> TaskService {
+    let data = std::env::temp_dir().join(format!("tasks-core-tests-{}", std::process::id()));
+    std::env::set_var("XDG_DATA_HOME", data);
+    let app_id = format!("dev.edfloreshz.Tasks.Test{}", Uuid::new_v4().simple());
+    TaskService::new(&app_id, Provider::Computer)
+}
+
+/// The two ends of a private peer-to-peer connection, which stands in for
+/// the session bus: what one end serves, the other one calls.
+pub async fn peers() -> zbus::Result<(Connection, Connection)> {
+    let (server, client) = UnixStream::pair()?;
+    let server = Builder::unix_stream(server)
+        .server(Guid::generate())?
+        .p2p()
+        .build();
+    let client = Builder::unix_stream(client).p2p().build();
+    tokio::try_join!(server, client)
+}
@@ -0,0 +1,440 @@
+//! Tasks as lines of a todo.txt file, following
+//! <https://github.com/todotxt/todo.txt>.
+//!
+//! A line holds one task without its sub-tasks. The first `+project` is the
+//! list the task belongs to and each `@context` is a tag. Of the `key:value`
+//! pairs, `due:` is the due date, `rec:` the recurrence as Simpletask writes
+//! it, `pri:` the priority of a completed task, and `id:` and `p:` tie a
+//! sub-task to its parent. Anything else stays in the title as written.
+//!
+//! Priority `(A)` is high and `(B)` is normal, every other letter is low.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+
+use crate::{
+    ical,
+    import::ImportedList,
+    models::{
+        list::List,
+        priority::Priority,
+        recurrence::{Frequency, Recurrence},
+        status::Status,
+        task::Task,
+    },
+};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// A task read from a line, without its sub-tasks and not in a list yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line {
+    pub task: Task,
+    /// The first `+project` of the line, without the `+`.
+    pub project: Option<String>,
+    /// The id of the task this one is a sub-task of, from `p:`.
+    pub parent: Option<String>,
+}
+
+/// Reads each line of a todo.txt file, `None` for blank lines.
+///
+/// A line without `id:` gets an id made from its text, which stays the same
+/// for as long as the line does.
+pub fn parse(text: &str) -> Vec<Option<Line>> {
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    text.lines()
+        .map(|line| {
+            let line = line.trim();
+            if line.is_empty() {
+                return None;
+            }
+            let occurrence = seen.entry(line).or_default();
+            let parsed = parse_line(line, *occurrence);
+            *occurrence += 1;
+            Some(parsed)
+        })
+        .collect()
+}
+
+/// Reads a line, the `occurrence`-th one with this exact text in the file.
+pub fn parse_line(line: &str, occurrence: usize) -> Line {
+    let mut task = Task::new(String::new(), String::new());
+    let mut words = line.split_whitespace().peekable();
+
+    if words.peek() == Some(&"x") {
+        words.next();
+        task.status = Status::Completed;
+        if let Some(priority) = words.peek().and_then(|word| priority(word)) {
+            task.priority = priority;
+            words.next();
+        }
+        if let Some(completed) = words.peek().and_then(|word| date(word)) {
+            task.completion_date = Some(local_midnight(completed));
+            words.next();
+            if let Some(created) = words.peek().and_then(|word| date(word)) {
+                task.created_date_time = local_midnight(created);
+                words.next();
+            }
+        }
+    } else {
+        if let Some(priority) = words.peek().and_then(|word| priority(word)) {
+            task.priority = priority;
+            words.next();
+        }
+        if let Some(created) = words.peek().and_then(|word| date(word)) {
+            task.created_date_time = local_midnight(created);
+            words.next();
+        }
+    }
+    task.last_modified_date_time = task.created_date_time;
+
+    let mut project = None;
+    let mut parent = None;
+    let mut id = None;
+    let mut title = vec![];
+    for word in words {
+        if let Some(name) = word.strip_prefix('+').filter(|name| !name.is_empty()) {
+            if project.is_none() {
+                project = Some(name.to_string());
+                continue;
+            }
+        } else if let Some(tag) = word.strip_prefix('@').filter(|tag| !tag.is_empty()) {
+            task.tags.push(tag.to_string());
+            continue;
+        } else if let Some((key, value)) =
+            word.split_once(':').filter(|(_, value)| !value.is_empty())
+        {
+            match key {
+                "due" => {
+                    if let Some(due) = date(value) {
+                        task.due_date = Some(utc_midnight(due));
+                        continue;
+                    }
+                }
+                "rec" => {
+                    if let Some(recurrence) = parse_recurrence(value) {
+                        task.recurrence = recurrence;
+                        continue;
+                    }
+                }
+                "pri" => {
+                    if let Some(priority) = priority(&format!("({value})")) {
+                        task.priority = priority;
+                        continue;
+                    }
+                }
+                "id" => {
+                    id = Some(value.to_string());
+                    continue;
+                }
+                "p" => {
+                    parent = Some(value.to_string());
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        title.push(word);
+    }
+    task.title = title.join(" ");
+    task.id = id.unwrap_or_else(|| line_id(line, occurrence));
+
+    Line {
+        task,
+        project,
+        parent,
+    }
+}
+
+/// Writes a task, without its sub-tasks or its id, as a line. `project` is
+/// the list it belongs to and `parent` the id of the task it is a sub-task
+/// of.
+pub fn write_line(task: &Task, project: Option<&str>, parent: Option<&str>) -> String {
+    let mut words = vec![];
+    let created = task.created_date_time.with_timezone(&Local).date_naive();
+    if task.status == Status::Completed {
+        words.push("x".to_string());
+        // The creation date can only follow a completion date.
+        if let Some(completed) = task.completion_date {
+            let completed = completed.with_timezone(&Local).date_naive();
+            words.push(completed.format(DATE_FORMAT).to_string());
+            words.push(created.format(DATE_FORMAT).to_string());
+        }
+    } else {
+        if let Some(letter) = priority_letter(task.priority) {
+            words.push(format!("({letter})"));
+        }
+        words.push(created.format(DATE_FORMAT).to_string());
+    }
+    // A line break in the title would start another task.
+    words.extend(task.title.split_whitespace().map(str::to_string));
+    if let Some(project) = project {
+        words.push(format!("+{}", token(project)));
+    }
+    for tag in &task.tags {
+        words.push(format!("@{}", token(tag)));
+    }
+    if let Some(due) = task.due_date {
+        words.push(format!("due:{}", due.date_naive().format(DATE_FORMAT)));
+    }
+    if let Some(recurrence) = recurrence_value(&task.recurrence) {
+        words.push(format!("rec:{recurrence}"));
+    }
+    if task.status == Status::Completed {
+        if let Some(letter) = priority_letter(task.priority) {
+            words.push(format!("pri:{letter}"));
+        }
+    }
+    if let Some(parent) = parent {
+        words.push(format!("p:{parent}"));
+    }
+    words.join(" ")
+}
+
+/// The id a line written without `id:` gets when read, for the
+/// `occurrence`-th line with this exact text in the file.
+pub fn line_id(line: &str, occurrence: usize) -> String {
+    // FNV-1a, which unlike the hasher of the standard library gives the same
+    // id on every system and with every release.
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in format!("{line}\n{occurrence}").bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{hash:016x}")
+}
+
+/// A list name or tag as a single word, for `+project` and `@context`.
+pub fn token(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+/// The recurrence as a `rec:` value, if it can be written as one: every
+/// few days, weeks, months or years, counted from the due date with a `+`
+/// or else from the completion date.
+pub fn recurrence_value(recurrence: &Recurrence) -> Option<String> {
+    let frequency = recurrence.frequency?;
+    if !recurrence.by_day.is_empty()
+        || !recurrence.by_month_day.is_empty()
+        || recurrence.until.is_some()
+        || recurrence.count.is_some()
+    {
+        return None;
+    }
+    let unit = match frequency {
+        Frequency::Daily => 'd',
+        Frequency::Weekly => 'w',
+        Frequency::Monthly => 'm',
+        Frequency::Yearly => 'y',
+    };
+    let strict = if recurrence.after_completion { "" } else { "+" };
+    Some(format!("{strict}{}{unit}", recurrence.interval.max(1)))
+}
+
+fn parse_recurrence(value: &str) -> Option<Recurrence> {
+    let (after_completion, value) = match value.strip_prefix('+') {
+        Some(value) => (false, value),
+        None => (true, value),
+    };
+    let unit = value.chars().last()?;
+    let frequency = match unit {
+        'd' => Frequency::Daily,
+        'w' => Frequency::Weekly,
+        'm' => Frequency::Monthly,
+        'y' => Frequency::Yearly,
+        _ => return None,
+    };
+    let interval = value[..value.len() - unit.len_utf8()]
+        .parse()
+        .ok()
+        .filter(|interval| *interval > 0)?;
+    Some(Recurrence {
+        frequency: Some(frequency),
+        interval,
+        after_completion,
+        ..Default::default()
+    })
+}
+
+fn priority(word: &str) -> Option<Priority> {
+    let letter = word.strip_prefix('(')?.strip_suffix(')')?;
+    match letter {
+        "A" => Some(Priority::High),
+        "B" => Some(Priority::Normal),
+        _ if letter.len() == 1 && letter.chars().all(|c| c.is_ascii_uppercase()) => {
+            Some(Priority::Low)
+        }
+        _ => None,
+    }
+}
+
+fn priority_letter(priority: Priority) -> Option<char> {
+    match priority {
+        Priority::High => Some('A'),
+        Priority::Normal => Some('B'),
+        Priority::Low => None,
+    }
+}
+
+fn date(word: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(word, DATE_FORMAT).ok()
+}
+
+/// Due dates are kept as midnight UTC of the day, like the app picks them.
+pub(crate) fn utc_midnight(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()
+}
+
+pub(crate) fn local_midnight(date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map_or_else(|| midnight.and_utc(), |local| local.with_timezone(&Utc))
+}
+
+/// Reads a todo.txt file into a list for each `+project`, and one for the
+/// tasks without a project, with sub-tasks nested under their parents.
+pub fn import(text: &str) -> Vec<ImportedList> {
+    let lines: Vec<Line> = parse(text).into_iter().flatten().collect();
+    let projects: HashMap<String, Option<String>> = lines
+        .iter()
+        .map(|line| (line.task.id.clone(), line.project.clone()))
+        .collect();
+    let tasks = ical::nest(
+        lines
+            .into_iter()
+            .map(|line| (line.task, line.parent))
+            .collect(),
+    );
+
+    let mut imported: Vec<ImportedList> = vec![];
+    for task in tasks {
+        let name = projects.get(&task.id).cloned().flatten();
+        match imported.iter_mut().find(|list| list.name == name) {
+            Some(list) => list.tasks.push(task),
+            None => imported.push(ImportedList {
+                name,
+                description: None,
+                tasks: vec![task],
+            }),
+        }
+    }
+    imported
+}
+
+/// Writes lists and their tasks as a todo.txt file, each task with the list
+/// as its `+project`. Only tasks with sub-tasks get an `id:`, for the
+/// sub-tasks to point at.
+pub fn export(lists: &[(List, Vec<Task>)]) -> String {
+    let mut text = String::new();
+    for (list, tasks) in lists {
+        let tasks: Vec<(Task, Option<String>)> = tasks.iter().flat_map(ical::flatten).collect();
+        let parents: HashSet<&str> = tasks
+            .iter()
+            .filter_map(|(_, parent)| parent.as_deref())
+            .collect();
+        for (task, parent) in &tasks {
+            text.push_str(&write_line(
+                task,
+                Some(list.name.as_str()),
+                parent.as_deref(),
+            ));
+            if parents.contains(task.id.as_str()) {
+                text.push_str(&format!(" id:{}", task.id));
+            }
+            text.push('\n');
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn parses_lines() {
+        let line = parse_line(
+            "(A) 2024-01-02 Call mom +Family @phone due:2024-01-05 rec:+2w id:7 p:3 url:x",
+            0,
+        );
+        assert_eq!(line.project.as_deref(), Some("Family"));
+        assert_eq!(line.parent.as_deref(), Some("3"));
+        let task = line.task;
+        assert_eq!(task.id, "7");
+        assert_eq!(task.title, "Call mom url:x");
+        assert_eq!(task.priority, Priority::High);
+        assert_eq!(task.tags, ["phone"]);
+        assert_eq!(
+            task.due_date,
+            Some(Utc.with_ymd_and_hms(2024, 1, 5, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            task.created_date_time.with_timezone(&Local).date_naive(),
+            NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()
+        );
+        assert_eq!(task.recurrence.frequency, Some(Frequency::Weekly));
+        assert_eq!(task.recurrence.interval, 2);
+        assert!(!task.recurrence.after_completion);
+
+        let done = parse_line("x 2024-01-03 2024-01-02 Pay rent pri:B", 0).task;
+        assert_eq!(done.status, Status::Completed);
+        assert_eq!(done.priority, Priority::Normal);
+        assert_eq!(done.title, "Pay rent");
+        assert!(done.completion_date.is_some());
+
+        // Lines without an id get the same one every time they are read.
+        let first = parse_line("Buy milk", 0).task.id;
+        assert_eq!(parse_line("Buy milk", 0).task.id, first);
+        assert_ne!(parse_line("Buy milk", 1).task.id, first);
+    }
+
+    #[test]
+    fn writes_lines() {
+        let mut task = Task::new("Call\nmom \n  soon".to_string(), String::new());
+        task.priority = Priority::High;
+        task.tags = vec!["phone calls".to_string()];
+        task.due_date = Some(Utc.with_ymd_and_hms(2024, 1, 5, 0, 0, 0).unwrap());
+        task.recurrence = Recurrence {
+            frequency: Some(Frequency::Monthly),
+            after_completion: true,
+            ..Default::default()
+        };
+        let line = write_line(&task, Some("Family stuff"), Some("3"));
+        let created = task.created_date_time.with_timezone(&Local).date_naive();
+        assert_eq!(
+            line,
+            format!(
+                "(A) {} Call mom soon +Family_stuff @phone_calls due:2024-01-05 rec:1m p:3",
+                created.format(DATE_FORMAT)
+            )
+        );
+
+        let read = parse_line(&line, 0);
+        assert_eq!(read.task.title, "Call mom soon");
+        assert_eq!(read.task.recurrence, task.recurrence);
+        assert_eq!(read.task.due_date, task.due_date);
+        assert_eq!(read.project.as_deref(), Some("Family_stuff"));
+    }
+
+    #[test]
+    fn reads_and_writes_recurrence() {
+        for value in ["1d", "+3w", "2m", "+1y"] {
+            let recurrence = parse_recurrence(value).unwrap();
+            assert_eq!(recurrence_value(&recurrence).as_deref(), Some(value));
+        }
+        assert_eq!(parse_recurrence("0d"), None);
+        assert_eq!(parse_recurrence("2x"), None);
+        assert_eq!(parse_recurrence("w"), None);
+
+        // Rules that need more than an interval can't be written.
+        let weekdays: Recurrence = "FREQ=WEEKLY;BYDAY=MO,FR".parse().unwrap();
+        assert_eq!(recurrence_value(&weekdays), None);
+        let count: Recurrence = "FREQ=DAILY;COUNT=3".parse().unwrap();
+        assert_eq!(recurrence_value(&count), None);
+    }
+}
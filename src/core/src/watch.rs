@@ -0,0 +1,147 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use futures::channel::mpsc::{self, UnboundedReceiver};
+use futures::StreamExt;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{
+    error::{Error, Result},
+    service::{Provider, TaskService},
+    services::{files, todo_txt::TodoTxtFiles},
+};
+
+/// How long the files have to stay untouched before a change is reported, so
+/// a sync tool writing many files at once causes a single reload.
+const SETTLE: Duration = Duration::from_millis(300);
+
+/// Something in the data of a provider that changed on disk.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StorageChange {
+    /// A list was created, renamed or deleted.
+    Lists,
+    /// Tasks of the list with this id were created, changed or deleted.
+    Tasks(String),
+    /// The database changed, so any list or task may have.
+    All,
+}
+
+/// Watches the data directory of a provider for changes made by other
+/// processes, such as another window or a sync tool like Syncthing.
+///
+/// Files this process wrote itself are not reported, as long as nobody
+/// changed them since. Writes that go elsewhere, such as to the database of
+/// the SQLite provider, are reported too.
+pub struct StorageWatcher {
+    path: PathBuf,
+    provider: Provider,
+    /// Files outside of the data directory that hold the data of the
+    /// provider.
+    files: Vec<PathBuf>,
+    events: UnboundedReceiver<notify::Result<Event>>,
+    _watcher: RecommendedWatcher,
+}
+
+impl StorageWatcher {
+    pub fn new(service: &TaskService) -> Result<Self> {
+        // Every provider keeps its data in this directory.
+        let path = dirs::data_local_dir()
+            .ok_or_else(|| Error::Unsupported("Storing data on this system"))?
+            .join(&service.app_id);
+        std::fs::create_dir_all(&path)?;
+
+        let (sender, events) = mpsc::unbounded();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.unbounded_send(event);
+        })?;
+        watcher.watch(&path, RecursiveMode::Recursive)?;
+
+        // Files are replaced rather than written to, so their directories are
+        // watched instead.
+        let mut files = vec![];
+        if service.provider == Provider::TodoTxt {
+            if let Some(todo_txt) = TodoTxtFiles::load(&service.app_id) {
+                files = vec![todo_txt.todo, todo_txt.done];
+            }
+        }
+        let directories: BTreeSet<&Path> = files.iter().filter_map(|file| file.parent()).collect();
+        for directory in directories {
+            watcher.watch(directory, RecursiveMode::NonRecursive)?;
+        }
+
+        Ok(Self {
+            path,
+            provider: service.provider,
+            files,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    /// Waits until the data changes and the files settle, and returns what
+    /// changed in the meantime.
+    pub async fn next(&mut self) -> Option<Vec<StorageChange>> {
+        let mut changes = BTreeSet::new();
+        loop {
+            let event = if changes.is_empty() {
+                self.events.next().await?
+            } else {
+                match tokio::time::timeout(SETTLE, self.events.next()).await {
+                    Ok(Some(event)) => event,
+                    Ok(None) | Err(_) => return Some(changes.into_iter().collect()),
+                }
+            };
+            match event {
+                Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                    changes.extend(
+                        event
+                            .paths
+                            .iter()
+                            .filter(|path| !files::is_own_write(path))
+                            .filter_map(|path| self.change(path)),
+                    );
+                }
+                Ok(_) => {}
+                Err(err) => tracing::warn!("failed to watch {}: {err}", self.path.display()),
+            }
+        }
+    }
+
+    /// What a change to the file at `path` means for the provider being watched.
+    fn change(&self, path: &Path) -> Option<StorageChange> {
+        if self.files.iter().any(|file| file == path) {
+            return Some(StorageChange::All);
+        }
+        let relative = path.strip_prefix(&self.path).ok()?;
+        match self.provider {
+            Provider::Computer => {
+                // Temporary files are renamed over the records once written.
+                if relative.extension().is_some_and(|ext| ext != "ron") {
+                    return None;
+                }
+                let mut components = relative.iter();
+                match components.next()?.to_str()? {
+                    "lists" => Some(StorageChange::Lists),
+                    "tasks" => {
+                        let list_id = components.next()?.to_str()?;
+                        Some(StorageChange::Tasks(list_id.to_string()))
+                    }
+                    _ => None,
+                }
+            }
+            Provider::Sqlite => {
+                let name = relative.to_str()?;
+                (name == "tasks.db" || name == "tasks.db-wal").then_some(StorageChange::All)
+            }
+            Provider::CalDav => {
+                // The local copy of the server, written by syncing as well.
+                (relative == Path::new("caldav/cache.ron")).then_some(StorageChange::All)
+            }
+            Provider::TodoTxt => {
+                // What todo.txt has no room for, such as notes and the trash.
+                (relative == Path::new("todo-txt/state.ron")).then_some(StorageChange::All)
+            }
+        }
+    }
+}
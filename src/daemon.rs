@@ -0,0 +1,115 @@
+use std::error::Error;
+
+use cosmic::iced::futures;
+use cosmic::Application;
+use tasks_core::dbus::{Signal, TasksServer};
+use tasks_core::reminders::{ReminderEvent, ReminderScheduler};
+use tasks_core::service::TaskService;
+use tokio::sync::mpsc;
+
+use crate::app::config::TasksConfig;
+use crate::app::localize::set_localization;
+use crate::app::settings::set_logger;
+use crate::app::Tasks;
+use crate::fl;
+
+/// How long to wait before trying again when reminders could not be read.
+const RETRY: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Runs `tasks --daemon`: posts reminders and due date alerts without a window,
+/// and serves the tasks on D-Bus for other applications.
+///
+/// The daemon is started with the session from the autostart entry. It shares
+/// the reminder and service names on the session bus with the app, so
+/// whichever of the two started first owns them and the other one takes over
+/// when it exits.
+pub fn run() {
+    set_localization();
+    set_logger();
+
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            log::error!("failed to start the reminder daemon: {err}");
+            return;
+        }
+    };
+    if let Err(err) = runtime.block_on(schedule()) {
+        log::error!("reminder daemon stopped: {err}");
+    }
+}
+
+async fn schedule() -> Result<(), Box<dyn Error>> {
+    let mut provider = TasksConfig::config().provider;
+    let (sender, mut changes) = mpsc::unbounded_channel();
+    // The watcher stops when it is dropped, so it has to live as long as the daemon.
+    let _watcher = TasksConfig::config_handler().and_then(|config| {
+        config
+            .watch(move |_, _| {
+                let _ = sender.send(());
+            })
+            .ok()
+    });
+
+    // Changes made through the service only need announcing, which it does itself.
+    let server = match TasksServer::session(TaskService::new(Tasks::APP_ID, provider)).await {
+        Ok((server, _)) => Some(server),
+        Err(err) => {
+            log::warn!("the D-Bus service is not available: {err}");
+            None
+        }
+    };
+    let (services, receiver) = futures::channel::mpsc::unbounded();
+    let mut scheduler = ReminderScheduler::session(TaskService::new(Tasks::APP_ID, provider))
+        .await?
+        .with_actions(fl!("complete"), fl!("snooze"))
+        .with_services(receiver);
+    log::info!("posting reminders");
+
+    // Switching providers runs on the side, as waiting for a reminder is not
+    // something to interrupt: one being posted or handled would be lost.
+    let switched = server.clone();
+    tokio::spawn(async move {
+        while let Some(()) = changes.recv().await {
+            let config = TasksConfig::config();
+            if config.provider == provider {
+                continue;
+            }
+            provider = config.provider;
+            let _ = services.unbounded_send(TaskService::new(Tasks::APP_ID, provider));
+            if let Some(server) = &switched {
+                let service = TaskService::new(Tasks::APP_ID, provider);
+                if let Err(err) = server.set_service(service).await {
+                    log::warn!("failed to switch the D-Bus service: {err}");
+                }
+            }
+        }
+    });
+
+    loop {
+        match scheduler.next().await {
+            Ok(event) => {
+                log::debug!("reminder: {event:?}");
+                if let ReminderEvent::Completed(task) | ReminderEvent::Snoozed(task) = event {
+                    announce(&server, Signal::TaskUpdated(task)).await;
+                }
+            }
+            Err(err) => {
+                log::error!("failed to process reminders: {err}");
+                tokio::time::sleep(RETRY).await;
+            }
+        }
+    }
+}
+
+/// Tells other applications about a change the daemon made.
+async fn announce(server: &Option<TasksServer>, signal: Signal) {
+    if let Some(server) = server {
+        if let Err(err) = server.emit(signal).await {
+            log::warn!("failed to announce change: {err}");
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::app::icon_cache::IconCache;
-use chrono::{NaiveDate, TimeZone, Utc};
+use chrono::{DateTime, Datelike, Days, Duration, Local, NaiveDate, NaiveTime, TimeZone, Utc};
 use cosmic::iced::{Alignment, Length};
 use cosmic::iced_widget::row;
 use cosmic::widget::segmented_button;
@@ -7,6 +7,7 @@ use cosmic::widget::segmented_button::Entity;
 use cosmic::{theme, widget, Element};
 use slotmap::{DefaultKey, SecondaryMap, SlotMap};
 use tasks_core::models::priority::Priority;
+use tasks_core::models::recurrence::Day;
 use tasks_core::models::status::Status;
 use tasks_core::models::task::Task;
 
@@ -19,6 +20,7 @@ pub struct Details {
     pub subtasks: SlotMap<DefaultKey, Task>,
     pub editing: SecondaryMap<DefaultKey, bool>,
     pub sub_task_input_ids: SecondaryMap<DefaultKey, widget::Id>,
+    reminder_options: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -36,6 +38,8 @@ pub enum Message {
     AddTask,
     OpenCalendarDialog,
     SetDueDate(NaiveDate),
+    SetReminder(usize),
+    ToggleRecurrence(Day),
 }
 
 pub enum Command {
@@ -72,6 +76,12 @@ impl Details {
             subtasks: SlotMap::new(),
             editing: SecondaryMap::new(),
             sub_task_input_ids: SecondaryMap::new(),
+            reminder_options: vec![
+                fl!("reminder-off"),
+                fl!("reminder-in-an-hour"),
+                fl!("reminder-tomorrow"),
+                fl!("reminder-next-week"),
+            ],
         }
     }
 
@@ -121,11 +131,11 @@ impl Details {
             Message::CompleteSubTask(id, completed) => {
                 let task = self.subtasks.get_mut(id);
                 if let Some(task) = task {
-                    task.status = if completed {
+                    task.set_status(if completed {
                         Status::Completed
                     } else {
                         Status::NotStarted
-                    };
+                    });
                 }
             }
             Message::DeleteSubTask(id) => {
@@ -152,6 +162,17 @@ impl Details {
             Message::OpenCalendarDialog => {
                 commands.push(Command::OpenCalendarDialog);
             }
+            Message::SetReminder(index) => {
+                if let Some(task) = &mut self.task {
+                    task.reminder_date = reminder_preset(index);
+                }
+            }
+            Message::ToggleRecurrence(day) => {
+                if let Some(task) = &mut self.task {
+                    let repeats = task.recurrence.contains(day);
+                    task.recurrence.set(day, !repeats);
+                }
+            }
             Message::SetDueDate(date) => {
                 let tz = Utc::now().timezone();
                 if let Some(task) = &mut self.task {
@@ -252,6 +273,31 @@ impl Details {
                             .on_press(Message::OpenCalendarDialog),
                         ),
                     )
+                    .add(
+                        widget::settings::item::builder(fl!("reminder"))
+                            .description(
+                                task.reminder_date
+                                    .map(|date| {
+                                        date.with_timezone(&Local)
+                                            .format("%m-%d-%Y %H:%M")
+                                            .to_string()
+                                    })
+                                    .unwrap_or_else(|| fl!("no-reminder")),
+                            )
+                            .control(widget::dropdown(
+                                &self.reminder_options,
+                                task.reminder_date.is_none().then_some(0),
+                                Message::SetReminder,
+                            )),
+                    )
+                    .add(
+                        widget::column::with_children(vec![
+                            widget::text::body(fl!("repeat")).into(),
+                            self.recurrence_picker(task),
+                        ])
+                        .spacing(spacing.space_xxs)
+                        .padding([0, 15, 0, 15]),
+                    )
                     .add(
                         widget::column::with_children(vec![
                             widget::text::body(fl!("notes")).into(),
@@ -273,6 +319,33 @@ impl Details {
             .into()
     }
 
+    fn recurrence_picker(&self, task: &Task) -> Element<Message> {
+        let spacing = theme::active().cosmic().spacing;
+        let buttons: Vec<Element<Message>> = Day::all()
+            .into_iter()
+            .map(|day| {
+                let label = match day {
+                    Day::Monday => fl!("monday"),
+                    Day::Tuesday => fl!("tuesday"),
+                    Day::Wednesday => fl!("wednesday"),
+                    Day::Thursday => fl!("thursday"),
+                    Day::Friday => fl!("friday"),
+                    Day::Saturday => fl!("saturday"),
+                    Day::Sunday => fl!("sunday"),
+                };
+                let button = if task.recurrence.contains(day) {
+                    widget::button::suggested(label)
+                } else {
+                    widget::button::standard(label)
+                };
+                button.on_press(Message::ToggleRecurrence(day)).into()
+            })
+            .collect();
+        widget::row::with_children(buttons)
+            .spacing(spacing.space_xxs)
+            .into()
+    }
+
     fn sub_task_input(&self) -> Element<Message> {
         let spacing = theme::active().cosmic().spacing;
 
@@ -294,3 +367,22 @@ impl Details {
         .into()
     }
 }
+
+/// The reminder time for an entry of the reminder dropdown: off, in an hour,
+/// tomorrow morning, or next Monday morning.
+fn reminder_preset(index: usize) -> Option<DateTime<Utc>> {
+    let now = Local::now();
+    let morning = NaiveTime::from_hms_opt(9, 0, 0)?;
+    let date = match index {
+        1 => return Some(Utc::now() + Duration::hours(1)),
+        2 => now.date_naive().checked_add_days(Days::new(1))?,
+        3 => now.date_naive().checked_add_days(Days::new(
+            7 - u64::from(now.weekday().num_days_from_monday()),
+        ))?,
+        _ => return None,
+    };
+    Local
+        .from_local_datetime(&date.and_time(morning))
+        .earliest()
+        .map(|date| date.with_timezone(&Utc))
+}
@@ -1,9 +1,17 @@
 mod app;
 mod content;
+mod daemon;
 mod details;
+mod search;
 mod todo;
+mod trash;
 
 pub fn main() -> cosmic::iced::Result {
+    if std::env::args().any(|arg| arg == "--daemon") {
+        daemon::run();
+        return Ok(());
+    }
+
     let (settings, flags) = app::settings::init();
     cosmic::app::run::<app::Tasks>(settings, flags)
 }
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::app::icon_cache::IconCache;
+use cosmic::iced::alignment::{Horizontal, Vertical};
+use cosmic::iced::{Alignment, Length};
+use cosmic::{theme, widget, Apply, Element};
+use tasks_core::models::list::List;
+use tasks_core::models::task::Task;
+use tasks_core::search::{SearchField, SearchResult};
+
+use crate::fl;
+
+pub struct Search {
+    results: Vec<SearchResult>,
+    lists: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    SetResults(Vec<SearchResult>),
+    SetLists(Vec<List>),
+    Open(String),
+}
+
+pub enum Command {
+    Open(Task),
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Self {
+            results: Vec::new(),
+            lists: Vec::new(),
+        }
+    }
+
+    fn empty(&self) -> Element<Message> {
+        widget::container(
+            widget::column::with_children(vec![
+                IconCache::get("system-search-symbolic", 56).into(),
+                widget::text::title1(fl!("no-results")).into(),
+                widget::text(fl!("no-results-suggestion")).into(),
+            ])
+            .spacing(10)
+            .align_items(Alignment::Center),
+        )
+        .align_y(Vertical::Center)
+        .align_x(Horizontal::Center)
+        .height(Length::Fill)
+        .width(Length::Fill)
+        .into()
+    }
+
+    pub fn update(&mut self, message: Message) -> Vec<Command> {
+        let mut commands = Vec::new();
+        match message {
+            Message::SetResults(results) => self.results = results,
+            Message::SetLists(lists) => {
+                self.lists = lists
+                    .into_iter()
+                    .map(|list| {
+                        let icon = list
+                            .icon
+                            .clone()
+                            .unwrap_or(emojis::get_by_shortcode("pencil").unwrap().to_string());
+                        (list.id().clone(), format!("{} {}", icon, list.name))
+                    })
+                    .collect();
+            }
+            Message::Open(id) => {
+                if let Some(result) = self.results.iter().find(|result| result.task.id() == &id) {
+                    commands.push(Command::Open(result.task.clone()));
+                }
+            }
+        }
+        commands
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let spacing = theme::active().cosmic().spacing;
+
+        if self.results.is_empty() {
+            return self.empty();
+        }
+
+        // Group the results by list, in the order the lists appear in the navigation.
+        let mut groups: HashMap<&str, Vec<&SearchResult>> = HashMap::new();
+        for result in &self.results {
+            groups
+                .entry(result.task.parent.as_str())
+                .or_default()
+                .push(result);
+        }
+
+        let mut column = widget::column::with_capacity(self.lists.len())
+            .spacing(spacing.space_s)
+            .padding([spacing.space_none, spacing.space_xxs]);
+
+        for (list_id, list_name) in &self.lists {
+            let Some(results) = groups.get(list_id.as_str()) else {
+                continue;
+            };
+
+            let mut items = widget::list::list_column()
+                .style(theme::Container::ContextDrawer)
+                .spacing(spacing.space_xxxs);
+
+            for result in results {
+                let mut details = widget::column::with_capacity(result.matches.len() + 1);
+                let title_ranges = result
+                    .matches
+                    .iter()
+                    .find(|m| m.field == SearchField::Title)
+                    .map(|m| m.ranges.as_slice())
+                    .unwrap_or_default();
+                details = details.push(highlight(&result.task.title, title_ranges));
+                for found in result
+                    .matches
+                    .iter()
+                    .filter(|m| m.field != SearchField::Title)
+                {
+                    let label = match found.field {
+                        SearchField::Title => fl!("title"),
+                        SearchField::Tag => fl!("tag"),
+                        SearchField::SubTask => fl!("sub-task"),
+                        SearchField::Notes => fl!("notes"),
+                    };
+                    details = details.push(
+                        widget::row::with_capacity(2)
+                            .spacing(spacing.space_xxs)
+                            .push(widget::text::caption(label))
+                            .push(highlight(&found.text, &found.ranges)),
+                    );
+                }
+
+                let row = widget::button(details.width(Length::Fill))
+                    .style(theme::Button::Text)
+                    .padding([spacing.space_xxs, spacing.space_xxs])
+                    .width(Length::Fill)
+                    .on_press(Message::Open(result.task.id().clone()));
+
+                items = items.add(row);
+            }
+
+            column = column
+                .push(widget::text::heading(list_name.as_str()))
+                .push(items);
+        }
+
+        column
+            .apply(widget::container)
+            .height(Length::Shrink)
+            .apply(widget::scrollable)
+            .height(Length::Fill)
+            .into()
+    }
+}
+
+/// A line of text with the matched ranges drawn in the accent color.
+fn highlight<'a>(text: &'a str, ranges: &[Range<usize>]) -> Element<'a, Message> {
+    let mut row = widget::row::with_capacity(ranges.len() * 2 + 1);
+    let mut position = 0;
+    for range in ranges {
+        if range.start > position {
+            row = row.push(widget::text(&text[position..range.start]));
+        }
+        row = row.push(widget::text(&text[range.clone()]).style(theme::Text::Accent));
+        position = range.end;
+    }
+    if position < text.len() {
+        row = row.push(widget::text(&text[position..]));
+    }
+    row.into()
+}
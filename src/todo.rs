@@ -1,8 +1,21 @@
-use crate::app::markdown::Markdown;
+use crate::app::history::Change;
+use crate::fl;
+use chrono::{DateTime, Utc};
+use cli_clipboard::{ClipboardContext, ClipboardProvider};
+use rfd::AsyncFileDialog;
 use std::error::Error;
+use std::path::{Path, PathBuf};
+use tasks_core::ical;
+use tasks_core::import::{self, ImportedList};
+use tasks_core::markdown::{self, Detail};
+use tasks_core::models::diagnostic::Diagnostic;
 use tasks_core::models::list::List;
+use tasks_core::models::query::TaskQuery;
+use tasks_core::models::smart_list::SmartList;
 use tasks_core::models::task::Task;
+use tasks_core::search::SearchIndex;
 use tasks_core::service::TaskService;
+use tasks_core::todo_txt;
 
 pub async fn update_list(list: List, service: TaskService) -> Result<(), Box<dyn Error>> {
     if let Some(mut service) = service.get_service() {
@@ -11,11 +24,21 @@ pub async fn update_list(list: List, service: TaskService) -> Result<(), Box<dyn
     Ok(())
 }
 
-pub async fn delete_list(id: String, service: TaskService) -> Result<(), Box<dyn Error>> {
+/// Deletes a list and returns the tasks it held, including the ones in the trash.
+pub async fn delete_list(id: String, service: TaskService) -> Result<Vec<Task>, Box<dyn Error>> {
     if let Some(mut service) = service.get_service() {
+        let mut tasks = service.get_tasks_from_list(id.clone()).await?;
+        tasks.extend(
+            service
+                .get_deleted_tasks()
+                .await?
+                .into_iter()
+                .filter(|task| task.parent == id),
+        );
         service.delete_list(id).await?;
+        return Ok(tasks);
     }
-    Ok(())
+    Ok(vec![])
 }
 
 pub async fn create_list(list: List, service: TaskService) -> Result<List, Box<dyn Error>> {
@@ -33,6 +56,16 @@ pub async fn create_task(task: Task, service: TaskService) -> Result<(), Box<dyn
     Ok(())
 }
 
+pub async fn create_next_occurrence(
+    task: Task,
+    service: TaskService,
+) -> Result<Option<Task>, Box<dyn Error>> {
+    if let Some(mut service) = service.get_service() {
+        return Ok(service.create_next_occurrence(&task).await?);
+    }
+    Ok(None)
+}
+
 pub async fn fetch_lists(service: TaskService) -> Result<Vec<List>, Box<dyn Error>> {
     if let Some(mut service) = service.get_service() {
         let lists = service.get_lists().await?;
@@ -52,9 +85,37 @@ pub async fn fetch_tasks(
     Ok(vec![])
 }
 
-pub async fn update_task(task: Task, service: TaskService) -> Result<(), Box<dyn Error>> {
+pub async fn fetch_smart_list(
+    smart_list: SmartList,
+    service: TaskService,
+) -> Result<Vec<Task>, Box<dyn Error>> {
+    if let Some(mut service) = service.get_service() {
+        let tasks = service.query(&TaskQuery::default()).await?;
+        return Ok(tasks
+            .into_iter()
+            .filter(|task| smart_list.matches(task))
+            .collect());
+    }
+    Ok(vec![])
+}
+
+pub async fn build_search_index(service: TaskService) -> Result<SearchIndex, Box<dyn Error>> {
     if let Some(mut service) = service.get_service() {
-        service.update_task(task).await?;
+        let tasks = service.query(&TaskQuery::default()).await?;
+        return Ok(SearchIndex::new(tasks));
+    }
+    Ok(SearchIndex::default())
+}
+
+/// Saves a task edited from the copy that was last modified at `expected`,
+/// failing with a conflict if the stored task changed since.
+pub async fn update_task(
+    task: Task,
+    expected: DateTime<Utc>,
+    service: TaskService,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(mut service) = service.get_service() {
+        service.update_task_if_unchanged(task, expected).await?;
     }
     Ok(())
 }
@@ -70,8 +131,219 @@ pub async fn delete_task(
     Ok(())
 }
 
-pub fn export_list(list: &List, tasks: &[Task]) -> String {
-    let markdown = list.markdown();
-    let tasks_markdown: String = tasks.iter().map(Markdown::markdown).collect();
-    format!("{markdown}\n{tasks_markdown}")
+pub async fn fetch_deleted_tasks(service: TaskService) -> Result<Vec<Task>, Box<dyn Error>> {
+    if let Some(mut service) = service.get_service() {
+        let tasks = service.get_deleted_tasks().await?;
+        return Ok(tasks);
+    }
+    Ok(vec![])
+}
+
+pub async fn restore_task(
+    list_id: String,
+    task_id: String,
+    service: TaskService,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(mut service) = service.get_service() {
+        service.restore_task(list_id, task_id).await?;
+    }
+    Ok(())
+}
+
+pub async fn purge_task(
+    list_id: String,
+    task_id: String,
+    service: TaskService,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(mut service) = service.get_service() {
+        service.purge_task(list_id, task_id).await?;
+    }
+    Ok(())
+}
+
+pub async fn purge_trash(
+    before: DateTime<Utc>,
+    service: TaskService,
+) -> Result<usize, Box<dyn Error>> {
+    if let Some(mut service) = service.get_service() {
+        let purged = service.purge_deleted_tasks(before).await?;
+        return Ok(purged);
+    }
+    Ok(0)
+}
+
+pub async fn apply_change(change: Change, service: TaskService) -> Result<(), Box<dyn Error>> {
+    let Some(mut service) = service.get_service() else {
+        return Ok(());
+    };
+    match change {
+        Change::CreateTask(task) => {
+            service.create_task(task).await?;
+        }
+        Change::PurgeTask(task) => {
+            service
+                .purge_task(task.parent.clone(), task.id().clone())
+                .await?;
+        }
+        Change::DeleteTask(task) => {
+            service
+                .delete_task(task.parent.clone(), task.id().clone())
+                .await?;
+        }
+        Change::RestoreTask(task) => {
+            service
+                .restore_task(task.parent.clone(), task.id().clone())
+                .await?;
+        }
+        Change::UpdateTask { after, .. } => service.update_task(*after).await?,
+        Change::CreateList { list, tasks } => {
+            service.create_list(list).await?;
+            for task in tasks {
+                service.create_task(task).await?;
+            }
+        }
+        Change::DeleteList { list, .. } => service.delete_list(list.id().clone()).await?,
+        Change::UpdateList { after, .. } => service.update_list(after).await?,
+    }
+    Ok(())
+}
+
+pub async fn fetch_diagnostics(service: TaskService) -> Result<Vec<Diagnostic>, Box<dyn Error>> {
+    if let Some(mut service) = service.get_service() {
+        let diagnostics = service.diagnostics().await?;
+        return Ok(diagnostics);
+    }
+    Ok(vec![])
+}
+
+pub async fn acknowledge_diagnostics(
+    diagnostics: Vec<Diagnostic>,
+    service: TaskService,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(mut service) = service.get_service() {
+        service.acknowledge_diagnostics(&diagnostics).await?;
+    }
+    Ok(())
+}
+
+pub fn export_list(list: &List, tasks: &[Task], detail: Detail) -> String {
+    markdown::export_list(list, tasks, detail)
+}
+
+/// Every list and its tasks.
+pub async fn export_all(service: TaskService) -> Result<Vec<(List, Vec<Task>)>, Box<dyn Error>> {
+    let mut exported = vec![];
+    if let Some(mut service) = service.get_service() {
+        for list in service.get_lists().await? {
+            let tasks = service.get_tasks_from_list(list.id().clone()).await?;
+            exported.push((list, tasks));
+        }
+    }
+    Ok(exported)
+}
+
+/// Saves the tasks of each imported list into `list`, or into a new list for
+/// each when there is none, and returns how many tasks were saved.
+pub async fn import(
+    lists: Vec<ImportedList>,
+    list: Option<List>,
+    service: TaskService,
+) -> Result<usize, Box<dyn Error>> {
+    let Some(mut service) = service.get_service() else {
+        return Err("No service found".into());
+    };
+    let mut saved = 0;
+    for imported in lists {
+        let list = match &list {
+            Some(list) => list.clone(),
+            None => {
+                let name = imported.name.unwrap_or_else(|| fl!("imported-list"));
+                let mut list = List::new(&name);
+                list.description = imported.description.unwrap_or_default();
+                service.create_list(list).await?
+            }
+        };
+        saved += import::save(service.as_mut(), &list, imported.tasks).await?;
+    }
+    Ok(saved)
+}
+
+/// Lists and their tasks in the format of the file at `path`: todo.txt for
+/// `.txt`, Markdown with `detail` for `.md`, and iCalendar otherwise.
+fn export(path: &Path, lists: &[(List, Vec<Task>)], detail: Detail) -> String {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("txt") => todo_txt::export(lists),
+        Some("md") => lists
+            .iter()
+            .map(|(list, tasks)| markdown::export_list(list, tasks, detail))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => ical::export_lists(lists),
+    }
+}
+
+/// Asks where to save `lists`, suggesting `name`, and returns where they were
+/// saved, or `None` if the user cancelled. Markdown files are written with
+/// `detail`.
+pub async fn save_file(
+    name: String,
+    lists: Vec<(List, Vec<Task>)>,
+    detail: Detail,
+) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let Some(file) = AsyncFileDialog::new()
+        .add_filter(fl!("icalendar"), &["ics"])
+        .add_filter(fl!("todo-txt"), &["txt"])
+        .add_filter(fl!("markdown"), &["md"])
+        .set_file_name(name)
+        .save_file()
+        .await
+    else {
+        return Ok(None);
+    };
+    let path = file.path().to_path_buf();
+    std::fs::write(&path, export(&path, &lists, detail))?;
+    Ok(Some(path))
+}
+
+/// Asks for a folder and writes each list into it as a Markdown file with
+/// every field, and returns the folder, or `None` if the user cancelled.
+pub async fn save_folder(lists: Vec<(List, Vec<Task>)>) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let Some(folder) = AsyncFileDialog::new().pick_folder().await else {
+        return Ok(None);
+    };
+    let path = folder.path().to_path_buf();
+    markdown::export_folder(&path, &lists, Detail::Full)?;
+    Ok(Some(path))
+}
+
+/// Asks for an iCalendar, todo.txt or Markdown file and reads it, or
+/// returns `None` if the user cancelled.
+pub async fn open_file() -> Result<Option<Vec<ImportedList>>, Box<dyn Error>> {
+    let Some(file) = AsyncFileDialog::new()
+        .add_filter(fl!("icalendar"), &["ics"])
+        .add_filter(fl!("todo-txt"), &["txt"])
+        .add_filter(fl!("markdown"), &["md", "markdown"])
+        .pick_file()
+        .await
+    else {
+        return Ok(None);
+    };
+    let contents = String::from_utf8(file.read().await)?;
+    Ok(Some(import::read(&contents, Some(file.path()))?))
+}
+
+/// Reads the tasks pasted from the clipboard.
+pub fn paste() -> Result<Vec<ImportedList>, Box<dyn Error>> {
+    let contents = ClipboardContext::new()?.get_contents()?;
+    Ok(import::read(&contents, None)?)
+}
+
+/// Asks for a todo.txt file, or returns `None` if the user cancelled.
+pub async fn pick_todo_txt(title: String) -> Option<PathBuf> {
+    AsyncFileDialog::new()
+        .set_title(title)
+        .add_filter(fl!("todo-txt"), &["txt"])
+        .pick_file()
+        .await
+        .map(|file| file.path().to_path_buf())
 }
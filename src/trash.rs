@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use crate::app::icon_cache::IconCache;
+use cosmic::iced::alignment::{Horizontal, Vertical};
+use cosmic::iced::{Alignment, Length};
+use cosmic::{theme, widget, Apply, Element};
+use tasks_core::models::list::List;
+use tasks_core::models::task::Task;
+
+use crate::fl;
+
+pub struct Trash {
+    tasks: Vec<Task>,
+    lists: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    SetItems(Vec<Task>),
+    SetLists(Vec<List>),
+    Restore(String),
+    Purge(String),
+    Empty,
+}
+
+pub enum Command {
+    Restore(Task),
+    Purge(Task),
+    Empty,
+}
+
+impl Trash {
+    pub fn new() -> Self {
+        Self {
+            tasks: Vec::new(),
+            lists: HashMap::new(),
+        }
+    }
+
+    fn header(&self) -> Element<Message> {
+        let spacing = theme::active().cosmic().spacing;
+        let empty_button = widget::button(IconCache::get("user-trash-full-symbolic", 18))
+            .style(theme::Button::Destructive)
+            .padding(spacing.space_xxs)
+            .on_press_maybe((!self.tasks.is_empty()).then_some(Message::Empty));
+
+        widget::row::with_capacity(2)
+            .align_items(Alignment::Center)
+            .spacing(spacing.space_s)
+            .padding([spacing.space_none, spacing.space_xxs])
+            .push(widget::text::title3(fl!("trash")).width(Length::Fill))
+            .push(empty_button)
+            .into()
+    }
+
+    fn empty(&self) -> Element<Message> {
+        let spacing = theme::active().cosmic().spacing;
+
+        let container = widget::container(
+            widget::column::with_children(vec![
+                IconCache::get("user-trash-full-symbolic", 56).into(),
+                widget::text::title1(fl!("trash-empty")).into(),
+                widget::text(fl!("trash-empty-suggestion")).into(),
+            ])
+            .spacing(10)
+            .align_items(Alignment::Center),
+        )
+        .align_y(Vertical::Center)
+        .align_x(Horizontal::Center)
+        .height(Length::Fill)
+        .width(Length::Fill);
+
+        widget::column::with_capacity(2)
+            .spacing(spacing.space_xxs)
+            .push(self.header())
+            .push(container)
+            .into()
+    }
+
+    pub fn update(&mut self, message: Message) -> Vec<Command> {
+        let mut commands = Vec::new();
+        match message {
+            Message::SetItems(mut tasks) => {
+                tasks.sort_by(|a, b| b.deletion_date().cmp(a.deletion_date()));
+                self.tasks = tasks;
+            }
+            Message::SetLists(lists) => {
+                self.lists = lists
+                    .into_iter()
+                    .map(|list| {
+                        let icon = list
+                            .icon
+                            .clone()
+                            .unwrap_or(emojis::get_by_shortcode("pencil").unwrap().to_string());
+                        (list.id().clone(), format!("{} {}", icon, list.name))
+                    })
+                    .collect();
+            }
+            Message::Restore(id) => {
+                if let Some(index) = self.tasks.iter().position(|task| task.id() == &id) {
+                    commands.push(Command::Restore(self.tasks.remove(index)));
+                }
+            }
+            Message::Purge(id) => {
+                if let Some(index) = self.tasks.iter().position(|task| task.id() == &id) {
+                    commands.push(Command::Purge(self.tasks.remove(index)));
+                }
+            }
+            Message::Empty => {
+                self.tasks.clear();
+                commands.push(Command::Empty);
+            }
+        }
+        commands
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let spacing = theme::active().cosmic().spacing;
+
+        if self.tasks.is_empty() {
+            return self.empty();
+        }
+
+        let mut items = widget::list::list_column()
+            .style(theme::Container::ContextDrawer)
+            .spacing(spacing.space_xxxs)
+            .padding([spacing.space_none, spacing.space_xxs]);
+
+        for task in &self.tasks {
+            let list_name = self.lists.get(&task.parent).cloned().unwrap_or_default();
+            let deleted = task
+                .deletion_date()
+                .map(|date| date.format("%m-%d-%Y").to_string())
+                .unwrap_or_default();
+
+            let restore_button = widget::button(IconCache::get("edit-undo-symbolic", 18))
+                .padding(spacing.space_xxs)
+                .style(theme::Button::Standard)
+                .on_press(Message::Restore(task.id().clone()));
+
+            let purge_button = widget::button(IconCache::get("user-trash-full-symbolic", 18))
+                .padding(spacing.space_xxs)
+                .style(theme::Button::Destructive)
+                .on_press(Message::Purge(task.id().clone()));
+
+            let row = widget::row::with_capacity(3)
+                .align_items(Alignment::Center)
+                .spacing(spacing.space_xxs)
+                .padding([spacing.space_xxxs, spacing.space_xxs])
+                .push(
+                    widget::column::with_capacity(2)
+                        .push(widget::text(&task.title))
+                        .push(widget::text::caption(fl!(
+                            "deleted-from",
+                            list = list_name,
+                            date = deleted
+                        )))
+                        .width(Length::Fill),
+                )
+                .push(restore_button)
+                .push(purge_button);
+
+            items = items.add(row);
+        }
+
+        widget::column::with_capacity(2)
+            .spacing(spacing.space_xxs)
+            .push(self.header())
+            .push(items)
+            .apply(widget::container)
+            .height(Length::Shrink)
+            .apply(widget::scrollable)
+            .height(Length::Fill)
+            .into()
+    }
+}